@@ -0,0 +1,62 @@
+//! Compare the time to parse many small documents with the vectors of the parser and with
+//! an arena reset between the documents
+//!
+//! ```text
+//! cargo run --release --example arena_bench --features arena [documents]
+//! ```
+
+use s_expr::{Arena, Parser};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const DOCUMENT: &str = r#"
+(define (fact n)
+  (if (<= n 1)
+      1
+      (* n (fact (- n 1)))))
+[config {name "bench"} {size 0x10} {ratio 0.25} {key #00ff_00ff#}]
+(list (a b) (c d) (e f) (g h) (i j))
+"#;
+
+fn vec_parse(documents: usize) -> (Duration, usize) {
+    let start = Instant::now();
+    let mut count = 0;
+    for _ in 0..documents {
+        let mut parser = Parser::new(black_box(DOCUMENT));
+        while let Some(el) = parser.next().expect("valid document") {
+            count += black_box(el).span.end.offset;
+        }
+    }
+    (start.elapsed(), count)
+}
+
+fn arena_parse(documents: usize) -> (Duration, usize) {
+    let start = Instant::now();
+    let mut count = 0;
+    let mut arena = Arena::new();
+    for _ in 0..documents {
+        let elements = Parser::new(black_box(DOCUMENT))
+            .parse_all_in(&arena)
+            .expect("valid document");
+        count += elements.iter().map(|el| el.span.end.offset).sum::<usize>();
+        arena.reset();
+    }
+    (start.elapsed(), count)
+}
+
+fn main() {
+    let documents = std::env::args()
+        .nth(1)
+        .map(|n| n.parse().expect("number of documents"))
+        .unwrap_or(100_000);
+    let (vec_time, vec_count) = vec_parse(documents);
+    let (arena_time, arena_count) = arena_parse(documents);
+    assert_eq!(vec_count, arena_count);
+    println!("{} documents", documents);
+    println!("vec:   {:?}", vec_time);
+    println!("arena: {:?}", arena_time);
+    println!(
+        "arena/vec: {:.2}",
+        arena_time.as_secs_f64() / vec_time.as_secs_f64()
+    );
+}
@@ -0,0 +1,177 @@
+//! Parsing of the elements in an arena, with the `arena` feature
+//!
+//! The elements of the groups are slices allocated in a [`bumpalo::Bump`] arena instead of
+//! vectors, so parsing a source allocates nearly nothing on the heap, and the whole tree is freed
+//! at once with the arena, e.g. to parse many small documents with one arena reset between them:
+//!
+//! ```
+//! use s_expr::{Arena, ArenaElement, Parser};
+//!
+//! let mut arena = Arena::new();
+//! for source in ["(a (b c))", "[d] e"] {
+//!     let elements = Parser::new(source).parse_all_in(&arena).unwrap();
+//!     assert!(matches!(elements[0].inner, ArenaElement::Group(_, _)));
+//!     arena.reset();
+//! }
+//! ```
+//!
+//! The arena doesn't run the destructors of the elements, which own nothing, as the atoms
+//! parsed borrow the source.
+
+use super::data::{Atom, GroupKind};
+use super::loc::{Span, Spanned};
+use super::parser::{Element, Parser, ParserError};
+use super::tokenizer::Token;
+
+/// Arena allocating the elements of [`Parser::parse_all_in`]
+pub use bumpalo::Bump as Arena;
+
+/// Element of S-Expr allocated in an arena, like [`Element`] with the elements of the groups in
+/// an arena slice
+#[derive(Debug)]
+pub enum ArenaElement<'arena, 'a> {
+    Group(GroupKind, &'arena [SpannedArenaElement<'arena, 'a>]),
+    Atom(Atom<'a>),
+    Comment(&'a str),
+}
+
+/// Spanned element allocated in an arena
+pub type SpannedArenaElement<'arena, 'a> = Spanned<ArenaElement<'arena, 'a>>;
+
+impl<'arena, 'a> ArenaElement<'arena, 'a> {
+    /// Get the atom, or None if the element is a group or a comment
+    pub fn atom(&self) -> Option<&Atom<'a>> {
+        match self {
+            ArenaElement::Atom(atom) => Some(atom),
+            _ => None,
+        }
+    }
+
+    /// Get the elements of a group of the given kind, or None if the element is not such a group
+    pub fn group(&self, kind: GroupKind) -> Option<&'arena [SpannedArenaElement<'arena, 'a>]> {
+        match self {
+            ArenaElement::Group(grp, elements) if *grp == kind => Some(elements),
+            _ => None,
+        }
+    }
+
+    /// Get the elements of a parentheses group, or None if the element is not a parentheses group
+    pub fn paren(&self) -> Option<&'arena [SpannedArenaElement<'arena, 'a>]> {
+        self.group(GroupKind::Paren)
+    }
+
+    /// Get a copy of the element as an [`Element`], which allocates the groups
+    pub fn to_element(&self) -> Element<'a> {
+        match self {
+            ArenaElement::Group(grp, elements) => Element::Group(
+                *grp,
+                elements
+                    .iter()
+                    .map(|el| el.as_spanned_ref().map(|el| el.to_element()))
+                    .collect(),
+            ),
+            ArenaElement::Atom(atom) => Element::Atom(atom.clone()),
+            ArenaElement::Comment(comment) => Element::Comment(comment),
+        }
+    }
+}
+
+impl<'a> Parser<'a> {
+    /// Parse all the remaining elements, allocating the groups in the arena
+    ///
+    /// The elements of the open groups are kept on one stack, which is moved to the arena when
+    /// a group closes, so the only allocations outside of the arena are the growths of the stacks.
+    pub fn parse_all_in<'arena>(
+        &mut self,
+        arena: &'arena Arena,
+    ) -> Result<&'arena [SpannedArenaElement<'arena, 'a>], ParserError> {
+        // the elements of all the open groups, and the top level elements at the bottom
+        let mut elements: Vec<SpannedArenaElement<'arena, 'a>> = Vec::new();
+        // the open groups, with the span of their opening token and the index of their first element
+        let mut open: Vec<(GroupKind, Span, usize)> = Vec::new();
+        loop {
+            let tok = match self.tokenizer.next()? {
+                None => match open.last() {
+                    None => break,
+                    Some((grp, _, _)) => return Err(ParserError::UnfinishedGroup(*grp)),
+                },
+                Some(tok) => tok,
+            };
+            let el = match tok.inner {
+                Token::Comment(comment) => ArenaElement::Comment(comment),
+                Token::Atom(atom) => ArenaElement::Atom(atom),
+                Token::Left(grp) => {
+                    open.push((grp, tok.span, elements.len()));
+                    continue;
+                }
+                Token::Right(grp) => {
+                    let (inner_grp, inner_start, first) = open
+                        .pop()
+                        .ok_or(ParserError::UnbalancedEmpty(tok.span.start, grp))?;
+                    let span = inner_start.merge(&tok.span);
+                    if inner_grp != grp {
+                        return Err(ParserError::UnbalancedMismatch {
+                            span,
+                            expected: inner_grp,
+                            got: grp,
+                        });
+                    }
+                    let children = arena.alloc_slice_fill_iter(elements.drain(first..));
+                    elements.push(Spanned::new(span, ArenaElement::Group(grp, children)));
+                    continue;
+                }
+            };
+            elements.push(Spanned::new(tok.span, el));
+        }
+        Ok(arena.alloc_slice_fill_iter(elements.drain(..)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::PROG1;
+    #[cfg(feature = "unicode")]
+    use crate::tests::PROG2;
+
+    #[test]
+    fn same_as_vec_parser() {
+        let arena = Arena::new();
+        let mut sources = vec![PROG1, "", "; only\n", "a (b [c {d ; e\n}]) \"f\" #00#"];
+        #[cfg(feature = "unicode")]
+        sources.push(PROG2);
+        for source in sources {
+            let in_arena = Parser::new(source).parse_all_in(&arena).unwrap();
+            let mut parser = Parser::new(source);
+            let mut count = 0;
+            while let Some(el) = parser.next().unwrap() {
+                assert_eq!(in_arena[count].span, el.span);
+                assert!(in_arena[count].to_element().structural_eq(&el));
+                count += 1;
+            }
+            assert_eq!(in_arena.len(), count);
+        }
+        let el = &Parser::new("(a b)").parse_all_in(&arena).unwrap()[0];
+        let children = el.paren().expect("paren");
+        assert_eq!(children[1].atom().and_then(|a| a.ident()), Some("b"));
+        assert_eq!(children[1].span, Span::on_line(1, 3, 4));
+        assert!(el.group(GroupKind::Bracket).is_none());
+    }
+
+    #[test]
+    fn errors() {
+        let arena = Arena::new();
+        let error = |source: &str| Parser::new(source).parse_all_in(&arena).unwrap_err();
+        for source in ["(a ]", "a }", "[a (b)", "(a \"b"] {
+            let mut parser = Parser::new(source);
+            let expected = loop {
+                match parser.next() {
+                    Ok(Some(_)) => {}
+                    Ok(None) => panic!("no error in {:?}", source),
+                    Err(e) => break e,
+                }
+            };
+            assert_eq!(error(source), expected, "{:?}", source);
+        }
+    }
+}
@@ -0,0 +1,264 @@
+//! Canonical text form of the elements
+//!
+//! The canonical form only depends on the values of the elements, so that it can be
+//! used for hashing or signing:
+//!
+//! * groups are printed on one line, with exactly one space between the elements
+//! * comments are removed
+//! * integral numbers are printed in decimal, without leading zeros or separators
+//! * decimal numbers are printed without separators, leading zeros of the integral part and
+//!   trailing zeros of the fractional part
+//! * bytes are printed in lowercase hexadecimal, without separators
+//! * strings only escape the quote and backslash characters
+//!
+//! The trailing zeros of the decimal numbers can be kept with [`CanonicalConfig::preserve_scale`],
+//! when the number of fractional digits is part of the value, e.g. for measurements.
+
+use super::data::{ADecimal, AStr, Atom};
+use super::parser::Element;
+
+/// Config for the canonical form
+#[derive(Debug, Clone, Default)]
+pub struct CanonicalConfig {
+    /// Keep the trailing zeros of the fractional part of the decimal numbers. Default is set to false
+    pub(crate) preserve_scale: bool,
+}
+
+impl CanonicalConfig {
+    /// Keep the trailing zeros of the fractional part of the decimal numbers, so that `1.50` stays
+    /// `1.50` instead of becoming `1.5`
+    pub fn preserve_scale(mut self, enabled: bool) -> Self {
+        self.preserve_scale = enabled;
+        self
+    }
+}
+
+/// Print an element in its canonical form
+///
+/// Two elements with the same structure and content (as defined by [`Element::structural_eq`])
+/// always have the same canonical form. A comment element has an empty canonical form.
+pub fn to_canonical_string(el: &Element) -> String {
+    to_canonical_string_with(el, &CanonicalConfig::default())
+}
+
+/// Print an element in its canonical form, with the given config
+///
+/// ```
+/// use s_expr::{to_canonical_string_with, CanonicalConfig, Parser};
+///
+/// let el = Parser::new("(1_0.50 0.0)").next().unwrap().unwrap();
+/// let cfg = CanonicalConfig::default().preserve_scale(true);
+/// assert_eq!(to_canonical_string_with(&el.inner, &cfg), "(10.50 0.0)");
+/// ```
+pub fn to_canonical_string_with(el: &Element, cfg: &CanonicalConfig) -> String {
+    let mut out = String::new();
+    element(&mut out, el, cfg);
+    out
+}
+
+fn element(out: &mut String, el: &Element, cfg: &CanonicalConfig) {
+    match el {
+        Element::Comment(_) => {}
+        Element::Atom(atom) => self::atom(out, atom, cfg),
+        Element::Group(grp, elements) => {
+            out.push(grp.open_char());
+            let mut first = true;
+            for e in elements {
+                if let Element::Comment(_) = e.inner {
+                    continue;
+                }
+                if !first {
+                    out.push(' ');
+                }
+                first = false;
+                element(out, &e.inner, cfg);
+            }
+            out.push(grp.close_char());
+        }
+    }
+}
+
+pub(crate) fn atom(out: &mut String, atom: &Atom, cfg: &CanonicalConfig) {
+    match atom {
+        Atom::Ident(ident) => out.push_str(ident),
+        Atom::Integral(num) => {
+            // writing to a String doesn't fail
+            let _ = num.write_normalized(out);
+        }
+        Atom::Decimal(dec) => decimal(out, dec, cfg),
+        Atom::String(astr) => string(out, astr),
+        Atom::Bytes(bytes) => {
+            out.push('#');
+            out.extend(
+                bytes
+                    .0
+                    .chars()
+                    .filter(|c| *c != '_')
+                    .map(|c| c.to_ascii_lowercase()),
+            );
+            out.push('#');
+        }
+    }
+}
+
+fn decimal(out: &mut String, dec: &ADecimal, cfg: &CanonicalConfig) {
+    let integral = dec.integral();
+    let integral = integral.trim_start_matches('0');
+    let fractional = dec.fractional();
+    let fractional = if cfg.preserve_scale {
+        &fractional
+    } else {
+        fractional.trim_end_matches('0')
+    };
+    out.push_str(if integral.is_empty() { "0" } else { integral });
+    out.push('.');
+    out.push_str(if fractional.is_empty() {
+        "0"
+    } else {
+        fractional
+    });
+}
+
+fn string(out: &mut String, astr: &AStr) {
+    // a string with invalid escapes has no known value, so it is kept as is
+    let decoded = match astr.try_decoded() {
+        Err(_) => {
+            out.push('"');
+            out.push_str(&astr.raw_data);
+            out.push('"');
+            return;
+        }
+        Ok(decoded) => decoded,
+    };
+    out.push('"');
+    for c in decoded.chars() {
+        if c == '"' || c == '\\' {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::tests::Rng;
+
+    fn canonical_all(data: &str) -> String {
+        let mut parser = Parser::new(data);
+        let mut out = Vec::new();
+        while let Some(el) = parser.next().expect("parse") {
+            let s = to_canonical_string(&el.inner);
+            if !s.is_empty() {
+                out.push(s);
+            }
+        }
+        out.join(" ")
+    }
+
+    #[test]
+    fn canonical_form() {
+        let data = r#"(define  x ; comment
+            [0x1_0 0b11 007 12_345 #DE_ad#]
+            {"a\"b\\c\n\x41" 0010.2500 1.})"#;
+        assert_eq!(
+            canonical_all(data),
+            r#"(define x [16 3 7 12345 #dead#] {"a\"b\\c
+A" 10.25 1.0})"#
+        );
+    }
+
+    #[test]
+    fn canonical_preserve_scale() {
+        let cfg = CanonicalConfig::default().preserve_scale(true);
+        let canonical = |data: &str| {
+            let el = Parser::new(data).next().expect("parse").expect("element");
+            to_canonical_string_with(&el.inner, &cfg)
+        };
+        assert_eq!(canonical("1.50"), "1.50");
+        assert_eq!(canonical("001.500"), "1.500");
+        assert_eq!(canonical("[1.0 0.00 10.0]"), "[1.0 0.00 10.0]");
+        assert_eq!(canonical("1."), "1.0");
+        assert_eq!(canonical_all("1.50"), "1.5");
+
+        let printed = canonical("(m 1.50)");
+        let el = Parser::new(&printed)
+            .next()
+            .expect("parse")
+            .expect("element");
+        let dec = el.paren().expect("paren")[1].inner.atom().expect("atom");
+        let Atom::Decimal(dec) = dec else {
+            panic!("not a decimal")
+        };
+        assert_eq!((dec.scale(), dec.trailing_zeros()), (2, 1));
+    }
+
+    #[test]
+    fn canonical_large_number() {
+        let data = "0xffffffffffffffffffffffffffffffffff 0b0";
+        assert_eq!(
+            canonical_all(data),
+            "87112285931760246646623899502532662132735 0"
+        );
+    }
+
+    #[test]
+    fn canonical_same_value() {
+        assert_eq!(
+            canonical_all("(a 0x10 #AB#)"),
+            canonical_all("(a  16 ; c\n#ab#)")
+        );
+        assert_eq!(canonical_all("0x10 16 1_6 0b1_0000 0016"), "16 16 16 16 16");
+    }
+
+    // generate a random source of a balanced element
+    fn random_source(rng: &mut Rng, out: &mut String, depth: usize) {
+        const ATOMS: &[&str] = &[
+            "abc",
+            "+",
+            "0x1f",
+            "0b1_0",
+            "00_12",
+            "1.50",
+            "#00aB#",
+            "\"x\\\"y\"",
+            "\"\\n\"",
+        ];
+        const GROUPS: &[(char, char)] = &[('(', ')'), ('[', ']'), ('{', '}')];
+        if depth == 0 || rng.next().is_multiple_of(3) {
+            out.push_str(ATOMS[rng.next() as usize % ATOMS.len()]);
+            return;
+        }
+        let (open, close) = GROUPS[rng.next() as usize % GROUPS.len()];
+        out.push(open);
+        for _ in 0..(rng.next() % 5) {
+            out.push_str(if rng.next().is_multiple_of(4) {
+                " ; comment\n"
+            } else {
+                "  "
+            });
+            random_source(rng, out, depth - 1);
+        }
+        out.push(close);
+    }
+
+    #[test]
+    fn canonical_fixed_point() {
+        let mut rng = Rng(0xca9);
+        for _ in 0..500 {
+            let mut src = String::new();
+            random_source(&mut rng, &mut src, 4);
+            let el = Parser::new(&src).next().expect("parse").expect("element");
+            let first = to_canonical_string(&el.inner);
+            let reparsed = Parser::new(&first).next().expect("parse").expect("element");
+            assert_eq!(
+                to_canonical_string(&reparsed.inner),
+                first,
+                "source: {}",
+                src
+            );
+        }
+    }
+}
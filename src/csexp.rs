@@ -0,0 +1,694 @@
+//! Canonical S-expressions, the binary form of the S-expressions of SPKI/SDSI
+//!
+//! A canonical S-expression is either an atom, made of its length in decimal, a `:` and its
+//! bytes, e.g. `3:foo`, or a list of S-expressions between parentheses, without any space,
+//! e.g. `(3:foo(3:bar))`. The elements are the same as the elements of the text form:
+//!
+//! * the lists are parentheses groups
+//! * the atoms are bytes, or strings with [`CsexpConfig::strings`]
+//!
+//! The display hints, e.g. the `[4:mime]` of `[4:mime]5:image`, aren't supported and are
+//! reported as [`CsexpError::DisplayHint`], since the elements don't have a place for them.
+//!
+//! The atoms and the groups have spans on the line 1, with the columns and the offsets being
+//! the byte offsets in the data.
+//!
+//! The elements are written in the canonical form with [`to_vec`], each atom being its bytes:
+//!
+//! * the strings are their decoded UTF-8 bytes, and the bytes their decoded bytes
+//! * the idents are their text, and the numbers their canonical text as defined by
+//!   [`to_canonical_string`](crate::to_canonical_string), or the big endian bytes
+//!   of the integral numbers with [`CsexpEncodeConfig::numbers_as_bytes`]
+//!
+//! The canonical form has no comments, and no groups other than the parentheses, so
+//! the comments and the other groups are errors, unless normalized with
+//! [`CsexpEncodeConfig::normalize`].
+//!
+//! The transport form, the base64 of the canonical form between braces, e.g. `{MzphYmM=}` for
+//! `3:abc`, travels through the text channels. It's written with [`to_transport_string`], and
+//! read like the canonical form, the reader detecting the form with the first byte. The spans of
+//! the elements of the transport form are in the decoded canonical form.
+//!
+//! ```
+//! use s_expr::csexp;
+//! use s_expr::to_string;
+//!
+//! let el = csexp::parse(b"(3:foo(0:))").unwrap();
+//! assert_eq!(to_string(&el.to_element()), "(#666f6f# (##))");
+//! ```
+
+use super::canonical::{self, CanonicalConfig};
+use super::data::{ABytes, ANumBase, Atom, AtomBuf, BytesDecodeError, GroupKind};
+use super::escape::EscapeError;
+use super::loc::{Span, Spanned};
+use super::parser::{Element, ElementOwned, SpannedElementOwned};
+use core::fmt;
+
+/// Config for the reading of the canonical S-expressions
+#[derive(Debug, Clone, Default)]
+pub struct CsexpConfig {
+    /// Read the atoms which are valid UTF-8 as strings. Default is set to false
+    pub(crate) strings: bool,
+}
+
+impl CsexpConfig {
+    /// Read the atoms which are valid UTF-8 as strings instead of bytes, the other atoms
+    /// staying bytes
+    pub fn strings(mut self, enabled: bool) -> Self {
+        self.strings = enabled;
+        self
+    }
+}
+
+/// Config for the writing of the canonical S-expressions
+#[derive(Debug, Clone, Default)]
+pub struct CsexpEncodeConfig {
+    /// Write the integral numbers as their big endian bytes. Default is set to false
+    pub(crate) numbers_as_bytes: bool,
+    /// Write the brackets and braces groups as lists and skip the comments. Default is set to false
+    pub(crate) normalize: bool,
+}
+
+impl CsexpEncodeConfig {
+    /// Write the integral numbers as their big endian bytes, with a leading zero byte when the
+    /// high bit is set so that they stay positive in two's complement, instead of their text
+    ///
+    /// The number 0 is the byte 0.
+    pub fn numbers_as_bytes(mut self, enabled: bool) -> Self {
+        self.numbers_as_bytes = enabled;
+        self
+    }
+
+    /// Write the brackets and braces groups as lists, like the parentheses groups, and skip
+    /// the comments, instead of failing with [`CsexpEncodeError::Group`] and
+    /// [`CsexpEncodeError::Comment`]
+    pub fn normalize(mut self, enabled: bool) -> Self {
+        self.normalize = enabled;
+        self
+    }
+}
+
+/// Error of the writing of an element as a canonical S-expression
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CsexpEncodeError {
+    /// Group which isn't a parentheses group, without normalization
+    Group(GroupKind),
+    /// Comment, without normalization
+    Comment,
+    /// String with an invalid escape
+    Escape(EscapeError),
+    /// Bytes literal which doesn't decode
+    Bytes(BytesDecodeError),
+}
+
+impl fmt::Display for CsexpEncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CsexpEncodeError::Group(grp) => write!(f, "{} are not canonical lists", grp),
+            CsexpEncodeError::Comment => f.write_str("comments are not canonical elements"),
+            CsexpEncodeError::Escape(e) => write!(f, "invalid string: {}", e),
+            CsexpEncodeError::Bytes(e) => write!(f, "invalid bytes: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CsexpEncodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CsexpEncodeError::Group(_) | CsexpEncodeError::Comment => None,
+            CsexpEncodeError::Escape(e) => Some(e),
+            CsexpEncodeError::Bytes(e) => Some(e),
+        }
+    }
+}
+
+/// Error of the reading of a canonical S-expression
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CsexpError {
+    /// The data ends before the end of the element, e.g. in a list or in the bytes of an atom
+    UnexpectedEnd,
+    /// Byte which doesn't start an element, or doesn't end the length of an atom, at its offset
+    UnexpectedByte { offset: usize, byte: u8 },
+    /// Length of an atom which isn't canonical, with leading zeros, or is too large, at
+    /// the offset of its first digit
+    InvalidLength { offset: usize },
+    /// Closing parenthesis without any open list, at its offset
+    UnbalancedClose { offset: usize },
+    /// Display hint of an atom, which aren't supported, at the offset of its `[`
+    DisplayHint { offset: usize },
+    /// Data after the end of the element, at the offset of its first byte
+    TrailingData { offset: usize },
+    /// Invalid base64 of the transport form, at the offset of the invalid byte, or of the
+    /// closing brace for a truncated base64
+    InvalidBase64 { offset: usize },
+}
+
+impl fmt::Display for CsexpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CsexpError::UnexpectedEnd => f.write_str("unexpected end of the data"),
+            CsexpError::UnexpectedByte { offset, byte } => {
+                write!(f, "unexpected byte 0x{:02x} at offset {}", byte, offset)
+            }
+            CsexpError::InvalidLength { offset } => {
+                write!(f, "invalid length of atom at offset {}", offset)
+            }
+            CsexpError::UnbalancedClose { offset } => {
+                write!(f, "closing `)` without any open list at offset {}", offset)
+            }
+            CsexpError::DisplayHint { offset } => {
+                write!(f, "unsupported display hint at offset {}", offset)
+            }
+            CsexpError::TrailingData { offset } => {
+                write!(f, "trailing data at offset {}", offset)
+            }
+            CsexpError::InvalidBase64 { offset } => {
+                write!(f, "invalid base64 at offset {}", offset)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CsexpError {}
+
+/// Read a canonical S-expression, in the canonical or the transport form, made of all the data
+pub fn parse(data: &[u8]) -> Result<ElementOwned, CsexpError> {
+    parse_with(data, &CsexpConfig::default())
+}
+
+/// Read a canonical S-expression, in the canonical or the transport form, made of all the data,
+/// with the given config
+///
+/// ```
+/// use s_expr::csexp::{self, CsexpConfig, CsexpError};
+/// use s_expr::to_string;
+///
+/// let cfg = CsexpConfig::default().strings(true);
+/// let el = csexp::parse_with(b"(4:name2:\xff\x00)", &cfg).unwrap();
+/// assert_eq!(to_string(&el.to_element()), "(\"name\" #ff00#)");
+/// assert_eq!(
+///     csexp::parse_with(b"(3:foo", &cfg).unwrap_err(),
+///     CsexpError::UnexpectedEnd
+/// );
+/// ```
+pub fn parse_with(data: &[u8], cfg: &CsexpConfig) -> Result<ElementOwned, CsexpError> {
+    if data.first() != Some(&b'{') {
+        return canonical(data, cfg);
+    }
+    let close = data
+        .iter()
+        .position(|b| *b == b'}')
+        .ok_or(CsexpError::UnexpectedEnd)?;
+    if close + 1 < data.len() {
+        return Err(CsexpError::TrailingData { offset: close + 1 });
+    }
+    let decoded = base64_decode(&data[1..close])
+        .map_err(|offset| CsexpError::InvalidBase64 { offset: offset + 1 })?;
+    canonical(&decoded, cfg)
+}
+
+// read the canonical form
+fn canonical(data: &[u8], cfg: &CsexpConfig) -> Result<ElementOwned, CsexpError> {
+    // the open lists, with the offset of their `(` and their elements
+    let mut stack: Vec<(usize, Vec<SpannedElementOwned>)> = Vec::new();
+    let mut index = 0;
+    loop {
+        let start = index;
+        let el = match *data.get(index).ok_or(CsexpError::UnexpectedEnd)? {
+            b'(' => {
+                stack.push((index, Vec::new()));
+                index += 1;
+                continue;
+            }
+            b')' => {
+                let (open, elements) = stack
+                    .pop()
+                    .ok_or(CsexpError::UnbalancedClose { offset: index })?;
+                index += 1;
+                Spanned::new(
+                    span(open, index),
+                    ElementOwned::Group(GroupKind::Paren, elements),
+                )
+            }
+            b'0'..=b'9' => {
+                let bytes = atom(data, &mut index)?;
+                let atom = match core::str::from_utf8(bytes) {
+                    Ok(s) if cfg.strings => AtomBuf::string(s),
+                    _ => AtomBuf::bytes(bytes),
+                };
+                Spanned::new(span(start, index), ElementOwned::Atom(atom))
+            }
+            b'[' => return Err(CsexpError::DisplayHint { offset: index }),
+            byte => {
+                return Err(CsexpError::UnexpectedByte {
+                    offset: index,
+                    byte,
+                })
+            }
+        };
+        match stack.last_mut() {
+            Some((_, elements)) => elements.push(el),
+            None if index < data.len() => return Err(CsexpError::TrailingData { offset: index }),
+            None => return Ok(el.inner),
+        }
+    }
+}
+
+fn span(start: usize, end: usize) -> Span {
+    Span::on_line(1, start, end)
+}
+
+// read the length, the `:` and the bytes of an atom, starting at the first digit of the length
+fn atom<'a>(data: &'a [u8], index: &mut usize) -> Result<&'a [u8], CsexpError> {
+    let start = *index;
+    let digits = data[start..]
+        .iter()
+        .take_while(|b| b.is_ascii_digit())
+        .count();
+    let invalid = CsexpError::InvalidLength { offset: start };
+    if digits > 1 && data[start] == b'0' {
+        return Err(invalid);
+    }
+    let len = data[start..start + digits]
+        .iter()
+        .try_fold(0usize, |len, b| {
+            len.checked_mul(10)?.checked_add(usize::from(b - b'0'))
+        })
+        .ok_or(invalid)?;
+    *index += digits;
+    match data.get(*index) {
+        None => return Err(CsexpError::UnexpectedEnd),
+        Some(b':') => *index += 1,
+        Some(byte) => {
+            return Err(CsexpError::UnexpectedByte {
+                offset: *index,
+                byte: *byte,
+            })
+        }
+    }
+    let bytes = data
+        .get(*index..)
+        .and_then(|rest| rest.get(..len))
+        .ok_or(CsexpError::UnexpectedEnd)?;
+    *index += len;
+    Ok(bytes)
+}
+
+/// Write the element as a canonical S-expression
+///
+/// ```
+/// use s_expr::{csexp, Parser};
+///
+/// let el = Parser::new("(sig \"a\\nb\" #00ff# 0x10)").next().unwrap().unwrap();
+/// assert_eq!(csexp::to_vec(&el).unwrap(), b"(3:sig3:a\nb2:\x00\xff2:16)");
+/// ```
+pub fn to_vec(el: &Element) -> Result<Vec<u8>, CsexpEncodeError> {
+    to_vec_with(el, &CsexpEncodeConfig::default())
+}
+
+/// Write the element as a canonical S-expression, with the given config
+pub fn to_vec_with(el: &Element, cfg: &CsexpEncodeConfig) -> Result<Vec<u8>, CsexpEncodeError> {
+    let mut out = Vec::new();
+    element(&mut out, el, cfg)?;
+    Ok(out)
+}
+
+fn element(
+    out: &mut Vec<u8>,
+    el: &Element,
+    cfg: &CsexpEncodeConfig,
+) -> Result<(), CsexpEncodeError> {
+    match el {
+        Element::Comment(_) if cfg.normalize => Ok(()),
+        Element::Comment(_) => Err(CsexpEncodeError::Comment),
+        Element::Group(grp, _) if *grp != GroupKind::Paren && !cfg.normalize => {
+            Err(CsexpEncodeError::Group(*grp))
+        }
+        Element::Group(_, elements) => {
+            out.push(b'(');
+            for e in elements {
+                element(out, &e.inner, cfg)?;
+            }
+            out.push(b')');
+            Ok(())
+        }
+        Element::Atom(atom) => {
+            let bytes = atom_bytes(atom, cfg)?;
+            out.extend_from_slice(bytes.len().to_string().as_bytes());
+            out.push(b':');
+            out.extend_from_slice(&bytes);
+            Ok(())
+        }
+    }
+}
+
+fn atom_bytes(atom: &Atom, cfg: &CsexpEncodeConfig) -> Result<Vec<u8>, CsexpEncodeError> {
+    match atom {
+        Atom::String(astr) => {
+            let decoded = astr.try_decoded().map_err(CsexpEncodeError::Escape)?;
+            Ok(decoded.as_bytes().to_vec())
+        }
+        Atom::Bytes(bytes) => bytes.to_vec().map_err(CsexpEncodeError::Bytes),
+        Atom::Integral(num) if cfg.numbers_as_bytes => {
+            let mut hex = num.digits_in(ANumBase::Hexadecimal);
+            // a leading zero byte when the high bit is set, or for an odd number of digits
+            if hex.len() % 2 == 1 {
+                hex.insert(0, '0');
+            } else if hex.as_bytes()[0] >= b'8' {
+                hex.insert_str(0, "00");
+            }
+            Ok(ABytes(&hex)
+                .to_vec()
+                .expect("even number of hexadecimal digits"))
+        }
+        _ => {
+            let mut text = String::new();
+            canonical::atom(&mut text, atom, &CanonicalConfig::default());
+            Ok(text.into_bytes())
+        }
+    }
+}
+
+/// Write the element as a canonical S-expression in the transport form, the base64 of
+/// the canonical form between braces
+///
+/// ```
+/// use s_expr::{csexp, to_string, Parser};
+///
+/// let el = Parser::new("(foo bar)").next().unwrap().unwrap();
+/// let transport = csexp::to_transport_string(&el).unwrap();
+/// assert_eq!(transport, "{KDM6Zm9vMzpiYXIp}");
+/// let el = csexp::parse(transport.as_bytes()).unwrap();
+/// assert_eq!(to_string(&el.to_element()), "(#666f6f# #626172#)");
+/// ```
+pub fn to_transport_string(el: &Element) -> Result<String, CsexpEncodeError> {
+    to_transport_string_with(el, &CsexpEncodeConfig::default())
+}
+
+/// Write the element as a canonical S-expression in the transport form, with the given config
+pub fn to_transport_string_with(
+    el: &Element,
+    cfg: &CsexpEncodeConfig,
+) -> Result<String, CsexpEncodeError> {
+    let canonical = to_vec_with(el, cfg)?;
+    let mut out = String::with_capacity(canonical.len().div_ceil(3) * 4 + 2);
+    out.push('{');
+    base64_encode(&canonical, &mut out);
+    out.push('}');
+    Ok(out)
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// append the base64 of the data, with the padding
+fn base64_encode(data: &[u8], out: &mut String) {
+    for chunk in data.chunks(3) {
+        let byte = |i: usize| u32::from(chunk.get(i).copied().unwrap_or(0));
+        let v = (byte(0) << 16) | (byte(1) << 8) | byte(2);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(v >> (18 - 6 * i)) as usize & 0x3f] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+}
+
+// decode the base64 with its padding, the whitespaces being skipped, or return the offset of
+// the first invalid byte, which is the length of the text for a truncated base64
+fn base64_decode(text: &[u8]) -> Result<Vec<u8>, usize> {
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    let (mut acc, mut bits, mut symbols, mut padding) = (0u32, 0, 0, 0);
+    for (i, b) in text.iter().enumerate() {
+        if b.is_ascii_whitespace() {
+            continue;
+        }
+        if *b == b'=' && symbols % 4 >= 2 && padding < 4 - symbols % 4 {
+            padding += 1;
+            continue;
+        }
+        let v = match BASE64.iter().position(|c| c == b) {
+            Some(v) if padding == 0 => v as u32,
+            _ => return Err(i),
+        };
+        acc = (acc << 6) | v;
+        bits += 6;
+        symbols += 1;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    // the symbols are completed to a multiple of 4 by the padding, without extra bits
+    if (symbols + padding) % 4 != 0 || acc != 0 {
+        return Err(text.len());
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::printer::to_string;
+
+    fn text(data: &[u8]) -> String {
+        let cfg = CsexpConfig::default().strings(true);
+        to_string(&parse_with(data, &cfg).expect("csexp").to_element())
+    }
+
+    #[test]
+    fn read() {
+        assert_eq!(text(b"3:abc"), "\"abc\"");
+        assert_eq!(text(b"0:"), "\"\"");
+        assert_eq!(text(b"()"), "()");
+        assert_eq!(text(b"(1:a(1:b1:c)())"), "(\"a\" (\"b\" \"c\")())");
+        assert_eq!(text(b"(3:a\"\n)"), "(\"a\\\"\\n\")");
+        assert_eq!(text(b"(2:\xc3\xa91:\xff)"), "(\"é\" #ff#)");
+        // the atoms contain any byte, including the delimiters
+        assert_eq!(text(b"(4:(a):)"), "(\"(a):\")");
+        let el = parse(b"(3:abc)").expect("csexp");
+        assert_eq!(to_string(&el.to_element()), "(#616263#)");
+        match el {
+            ElementOwned::Group(GroupKind::Paren, elements) => {
+                assert_eq!(elements[0].span, Span::on_line(1, 1, 6))
+            }
+            _ => panic!("not a list"),
+        }
+
+        let long = [b"300:".as_slice(), &[b'x'; 300]].concat();
+        assert_eq!(text(&long), format!("\"{}\"", "x".repeat(300)));
+    }
+
+    fn encode(text: &str, cfg: &CsexpEncodeConfig) -> Result<Vec<u8>, CsexpEncodeError> {
+        let el = crate::parser::Parser::new(text).next().unwrap().unwrap();
+        to_vec_with(&el, cfg)
+    }
+
+    #[test]
+    fn write() {
+        let cfg = CsexpEncodeConfig::default();
+        let encoded = encode("(a (b \"\\u{e9}\\\"\") #0_1# 007 1.50 \"\")", &cfg).unwrap();
+        assert_eq!(encoded, b"(1:a(1:b3:\xc3\xa9\")1:\x011:73:1.50:)");
+        assert_eq!(encode("\"\"", &cfg).unwrap(), b"0:");
+
+        let bytes = CsexpEncodeConfig::default().numbers_as_bytes(true);
+        let numbers = [
+            ("0", b"1:\x00".as_slice()),
+            ("0x7f", b"1:\x7f"),
+            ("128", b"2:\x00\x80"),
+            ("0xfff", b"2:\x0f\xff"),
+            ("0b1_0000_0000", b"2:\x01\x00"),
+            ("1.5", b"3:1.5"),
+        ];
+        for (number, expected) in numbers {
+            assert_eq!(encode(number, &bytes).unwrap(), expected, "{}", number);
+        }
+        let large = encode("0x1_0000_0000_0000_0000_0000_0000_0000_0000", &bytes).unwrap();
+        assert_eq!(large, [b"17:\x01".as_slice(), &[0; 16]].concat());
+
+        // the brackets, braces and comments
+        let text = "(a ; comment\n [b {c}])";
+        assert_eq!(encode(text, &cfg), Err(CsexpEncodeError::Comment));
+        assert_eq!(
+            encode("(a [b])", &cfg),
+            Err(CsexpEncodeError::Group(GroupKind::Bracket))
+        );
+        let normalize = CsexpEncodeConfig::default().normalize(true);
+        assert_eq!(encode(text, &normalize).unwrap(), b"(1:a(1:b(1:c)))");
+        assert!(matches!(
+            encode("#0f0#", &cfg),
+            Err(CsexpEncodeError::Bytes(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "unicode")]
+    fn write_read_roundtrip() {
+        let cfg = CsexpEncodeConfig::default().normalize(true);
+        let read = CsexpConfig::default().strings(true);
+        for text in [
+            crate::tests::PROG1,
+            crate::tests::PROG2,
+            "(\"é\\t\" #ff_00# ())",
+        ] {
+            let mut parser = crate::parser::Parser::new(text);
+            while let Some(el) = parser.next().unwrap() {
+                if let Element::Comment(_) = el.inner {
+                    continue;
+                }
+                let encoded = to_vec_with(&el, &cfg).unwrap();
+                for read in [&read, &CsexpConfig::default()] {
+                    let decoded = parse_with(&encoded, read).unwrap();
+                    assert_eq!(to_vec_with(&decoded.to_element(), &cfg).unwrap(), encoded);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn base64() {
+        // the test vectors of RFC 4648
+        let vectors = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (data, encoded) in vectors {
+            let mut out = String::new();
+            base64_encode(data.as_bytes(), &mut out);
+            assert_eq!(out, encoded);
+            assert_eq!(
+                base64_decode(encoded.as_bytes()),
+                Ok(data.as_bytes().to_vec())
+            );
+        }
+        let all = (0..=255).collect::<Vec<u8>>();
+        let mut out = String::new();
+        base64_encode(&all, &mut out);
+        assert_eq!(base64_decode(out.as_bytes()), Ok(all));
+        assert_eq!(base64_decode(b"Zm9v\n YmFy"), Ok(b"foobar".to_vec()));
+
+        assert_eq!(base64_decode(b"Zm9vY"), Err(5));
+        assert_eq!(base64_decode(b"Zm8"), Err(3));
+        assert_eq!(base64_decode(b"Zm=8"), Err(3));
+        assert_eq!(base64_decode(b"Zm8=="), Err(4));
+        assert_eq!(base64_decode(b"Zm9v*"), Err(4));
+        // extra bits in the last symbol
+        assert_eq!(base64_decode(b"Zh=="), Err(4));
+    }
+
+    #[test]
+    fn transport() {
+        let cfg = CsexpEncodeConfig::default();
+        let read = CsexpConfig::default().strings(true);
+        let vectors = [
+            ("abc", "{MzphYmM=}"),
+            ("(foo bar)", "{KDM6Zm9vMzpiYXIp}"),
+            ("(a (b c))", "{KDE6YSgxOmIxOmMpKQ==}"),
+        ];
+        for (text, transport) in vectors {
+            let el = crate::parser::Parser::new(text).next().unwrap().unwrap();
+            assert_eq!(to_transport_string_with(&el, &cfg).unwrap(), transport);
+            let canonical = to_vec_with(&el, &cfg).unwrap();
+            let decoded = parse_with(transport.as_bytes(), &read).unwrap();
+            assert_eq!(to_vec_with(&decoded.to_element(), &cfg).unwrap(), canonical);
+        }
+        // the base64 can be split on lines
+        let el = parse(b"{KDM6Zm9v\r\n  MzpiYXIp}").unwrap();
+        assert_eq!(
+            to_vec_with(&el.to_element(), &cfg).unwrap(),
+            b"(3:foo3:bar)"
+        );
+
+        assert_eq!(parse(b"{KDM6").unwrap_err(), CsexpError::UnexpectedEnd);
+        assert_eq!(
+            parse(b"{MzphYmM=}\n").unwrap_err(),
+            CsexpError::TrailingData { offset: 10 }
+        );
+        assert_eq!(
+            parse(b"{Mzph*mM=}").unwrap_err(),
+            CsexpError::InvalidBase64 { offset: 5 }
+        );
+        assert_eq!(
+            parse(b"{MzphYm}").unwrap_err(),
+            CsexpError::InvalidBase64 { offset: 7 }
+        );
+        // the decoded canonical form is checked
+        assert_eq!(parse(b"{KDM6}").unwrap_err(), CsexpError::UnexpectedEnd);
+    }
+
+    #[test]
+    fn spki_draft_vectors() {
+        // the example of the S-expressions draft of Rivest: `(snicker "abc" (#03# |YWJj|))`,
+        // with its canonical and its transport representations
+        let canonical: &[u8] = b"(7:snicker3:abc(1:\x033:abc))";
+        let transport = "{KDc6c25pY2tlcjM6YWJjKDE6AzM6YWJjKSk=}";
+        let cfg = CsexpEncodeConfig::default();
+        let el = parse(canonical).unwrap();
+        assert_eq!(to_vec_with(&el.to_element(), &cfg).unwrap(), canonical);
+        assert_eq!(
+            to_transport_string_with(&el.to_element(), &cfg).unwrap(),
+            transport
+        );
+        let decoded = parse(transport.as_bytes()).unwrap();
+        assert_eq!(to_vec_with(&decoded.to_element(), &cfg).unwrap(), canonical);
+        // the same S-expression in the text form, without the base64 `|YWJj|` of the draft
+        let text = crate::parser::Parser::new("(snicker \"abc\" (#03# \"abc\"))")
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(to_vec_with(&text, &cfg).unwrap(), canonical);
+        assert_eq!(to_transport_string_with(&text, &cfg).unwrap(), transport);
+
+        // the display hints of the draft, e.g. `[image/gif]`, are not supported
+        assert_eq!(
+            parse(b"(4:icon[9:image/gif]4:abcd)").unwrap_err(),
+            CsexpError::DisplayHint { offset: 7 }
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let error = |data: &[u8]| parse(data).unwrap_err();
+        assert_eq!(error(b""), CsexpError::UnexpectedEnd);
+        assert_eq!(error(b"(1:a"), CsexpError::UnexpectedEnd);
+        assert_eq!(error(b"4:abc"), CsexpError::UnexpectedEnd);
+        assert_eq!(error(b"12"), CsexpError::UnexpectedEnd);
+        assert_eq!(
+            error(b"(1:a 1:b)"),
+            CsexpError::UnexpectedByte {
+                offset: 4,
+                byte: b' '
+            }
+        );
+        assert_eq!(
+            error(b"3abc"),
+            CsexpError::UnexpectedByte {
+                offset: 1,
+                byte: b'a'
+            }
+        );
+        assert_eq!(error(b"(03:abc)"), CsexpError::InvalidLength { offset: 1 });
+        assert_eq!(
+            error(b"99999999999999999999999:a"),
+            CsexpError::InvalidLength { offset: 0 }
+        );
+        assert_eq!(error(b"1:a)"), CsexpError::TrailingData { offset: 3 });
+        assert_eq!(error(b"())"), CsexpError::TrailingData { offset: 2 });
+        assert_eq!(error(b")"), CsexpError::UnbalancedClose { offset: 0 });
+        assert_eq!(
+            error(b"([4:mime]5:image)"),
+            CsexpError::DisplayHint { offset: 1 }
+        );
+        assert_eq!(
+            error(b"(1:a)x").to_string().as_str(),
+            "trailing data at offset 5",
+        );
+    }
+}
@@ -1,5 +1,13 @@
+use super::escape::{self, EscapeError};
+use super::ident::is_ident;
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use std::borrow::Cow;
+
 /// Type of group
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum GroupKind {
     /// Group of ()
@@ -10,8 +18,103 @@ pub enum GroupKind {
     Bracket,
 }
 
+impl GroupKind {
+    /// All the kinds of group
+    pub const ALL: [GroupKind; 3] = [GroupKind::Paren, GroupKind::Brace, GroupKind::Bracket];
+
+    /// Get the character opening the group, e.g. `(`
+    pub fn open_char(self) -> char {
+        self.delimiters().0
+    }
+
+    /// Get the character closing the group, e.g. `)`
+    pub fn close_char(self) -> char {
+        self.delimiters().1
+    }
+
+    /// Get the opening and closing characters of the group, e.g. `('(', ')')`
+    pub fn delimiters(self) -> (char, char) {
+        match self {
+            GroupKind::Paren => ('(', ')'),
+            GroupKind::Brace => ('{', '}'),
+            GroupKind::Bracket => ('[', ']'),
+        }
+    }
+
+    /// Get the kind of group opened by the character, or None if it isn't an opening character
+    pub fn from_open_char(c: char) -> Option<GroupKind> {
+        Self::ALL.into_iter().find(|grp| grp.open_char() == c)
+    }
+
+    /// Get the kind of group closed by the character, or None if it isn't a closing character
+    pub fn from_close_char(c: char) -> Option<GroupKind> {
+        Self::ALL.into_iter().find(|grp| grp.close_char() == c)
+    }
+}
+
+/// The name of the group with its delimiters, e.g. ``parentheses `()` ``
+impl fmt::Display for GroupKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            GroupKind::Paren => "parentheses",
+            GroupKind::Brace => "braces",
+            GroupKind::Bracket => "brackets",
+        };
+        write!(f, "{} `{}{}`", name, self.open_char(), self.close_char())
+    }
+}
+
+/// Parse the kind of group from its name, either `paren`, `brace` or `bracket`
+///
+/// ```
+/// use s_expr::GroupKind;
+///
+/// assert_eq!("bracket".parse(), Ok(GroupKind::Bracket));
+/// assert!("curly".parse::<GroupKind>().is_err());
+/// ```
+impl core::str::FromStr for GroupKind {
+    type Err = UnknownGroupKind;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "paren" => Ok(GroupKind::Paren),
+            "brace" => Ok(GroupKind::Brace),
+            "bracket" => Ok(GroupKind::Bracket),
+            _ => Err(UnknownGroupKind(s.to_string())),
+        }
+    }
+}
+
+/// Unknown name of group kind, see the `FromStr` implementation of [`GroupKind`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownGroupKind(pub String);
+
+impl fmt::Display for UnknownGroupKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown group kind {:?}, expected paren, brace or bracket",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for UnknownGroupKind {}
+
 /// Atom literal (Number, Bytes, String, or Ident)
-#[derive(Clone, Debug)]
+///
+/// Two atoms are equal if they are the same variant with the same value: numbers are compared
+/// by value whatever their bases, strings by content once the escapes are resolved, bytes by
+/// digits ignoring the case and the separators, and idents by text. An integral number is never
+/// equal to a decimal number. Use [`Atom::raw_eq`] to compare the literals as written.
+///
+/// The hash is consistent with the equality, so that the atoms can be used as keys of a `HashMap`,
+/// e.g. `0x10` and `16` have the same hash.
+///
+/// The atoms are ordered by variant first, `Ident < Integral < Decimal < String < Bytes`, then
+/// by value: numbers by numeric value, strings by content once the escapes are resolved, bytes
+/// by digits ignoring the case and the separators, and idents by text.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Atom<'a> {
     /// Integral number literal
     Integral(ANum<'a>),
@@ -25,7 +128,202 @@ pub enum Atom<'a> {
     Ident(&'a str),
 }
 
+impl<'a> PartialOrd for Atom<'a> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'a> Ord for Atom<'a> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Atom::Ident(i1), Atom::Ident(i2)) => i1.cmp(i2),
+            (Atom::Integral(n1), Atom::Integral(n2)) => n1.cmp(n2),
+            (Atom::Decimal(d1), Atom::Decimal(d2)) => d1.cmp(d2),
+            (Atom::String(s1), Atom::String(s2)) => s1.cmp(s2),
+            (Atom::Bytes(b1), Atom::Bytes(b2)) => b1.cmp(b2),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+/// Kind of an atom, without its data, e.g. to report what was found in an error message
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AtomKind {
+    /// Integral number literal
+    Integral,
+    /// Decimal number literal
+    Decimal,
+    /// Bytes literal
+    Bytes,
+    /// String literal
+    String,
+    /// Ident
+    Ident,
+}
+
+impl fmt::Display for AtomKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            AtomKind::Integral => "integral number",
+            AtomKind::Decimal => "decimal number",
+            AtomKind::Bytes => "bytes",
+            AtomKind::String => "string",
+            AtomKind::Ident => "ident",
+        };
+        f.write_str(name)
+    }
+}
+
 impl<'a> Atom<'a> {
+    /// Get the kind of the atom
+    ///
+    /// ```
+    /// use s_expr::{Atom, AtomKind};
+    ///
+    /// let atom = Atom::Ident("foo");
+    /// assert_eq!(atom.kind(), AtomKind::Ident);
+    /// assert_eq!(format!("expected a number, found {}", atom.kind()), "expected a number, found ident");
+    /// ```
+    pub fn kind(&self) -> AtomKind {
+        match self {
+            Atom::Integral(_) => AtomKind::Integral,
+            Atom::Decimal(_) => AtomKind::Decimal,
+            Atom::Bytes(_) => AtomKind::Bytes,
+            Atom::String(_) => AtomKind::String,
+            Atom::Ident(_) => AtomKind::Ident,
+        }
+    }
+
+    /// Check if the atom is an ident
+    pub fn is_ident(&self) -> bool {
+        matches!(self, Atom::Ident(_))
+    }
+
+    /// Check if the atom is a number, either integral or decimal
+    pub fn is_number(&self) -> bool {
+        matches!(self, Atom::Integral(_) | Atom::Decimal(_))
+    }
+
+    /// Check if the atom is a string
+    pub fn is_string(&self) -> bool {
+        matches!(self, Atom::String(_))
+    }
+
+    /// Check if the atom is a bytes literal
+    pub fn is_bytes(&self) -> bool {
+        matches!(self, Atom::Bytes(_))
+    }
+
+    /// Get the name of a keyword, which is an ident starting with a single `:`, e.g. `name`
+    /// for `:name`, or None for any other atom
+    ///
+    /// The operators made of colons only, like `:` and `::`, and the idents starting with `::`
+    /// are not keywords.
+    ///
+    /// ```
+    /// use s_expr::Atom;
+    ///
+    /// assert_eq!(Atom::Ident(":key").as_keyword(), Some("key"));
+    /// assert_eq!(Atom::Ident("::key").as_keyword(), None);
+    /// assert_eq!(Atom::Ident(":").as_keyword(), None);
+    /// ```
+    pub fn as_keyword(&self) -> Option<&'a str> {
+        let name = self.ident()?.strip_prefix(':')?;
+        if name.is_empty() || name.starts_with(':') {
+            None
+        } else {
+            Some(name)
+        }
+    }
+
+    /// Check if the atom is a keyword, see [`Atom::as_keyword`]
+    pub fn is_keyword(&self) -> bool {
+        self.as_keyword().is_some()
+    }
+
+    /// Split an ident on the first separator, e.g. `("ns", "sym")` for `ns/sym` with `/`
+    ///
+    /// Returns None for the other atoms, and for the idents without separator or with the separator
+    /// at the start or at the end, so that the operators like `/` or the keywords like `:name`
+    /// are not split. The second part might contain more separators, e.g. `("a", "b/c")` for `a/b/c`.
+    pub fn ident_parts(&self, sep: char) -> Option<(&'a str, &'a str)> {
+        let (first, second) = self.ident()?.split_once(sep)?;
+        if first.is_empty() || second.is_empty() {
+            None
+        } else {
+            Some((first, second))
+        }
+    }
+
+    /// Get the boolean value of the idents `true` and `false`, or None for any other atom
+    pub fn as_bool(&self) -> Option<bool> {
+        self.as_bool_with(&["true"], &["false"])
+    }
+
+    /// Get the boolean value of an ident from a custom vocabulary, true if it is one of
+    /// the truthy idents and false if it is one of the falsy idents, or None otherwise
+    ///
+    /// ```
+    /// use s_expr::Atom;
+    ///
+    /// let (truthy, falsy) = (&["yes", "on"][..], &["no", "off"][..]);
+    /// assert_eq!(Atom::Ident("on").as_bool_with(truthy, falsy), Some(true));
+    /// assert_eq!(Atom::Ident("no").as_bool_with(truthy, falsy), Some(false));
+    /// assert_eq!(Atom::Ident("true").as_bool_with(truthy, falsy), None);
+    /// ```
+    pub fn as_bool_with(&self, truthy: &[&str], falsy: &[&str]) -> Option<bool> {
+        self.bool_ident(truthy, falsy, |i1, i2| i1 == i2)
+    }
+
+    /// Get the boolean value of an ident like [`Atom::as_bool_with`], ignoring the ascii case,
+    /// e.g. `Yes` or `YES` for `yes`
+    pub fn as_bool_ignore_case(&self, truthy: &[&str], falsy: &[&str]) -> Option<bool> {
+        self.bool_ident(truthy, falsy, |i1, i2| i1.eq_ignore_ascii_case(i2))
+    }
+
+    fn bool_ident<F: Fn(&str, &str) -> bool>(
+        &self,
+        truthy: &[&str],
+        falsy: &[&str],
+        eq: F,
+    ) -> Option<bool> {
+        let ident = self.ident()?;
+        if truthy.iter().any(|t| eq(ident, t)) {
+            Some(true)
+        } else if falsy.iter().any(|f| eq(ident, f)) {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    // rank of the variant in the ordering of the atoms
+    fn rank(&self) -> u8 {
+        match self {
+            Atom::Ident(_) => 0,
+            Atom::Integral(_) => 1,
+            Atom::Decimal(_) => 2,
+            Atom::String(_) => 3,
+            Atom::Bytes(_) => 4,
+        }
+    }
+
+    /// Check if two atoms are written the same way, e.g. `0x10` and `16` are equal by value but not
+    /// written the same way
+    pub fn raw_eq(&self, other: &Atom) -> bool {
+        match (self, other) {
+            (Atom::Integral(n1), Atom::Integral(n2)) => n1.base == n2.base && n1.dat == n2.dat,
+            (Atom::Decimal(d1), Atom::Decimal(d2)) => {
+                d1.raw_integral == d2.raw_integral && d1.raw_fractional == d2.raw_fractional
+            }
+            (Atom::Bytes(b1), Atom::Bytes(b2)) => b1.0 == b2.0,
+            (Atom::String(s1), Atom::String(s2)) => s1.raw_data == s2.raw_data,
+            (Atom::Ident(i1), Atom::Ident(i2)) => i1 == i2,
+            _ => false,
+        }
+    }
+
     /// Get the Number in an Atom if the right variant, or None
     pub fn number(&self) -> Option<&ANum<'a>> {
         match self {
@@ -67,25 +365,711 @@ impl<'a> Atom<'a> {
     }
 }
 
+/// The literal syntax of the atom, which parses back to the same atom, e.g. `"a\n"` with the quotes
+/// and the escapes of a string, `0x1f` or `#dead#`
+impl<'a> fmt::Display for Atom<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Atom::Ident(ident) => f.write_str(ident),
+            Atom::String(astr) => write!(f, "\"{}\"", astr.raw_data),
+            Atom::Bytes(bytes) => bytes.fmt(f),
+            Atom::Integral(num) => num.fmt(f),
+            Atom::Decimal(dec) => dec.fmt(f),
+        }
+    }
+}
+
+/// Owned atom, with the same variants as [`Atom`] and the data held in `String`s, e.g. to build an
+/// atom from a computed value or to keep it after the source is dropped
+///
+/// The comparison, the hash and the display are the ones of the atom view, see [`AtomBuf::as_atom`].
+///
+/// ```
+/// use s_expr::{Atom, AtomBuf, Printer, Tokenizer, Token};
+///
+/// let buf = {
+///     let source = String::from("0x1f");
+///     let token = Tokenizer::new(&source).next().unwrap().unwrap();
+///     match &token.inner {
+///         Token::Atom(atom) => AtomBuf::from(atom),
+///         _ => panic!("not an atom"),
+///     }
+/// };
+/// assert_eq!(buf, AtomBuf::from(31u8));
+///
+/// let mut p = Printer::default();
+/// p.atom(&buf.as_atom());
+/// assert_eq!(p.to_string(), "0x1f");
+/// ```
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AtomBuf {
+    /// Integral number literal, with the digits in the base, which might contains _ separators
+    Integral { base: ANumBase, dat: String },
+    /// Decimal number literal, with the integral and fractional digits, which might contains _ separators
+    Decimal {
+        integral: String,
+        fractional: String,
+    },
+    /// Bytes literal, with the hexadecimal digits, which might contains _ separators
+    Bytes(String),
+    /// String literal, with the raw data which might contains escapes
+    String { has_escape: bool, raw_data: String },
+    /// Ident
+    Ident(String),
+}
+
+impl AtomBuf {
+    /// Create an ident, or an error if the text doesn't parse back as this exact ident, e.g. if it
+    /// is empty, or contains whitespaces, delimiters or quotes
+    ///
+    /// ```
+    /// use s_expr::AtomBuf;
+    ///
+    /// assert_eq!(AtomBuf::ident("define").unwrap().to_string(), "define");
+    /// assert!(AtomBuf::ident("a b").is_err());
+    /// assert!(AtomBuf::ident("12").is_err());
+    /// ```
+    pub fn ident(s: &str) -> Result<Self, InvalidIdent> {
+        if is_ident(s) {
+            Ok(AtomBuf::Ident(s.to_string()))
+        } else {
+            Err(InvalidIdent(s.to_string()))
+        }
+    }
+
+    /// Create an integral number with the digits of the value in the given base
+    pub fn integral_u128(v: u128, base: ANumBase) -> Self {
+        let dat = match base {
+            ANumBase::Binary => format!("{:b}", v),
+            ANumBase::Decimal => format!("{}", v),
+            ANumBase::Hexadecimal => format!("{:x}", v),
+        };
+        AtomBuf::Integral { base, dat }
+    }
+
+    /// Create a string, with the raw data escaped as the printer does, so that the content of the
+    /// atom is the given text
+    ///
+    /// ```
+    /// use s_expr::AtomBuf;
+    ///
+    /// let s = AtomBuf::string("say \"hi\"");
+    /// assert_eq!(s.to_string(), r#""say \"hi\"""#);
+    /// assert_eq!(s.as_atom().string().unwrap().decoded(), "say \"hi\"");
+    /// ```
+    pub fn string(s: &str) -> Self {
+        let astr = AStr::from_decoded(s);
+        AtomBuf::String {
+            has_escape: astr.has_escape,
+            raw_data: astr.raw_data.into_owned(),
+        }
+    }
+
+    /// Create a bytes literal, with the bytes encoded in lowercase hexadecimal
+    pub fn bytes(bytes: &[u8]) -> Self {
+        ABytes::from_bytes(bytes).into()
+    }
+
+    /// Get a view of the owned atom as an [`Atom`]
+    pub fn as_atom(&self) -> Atom<'_> {
+        match self {
+            AtomBuf::Integral { base, dat } => Atom::Integral(ANum { base: *base, dat }),
+            AtomBuf::Decimal {
+                integral,
+                fractional,
+            } => Atom::Decimal(ADecimal {
+                raw_integral: integral,
+                raw_fractional: fractional,
+            }),
+            AtomBuf::Bytes(bytes) => Atom::Bytes(ABytes(bytes)),
+            AtomBuf::String {
+                has_escape,
+                raw_data,
+            } => Atom::String(AStr {
+                has_escape: *has_escape,
+                raw_data: Cow::Borrowed(raw_data),
+            }),
+            AtomBuf::Ident(ident) => Atom::Ident(ident),
+        }
+    }
+}
+
+impl<'a> From<&Atom<'a>> for AtomBuf {
+    fn from(atom: &Atom<'a>) -> Self {
+        match atom {
+            Atom::Integral(num) => AtomBuf::Integral {
+                base: num.base,
+                dat: num.dat.to_string(),
+            },
+            Atom::Decimal(dec) => AtomBuf::Decimal {
+                integral: dec.raw_integral.to_string(),
+                fractional: dec.raw_fractional.to_string(),
+            },
+            Atom::Bytes(bytes) => AtomBuf::Bytes(bytes.0.to_string()),
+            Atom::String(astr) => AtomBuf::String {
+                has_escape: astr.has_escape,
+                raw_data: astr.raw_data.to_string(),
+            },
+            Atom::Ident(ident) => AtomBuf::Ident(ident.to_string()),
+        }
+    }
+}
+
+/// Invalid ident given to [`AtomBuf::ident`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidIdent(pub String);
+
+impl fmt::Display for InvalidIdent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid ident {:?}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidIdent {}
+
+// unsigned integers as decimal integral numbers
+macro_rules! atombuf_from_uint {
+    ($($ty:ty),*) => {
+        $(
+            impl From<$ty> for AtomBuf {
+                fn from(v: $ty) -> Self {
+                    AtomBuf::Integral {
+                        base: ANumBase::Decimal,
+                        dat: v.to_string(),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+atombuf_from_uint!(u8, u16, u32, u64, u128, usize);
+
+impl PartialEq for AtomBuf {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_atom() == other.as_atom()
+    }
+}
+
+impl Eq for AtomBuf {}
+
+impl Hash for AtomBuf {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_atom().hash(state)
+    }
+}
+
+impl PartialOrd for AtomBuf {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for AtomBuf {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_atom().cmp(&other.as_atom())
+    }
+}
+
+impl fmt::Display for AtomBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_atom().fmt(f)
+    }
+}
+
 /// A String literal, that may contains escapes
+///
+/// The raw data is the escaped form of the string, as written between the quotes. The tokenizer
+/// borrows it from the source, while the strings created with [`AStr::from_decoded`] own it.
+///
+/// Two strings are equal if they have the same content once the escapes are resolved,
+/// e.g. `"a\x41"` and `"aA"`
 #[derive(Clone, Debug)]
 pub struct AStr<'a> {
     pub has_escape: bool,
-    pub raw_data: &'a str,
+    pub raw_data: Cow<'a, str>,
+}
+
+impl<'a> PartialEq for AStr<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        if !self.has_escape && !other.has_escape {
+            self.raw_data == other.raw_data
+        } else {
+            self.content() == other.content()
+        }
+    }
+}
+
+impl<'a> Eq for AStr<'a> {}
+
+impl<'a> PartialOrd for AStr<'a> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// The strings are ordered by content once the escapes are resolved
+impl<'a> Ord for AStr<'a> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.content().cmp(&other.content())
+    }
+}
+
+impl<'a> Hash for AStr<'a> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.content().hash(state)
+    }
 }
 
 impl<'a> AStr<'a> {
+    /// Create a string with the given content, escaped like the printer does by default
+    ///
+    /// ```
+    /// use s_expr::AStr;
+    ///
+    /// let astr = AStr::from_decoded("a \"b\"");
+    /// assert_eq!(astr.raw(), r#"a \"b\""#);
+    /// assert!(astr.has_escape);
+    /// assert_eq!(astr.decoded(), "a \"b\"");
+    /// assert!(!AStr::from_decoded("plain").has_escape);
+    /// ```
+    pub fn from_decoded(content: &str) -> Self {
+        let mut raw_data = String::with_capacity(content.len());
+        // writing to a String doesn't fail
+        let _ = escape::escape_default(&mut raw_data, content);
+        AStr {
+            has_escape: raw_data.contains('\\'),
+            raw_data: Cow::Owned(raw_data),
+        }
+    }
+
+    /// Get the raw data of the string, with the escapes as written
+    pub fn raw(&self) -> &str {
+        &self.raw_data
+    }
+
+    /// Check if the raw data is borrowed, which is the case of the strings from the tokenizer
+    pub fn is_borrowed(&self) -> bool {
+        matches!(self.raw_data, Cow::Borrowed(_))
+    }
+
+    /// Convert to a string owning its raw data
+    pub fn into_owned(self) -> AStr<'static> {
+        AStr {
+            has_escape: self.has_escape,
+            raw_data: Cow::Owned(self.raw_data.into_owned()),
+        }
+    }
+
+    // the content of the string, borrowing the raw data, owned or not, if there's no escape
+    fn content(&self) -> Cow<'_, str> {
+        if self.has_escape {
+            Cow::Owned(escape::unescape_lossy(&self.raw_data))
+        } else {
+            Cow::Borrowed(&self.raw_data)
+        }
+    }
+
+    /// Get the content of the string, with the escapes resolved, see [`AStr::decoded`]
+    #[allow(clippy::inherent_to_string)]
     pub fn to_string(&self) -> String {
-        self.raw_data.to_string()
+        self.decoded().into_owned()
+    }
+
+    /// Get the content of the string with the escapes resolved, borrowing the raw data if there's
+    /// no escape and the raw data is borrowed
+    ///
+    /// The supported escapes are `\\`, `\"`, `\n`, `\t`, `\r`, `\0`, `\xNN` for ascii values
+    /// and `\u{N..}` for any unicode scalar value. The invalid escapes are kept as is, use
+    /// [`AStr::try_decoded`] to reject them.
+    ///
+    /// ```
+    /// use s_expr::{Atom, Token, Tokenizer};
+    ///
+    /// let mut tokenizer = Tokenizer::new(r#""def\"x\\y""#);
+    /// let token = tokenizer.next().unwrap().unwrap();
+    /// let Token::Atom(Atom::String(astr)) = token.inner else { panic!("not a string") };
+    /// assert_eq!(astr.raw_data, r#"def\"x\\y"#);
+    /// assert_eq!(astr.decoded(), r#"def"x\y"#);
+    /// ```
+    pub fn decoded(&self) -> Cow<'a, str> {
+        if self.has_escape {
+            Cow::Owned(escape::unescape_lossy(&self.raw_data))
+        } else {
+            self.raw_data.clone()
+        }
+    }
+
+    /// Get the number of characters of the content of the string, with the escapes resolved,
+    /// without allocating, or the first invalid escape
+    ///
+    /// ```
+    /// use s_expr::AStr;
+    ///
+    /// let astr = AStr { has_escape: true, raw_data: r"caf\u{e9}\n".into() };
+    /// assert_eq!(astr.decoded_len(), Ok(5));
+    /// ```
+    pub fn decoded_len(&self) -> Result<usize, EscapeError> {
+        if self.has_escape {
+            self.chars().try_fold(0, |len, c| c.map(|_| len + 1))
+        } else {
+            Ok(self.raw_data.chars().count())
+        }
+    }
+
+    /// Check if the content of the string is empty, which is the case only if the raw data is empty
+    pub fn is_empty(&self) -> bool {
+        self.raw_data.is_empty()
+    }
+
+    /// Iterate over the characters of the content of the string, with the escapes resolved, and
+    /// the byte offset of each character in the raw data, which is the offset of the backslash for
+    /// an escape
+    ///
+    /// The iterator stops after the first invalid escape, which is the same as the one of [`AStr::try_decoded`].
+    ///
+    /// ```
+    /// use s_expr::AStr;
+    ///
+    /// let astr = AStr { has_escape: true, raw_data: r"a\tb".into() };
+    /// let chars = astr.chars().collect::<Result<Vec<_>, _>>();
+    /// assert_eq!(chars, Ok(vec![('a', 0), ('\t', 1), ('b', 3)]));
+    /// ```
+    pub fn chars(&self) -> impl Iterator<Item = Result<(char, usize), EscapeError>> + '_ {
+        escape::Unescape::new(&self.raw_data, self.has_escape)
+    }
+
+    /// Get the content of the string with the escapes resolved like [`AStr::decoded`], or the first invalid escape
+    pub fn try_decoded(&self) -> Result<Cow<'a, str>, EscapeError> {
+        if self.has_escape {
+            escape::unescape(&self.raw_data).map(Cow::Owned)
+        } else {
+            Ok(self.raw_data.clone())
+        }
     }
 }
 
-/// A Bytes literal
+/// A Bytes literal, made of hexadecimal digits which might contains _ separators
+///
+/// Two bytes literals are equal if they have the same digits, ignoring the case and the separators,
+/// e.g. `#DE_AD#` and `#dead#`
 #[derive(Clone, Debug)]
 pub struct ABytes<'a>(pub &'a str);
 
+impl<'a> PartialEq for ABytes<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        self.hex_digits().eq(other.hex_digits())
+    }
+}
+
+impl<'a> Eq for ABytes<'a> {}
+
+impl<'a> PartialOrd for ABytes<'a> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// The bytes are ordered by digits, which is the order of the decoded bytes
+impl<'a> Ord for ABytes<'a> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.hex_digits().cmp(other.hex_digits())
+    }
+}
+
+impl<'a> Hash for ABytes<'a> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.hex_digits().for_each(|c| c.hash(state))
+    }
+}
+
+/// The literal syntax of the bytes, e.g. `#dead_beef#`
+impl<'a> fmt::Display for ABytes<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}#", self.0)
+    }
+}
+
+// write the hexadecimal digits of the data, with a `_` separator every `group` bytes unless 0
+pub(crate) fn write_hex<W: fmt::Write + ?Sized>(
+    out: &mut W,
+    data: &[u8],
+    uppercase: bool,
+    group: usize,
+) -> fmt::Result {
+    for (i, b) in data.iter().enumerate() {
+        if group > 0 && i > 0 && i % group == 0 {
+            out.write_char('_')?;
+        }
+        if uppercase {
+            write!(out, "{:02X}", b)?;
+        } else {
+            write!(out, "{:02x}", b)?;
+        }
+    }
+    Ok(())
+}
+
+/// An owned bytes literal, see [`ABytes::from_bytes`]
+///
+/// The equality, the hash and the order are the ones of [`ABytes`]
+#[derive(Clone, Debug)]
+pub struct ABytesBuf(pub(crate) String);
+
+impl ABytesBuf {
+    /// Get a view of the owned literal as an [`ABytes`]
+    pub fn as_abytes(&self) -> ABytes<'_> {
+        ABytes(&self.0)
+    }
+
+    /// Get the hexadecimal digits and the separators, without the `#`
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Get the hexadecimal digits and the separators as a String, without the `#`
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl PartialEq for ABytesBuf {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_abytes() == other.as_abytes()
+    }
+}
+
+impl Eq for ABytesBuf {}
+
+impl PartialOrd for ABytesBuf {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ABytesBuf {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_abytes().cmp(&other.as_abytes())
+    }
+}
+
+impl Hash for ABytesBuf {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_abytes().hash(state)
+    }
+}
+
+impl fmt::Display for ABytesBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_abytes().fmt(f)
+    }
+}
+
+impl From<ABytesBuf> for AtomBuf {
+    fn from(bytes: ABytesBuf) -> Self {
+        AtomBuf::Bytes(bytes.0)
+    }
+}
+
+/// Invalid bytes literal, see [`ABytes::to_vec`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BytesDecodeError {
+    /// A character is neither an hexadecimal digit nor a separator, at the given byte offset
+    InvalidChar { offset: usize, ch: char },
+    /// The number of digits is odd, the offset is the one of the last digit, which has no pair
+    OddLength { offset: usize },
+}
+
+impl BytesDecodeError {
+    /// Byte offset of the invalid character or of the unpaired digit in the raw data
+    pub fn offset(&self) -> usize {
+        match self {
+            BytesDecodeError::InvalidChar { offset, .. } => *offset,
+            BytesDecodeError::OddLength { offset } => *offset,
+        }
+    }
+}
+
+impl fmt::Display for BytesDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BytesDecodeError::InvalidChar { offset, ch } => {
+                write!(f, "invalid character {:?} at offset {}", ch, offset)
+            }
+            BytesDecodeError::OddLength { offset } => {
+                write!(
+                    f,
+                    "odd number of digits, unpaired digit at offset {}",
+                    offset
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for BytesDecodeError {}
+
+impl<'a> ABytes<'a> {
+    // digits without the separators, in lowercase
+    fn hex_digits(&self) -> impl Iterator<Item = char> + 'a {
+        self.0
+            .chars()
+            .filter(|c| *c != '_' && !c.is_ascii_whitespace())
+            .map(|c| c.to_ascii_lowercase())
+    }
+
+    /// Create an owned bytes literal, hex encoding the data in lowercase without separators
+    ///
+    /// ```
+    /// use s_expr::ABytes;
+    ///
+    /// let bytes = ABytes::from_bytes(&[0xde, 0xad]);
+    /// assert_eq!(bytes.to_string(), "#dead#");
+    /// assert_eq!(bytes.as_abytes().to_vec(), Ok(vec![0xde, 0xad]));
+    /// ```
+    pub fn from_bytes(data: &[u8]) -> ABytesBuf {
+        let mut hex = String::with_capacity(data.len() * 2);
+        // writing to a String doesn't fail
+        let _ = write_hex(&mut hex, data, false, 0);
+        ABytesBuf(hex)
+    }
+
+    /// Create an owned bytes literal from hexadecimal text, as written between the `#`,
+    /// checking that it decodes like [`ABytes::to_vec`]
+    ///
+    /// ```
+    /// use s_expr::{ABytes, BytesDecodeError};
+    ///
+    /// assert_eq!(ABytes::from_hex_str("DE_ad").unwrap().to_string(), "#DE_ad#");
+    /// assert_eq!(ABytes::from_hex_str("abc"), Err(BytesDecodeError::OddLength { offset: 2 }));
+    /// ```
+    pub fn from_hex_str(hex: &str) -> Result<ABytesBuf, BytesDecodeError> {
+        ABytes(hex).validate()?;
+        Ok(ABytesBuf(hex.to_string()))
+    }
+
+    /// Decode the hexadecimal digits into bytes, skipping the `_` and whitespace separators
+    ///
+    /// ```
+    /// use s_expr::{ABytes, BytesDecodeError};
+    ///
+    /// assert_eq!(ABytes("00fF_10").to_vec(), Ok(vec![0x00, 0xff, 0x10]));
+    /// assert_eq!(ABytes("0_01").to_vec(), Err(BytesDecodeError::OddLength { offset: 3 }));
+    /// assert_eq!(ABytes("0g").to_vec(), Err(BytesDecodeError::InvalidChar { offset: 1, ch: 'g' }));
+    /// ```
+    pub fn to_vec(&self) -> Result<Vec<u8>, BytesDecodeError> {
+        let mut out = Vec::with_capacity(self.0.len() / 2);
+        self.decode_into(&mut out)?;
+        Ok(out)
+    }
+
+    /// Decode the bytes like [`ABytes::to_vec`], appending them to an existing buffer
+    ///
+    /// On error the buffer is left as it was before the call
+    pub fn decode_into(&self, out: &mut Vec<u8>) -> Result<(), BytesDecodeError> {
+        let len = out.len();
+        let r = self.decode(|b| out.push(b));
+        if r.is_err() {
+            out.truncate(len);
+        }
+        r
+    }
+
+    /// Number of bytes of the literal, which is half the number of digits, without decoding it
+    ///
+    /// ```
+    /// use s_expr::ABytes;
+    ///
+    /// assert_eq!(ABytes("0011_2233").byte_len(), Ok(4));
+    /// assert!(ABytes("001").byte_len().is_err());
+    /// ```
+    pub fn byte_len(&self) -> Result<usize, BytesDecodeError> {
+        let mut len = 0;
+        self.decode(|_| len += 1)?;
+        Ok(len)
+    }
+
+    /// Check if the literal has no digits, e.g. `##` or `#__#`
+    pub fn is_empty(&self) -> bool {
+        self.0.chars().all(|c| c == '_' || c.is_ascii_whitespace())
+    }
+
+    /// Check that the literal decodes like [`ABytes::to_vec`], without allocating
+    pub fn validate(&self) -> Result<(), BytesDecodeError> {
+        self.decode(|_| ())
+    }
+
+    /// Iterate over the decoded bytes without allocating, decoding the digits in pairs on the fly
+    ///
+    /// The iterator stops after the first error, which is the same as the one of [`ABytes::to_vec`].
+    ///
+    /// ```
+    /// use s_expr::{ABytes, BytesDecodeError};
+    ///
+    /// let mut bytes = ABytes("01_2").iter_bytes();
+    /// assert_eq!(bytes.next(), Some(Ok(0x01)));
+    /// assert_eq!(bytes.next(), Some(Err(BytesDecodeError::OddLength { offset: 3 })));
+    /// assert_eq!(bytes.next(), None);
+    /// ```
+    pub fn iter_bytes(&self) -> impl Iterator<Item = Result<u8, BytesDecodeError>> + 'a {
+        BytesDecoder {
+            chars: self.0.char_indices(),
+            done: false,
+        }
+    }
+
+    // decode the digits in pairs, calling f for each byte
+    fn decode<F: FnMut(u8)>(&self, mut f: F) -> Result<(), BytesDecodeError> {
+        for b in self.iter_bytes() {
+            f(b?)
+        }
+        Ok(())
+    }
+}
+
+// decoder of the digits of a bytes literal, which stops after the first error
+struct BytesDecoder<'a> {
+    chars: core::str::CharIndices<'a>,
+    done: bool,
+}
+
+impl<'a> BytesDecoder<'a> {
+    // next digit with its offset, skipping the separators
+    fn digit(&mut self) -> Option<Result<(usize, u8), BytesDecodeError>> {
+        let (offset, ch) = self
+            .chars
+            .find(|(_, ch)| *ch != '_' && !ch.is_ascii_whitespace())?;
+        Some(match ch.to_digit(16) {
+            Some(v) => Ok((offset, v as u8)),
+            None => Err(BytesDecodeError::InvalidChar { offset, ch }),
+        })
+    }
+}
+
+impl<'a> Iterator for BytesDecoder<'a> {
+    type Item = Result<u8, BytesDecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let r = match self.digit()? {
+            Err(e) => Err(e),
+            Ok((offset, high)) => match self.digit() {
+                None => Err(BytesDecodeError::OddLength { offset }),
+                Some(Err(e)) => Err(e),
+                Some(Ok((_, low))) => Ok(high << 4 | low),
+            },
+        };
+        self.done = r.is_err();
+        Some(r)
+    }
+}
+
 /// Supported number base
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ANumBase {
     /// Binary Base (2), made of '0'..'1'
     Binary = 2,
@@ -101,6 +1085,15 @@ impl ANumBase {
         self as u32
     }
 
+    /// Return the prefix of the number literals in the base, `0b`, `0x` or nothing for decimal
+    pub fn prefix(self) -> &'static str {
+        match self {
+            ANumBase::Binary => "0b",
+            ANumBase::Decimal => "",
+            ANumBase::Hexadecimal => "0x",
+        }
+    }
+
     pub fn from_radix(v: u32) -> Option<Self> {
         if v == 2 {
             Some(Self::Binary)
@@ -115,6 +1108,12 @@ impl ANumBase {
 }
 
 /// Integral Number
+///
+/// Two numbers are equal if they have the same value, whatever their bases, separators and
+/// leading zeros, e.g. `0x10`, `0b1_0000` and `016`
+///
+/// The number literals have no sign, so the conversions to the signed integers only fail
+/// above the maximum of the type.
 #[derive(Clone, Debug)]
 pub struct ANum<'a> {
     pub base: ANumBase,
@@ -144,6 +1143,155 @@ impl<'a> ANum<'a> {
         self.dat.chars().filter(|c| *c != '_').collect::<String>()
     }
 
+    /// Get the number as the nearest f64, with true if it is exactly the value of the number
+    ///
+    /// The numbers of any size and base are converted, the numbers above `f64::MAX` being converted
+    /// to infinity, which is not exact.
+    ///
+    /// ```
+    /// use s_expr::{ANum, ANumBase};
+    ///
+    /// assert_eq!(ANum { base: ANumBase::Hexadecimal, dat: "ff" }.to_f64(), (255.0, true));
+    /// let large = ANum { base: ANumBase::Decimal, dat: "9007199254740993" };
+    /// assert_eq!(large.to_f64(), (9007199254740992.0, false));
+    /// ```
+    pub fn to_f64(&self) -> (f64, bool) {
+        let digits = self.digits_in(ANumBase::Decimal);
+        // the parsing of the decimal digits gives the nearest f64
+        let v = digits.parse::<f64>().unwrap_or(f64::INFINITY);
+        let exact = v.is_finite() && format!("{:.0}", v) == digits;
+        (v, exact)
+    }
+
+    /// Get the number of digits written, the '_' separators excluded
+    pub fn digit_count(&self) -> usize {
+        self.dat.chars().filter(|c| *c != '_').count()
+    }
+
+    /// Get the number of zero digits written before the first non zero digit, e.g. 2 for `0x00ff`,
+    /// the '_' separators excluded. All the digits of a zero number are leading zeros
+    pub fn leading_zero_digits(&self) -> usize {
+        self.dat
+            .chars()
+            .filter(|c| *c != '_')
+            .take_while(|c| *c == '0')
+            .count()
+    }
+
+    /// Get the number of bytes written by an hexadecimal number, leading zeros included, e.g.
+    /// 2 for `0x00ff` and 1 for `0xff`, or None if the number is not hexadecimal
+    ///
+    /// An odd number of digits is rounded up, so `0xfff` is 2 bytes
+    pub fn byte_width(&self) -> Option<usize> {
+        match self.base {
+            ANumBase::Hexadecimal => Some(self.digit_count().div_ceil(2)),
+            _ => None,
+        }
+    }
+
+    // digits of the number without separators and leading zeros, in lowercase
+    fn significant_digits(&self) -> impl Iterator<Item = char> + 'a {
+        self.dat
+            .chars()
+            .filter(|c| *c != '_')
+            .skip_while(|c| *c == '0')
+            .map(|c| c.to_ascii_lowercase())
+    }
+
+    /// Get the canonical text of the number: its value in decimal, without leading zeros or separators,
+    /// whatever the base of the literal
+    ///
+    /// The conversion never fails, the numbers that don't fit in a u128 are converted with
+    /// arbitrary precision. There's never a sign, as the number literals have none.
+    ///
+    /// ```
+    /// use s_expr::{ANum, ANumBase};
+    ///
+    /// let hex = ANum { base: ANumBase::Hexadecimal, dat: "0_10" };
+    /// let dec = ANum { base: ANumBase::Decimal, dat: "1_6" };
+    /// assert_eq!(hex.normalized(), "16");
+    /// assert_eq!(dec.normalized(), "16");
+    /// ```
+    pub fn normalized(&self) -> String {
+        let mut out = String::new();
+        // writing to a String doesn't fail
+        let _ = self.write_normalized(&mut out);
+        out
+    }
+
+    /// Write the canonical text of the number like [`ANum::normalized`]
+    ///
+    /// Nothing is allocated for the numbers that fit in a u128.
+    pub fn write_normalized<W: fmt::Write + ?Sized>(&self, out: &mut W) -> fmt::Result {
+        let radix = self.base.to_radix();
+        let value = self
+            .dat
+            .chars()
+            .filter_map(|c| c.to_digit(radix))
+            .try_fold(0u128, |v, d| {
+                v.checked_mul(radix as u128)?.checked_add(d as u128)
+            });
+        match value {
+            Some(v) => write!(out, "{}", v),
+            None => out.write_str(&self.digits_in(ANumBase::Decimal)),
+        }
+    }
+
+    // digits of the number in the given base, without leading zeros and lowercase, for any size of number
+    pub(crate) fn digits_in(&self, base: ANumBase) -> String {
+        // little endian limbs of `width` digits in the target base
+        let (limb, width) = match base {
+            ANumBase::Binary => (1u64 << 30, 30),
+            ANumBase::Decimal => (1_000_000_000, 9),
+            ANumBase::Hexadecimal => (1u64 << 28, 7),
+        };
+        let radix = self.base.to_radix() as u64;
+        let mut limbs: Vec<u64> = vec![0];
+        for d in self.dat.chars().filter_map(|c| c.to_digit(radix as u32)) {
+            let mut carry = d as u64;
+            for l in limbs.iter_mut() {
+                let v = *l * radix + carry;
+                *l = v % limb;
+                carry = v / limb;
+            }
+            if carry > 0 {
+                limbs.push(carry);
+            }
+        }
+        let format = |v: u64, pad: usize| match base {
+            ANumBase::Binary => format!("{:0pad$b}", v, pad = pad),
+            ANumBase::Decimal => format!("{:0pad$}", v, pad = pad),
+            ANumBase::Hexadecimal => format!("{:0pad$x}", v, pad = pad),
+        };
+        let mut out = format(limbs.pop().unwrap_or(0), 0);
+        for l in limbs.iter().rev() {
+            out.push_str(&format(*l, width));
+        }
+        out
+    }
+
+    /// Get the number as an arbitrary precision unsigned integer, which never overflows,
+    /// or None if the digits are not valid digits of the base, e.g. if the number is empty
+    ///
+    /// ```
+    /// use s_expr::{ANum, ANumBase};
+    ///
+    /// let num = ANum { base: ANumBase::Hexadecimal, dat: "1_0000_0000_0000_0000" };
+    /// assert_eq!(num.to_biguint(), Some(num_bigint::BigUint::from(1u128 << 64)));
+    /// assert_eq!(ANum { base: ANumBase::Binary, dat: "102" }.to_biguint(), None);
+    /// ```
+    #[cfg(feature = "bigint")]
+    pub fn to_biguint(&self) -> Option<num_bigint::BigUint> {
+        num_bigint::BigUint::parse_bytes(self.digits().as_bytes(), self.base.to_radix())
+    }
+
+    /// Get the number as an arbitrary precision signed integer, which is always positive,
+    /// or None if the digits are not valid like [`ANum::to_biguint`]
+    #[cfg(feature = "bigint")]
+    pub fn to_bigint(&self) -> Option<num_bigint::BigInt> {
+        self.to_biguint().map(Into::into)
+    }
+
     /// Try to parse the ANum into a u8, which will raise an error if there's an overflow
     pub fn to_u8(&self) -> Result<u8, core::num::ParseIntError> {
         u8::from_str_radix(&self.digits(), self.base.to_radix())
@@ -168,15 +1316,312 @@ impl<'a> ANum<'a> {
     pub fn to_u128(&self) -> Result<u128, core::num::ParseIntError> {
         u128::from_str_radix(&self.digits(), self.base.to_radix())
     }
+
+    /// Try to parse the ANum into a usize, which will raise an error if the number is above the
+    /// `usize::MAX` of the target, rather than truncating it on 32 bits targets
+    pub fn to_usize(&self) -> Result<usize, core::num::ParseIntError> {
+        usize::from_str_radix(&self.digits(), self.base.to_radix())
+    }
+
+    /// Try to parse the ANum into an i8, which will raise an error if the number is above `i8::MAX`
+    pub fn to_i8(&self) -> Result<i8, core::num::ParseIntError> {
+        i8::from_str_radix(&self.digits(), self.base.to_radix())
+    }
+
+    /// Try to parse the ANum into an i16, which will raise an error if the number is above `i16::MAX`
+    pub fn to_i16(&self) -> Result<i16, core::num::ParseIntError> {
+        i16::from_str_radix(&self.digits(), self.base.to_radix())
+    }
+
+    /// Try to parse the ANum into an i32, which will raise an error if the number is above `i32::MAX`
+    pub fn to_i32(&self) -> Result<i32, core::num::ParseIntError> {
+        i32::from_str_radix(&self.digits(), self.base.to_radix())
+    }
+
+    /// Try to parse the ANum into an i64, which will raise an error if the number is above `i64::MAX`
+    pub fn to_i64(&self) -> Result<i64, core::num::ParseIntError> {
+        i64::from_str_radix(&self.digits(), self.base.to_radix())
+    }
+
+    /// Try to parse the ANum into an i128, which will raise an error if the number is above `i128::MAX`
+    pub fn to_i128(&self) -> Result<i128, core::num::ParseIntError> {
+        i128::from_str_radix(&self.digits(), self.base.to_radix())
+    }
+
+    /// Try to parse the ANum into an isize, which will raise an error if the number is above the
+    /// `isize::MAX` of the target
+    pub fn to_isize(&self) -> Result<isize, core::num::ParseIntError> {
+        isize::from_str_radix(&self.digits(), self.base.to_radix())
+    }
+}
+
+/// The literal syntax of the number, in its base with the `0b` or `0x` prefix and the digits as
+/// written, e.g. `0x1_ab`. The alternate form `{:#}` strips the `_` separators, e.g. `0x1ab`
+impl<'a> fmt::Display for ANum<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.base.prefix())?;
+        if f.alternate() {
+            self.dat
+                .split('_')
+                .try_for_each(|digits| f.write_str(digits))
+        } else {
+            f.write_str(self.dat)
+        }
+    }
+}
+
+impl<'a> PartialEq for ANum<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        if self.base == other.base {
+            self.significant_digits().eq(other.significant_digits())
+        } else {
+            self.digits_in(ANumBase::Decimal) == other.digits_in(ANumBase::Decimal)
+        }
+    }
+}
+
+impl<'a> Eq for ANum<'a> {}
+
+impl<'a> PartialOrd for ANum<'a> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// The numbers are ordered by value
+impl<'a> Ord for ANum<'a> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // without leading zeros, the longest number is the largest
+        let d1 = self.digits_in(ANumBase::Decimal);
+        let d2 = other.digits_in(ANumBase::Decimal);
+        d1.len().cmp(&d2.len()).then_with(|| d1.cmp(&d2))
+    }
+}
+
+impl<'a> Hash for ANum<'a> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.digits_in(ANumBase::Decimal).hash(state)
+    }
+}
+
+// conversions of the number, and of the atom when it is a number, to the integer primitives,
+// using the inherent methods
+macro_rules! anum_try_from {
+    ($($ty:ty => $method:ident),*) => {
+        $(
+            impl<'a, 'b> TryFrom<&'b ANum<'a>> for $ty {
+                type Error = core::num::ParseIntError;
+
+                fn try_from(num: &'b ANum<'a>) -> Result<Self, Self::Error> {
+                    num.$method()
+                }
+            }
+
+            impl<'a, 'b> TryFrom<&'b Atom<'a>> for $ty {
+                type Error = FromAtomError;
+
+                fn try_from(atom: &'b Atom<'a>) -> Result<Self, Self::Error> {
+                    match atom {
+                        Atom::Integral(num) => num.$method().map_err(FromAtomError::Int),
+                        _ => Err(FromAtomError::unexpected(&[AtomKind::Integral], atom)),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+anum_try_from!(
+    u8 => to_u8, u16 => to_u16, u32 => to_u32, u64 => to_u64, u128 => to_u128, usize => to_usize,
+    i8 => to_i8, i16 => to_i16, i32 => to_i32, i64 => to_i64, i128 => to_i128, isize => to_isize
+);
+
+/// Error converting an atom to a Rust value with `TryFrom`
+///
+/// ```
+/// use s_expr::{Atom, AtomKind, FromAtomError};
+///
+/// let n: Result<u32, _> = (&Atom::Ident("x")).try_into();
+/// let expected = &[AtomKind::Integral];
+/// assert_eq!(n, Err(FromAtomError::UnexpectedKind { expected, found: AtomKind::Ident }));
+/// assert_eq!(n.unwrap_err().to_string(), "expected integral number, found ident");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FromAtomError {
+    /// The atom is not of one of the expected kinds
+    UnexpectedKind {
+        expected: &'static [AtomKind],
+        found: AtomKind,
+    },
+    /// The atom is not one of the idents `true` and `false`
+    NotBool { found: AtomKind },
+    /// The integral number doesn't fit in the integer type
+    Int(core::num::ParseIntError),
+    /// The string has an invalid escape
+    Escape(EscapeError),
+    /// The bytes literal doesn't decode
+    Bytes(BytesDecodeError),
+}
+
+impl FromAtomError {
+    fn unexpected(expected: &'static [AtomKind], atom: &Atom) -> Self {
+        FromAtomError::UnexpectedKind {
+            expected,
+            found: atom.kind(),
+        }
+    }
+}
+
+impl fmt::Display for FromAtomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FromAtomError::UnexpectedKind { expected, found } => {
+                f.write_str("expected ")?;
+                for (i, kind) in expected.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(" or ")?;
+                    }
+                    write!(f, "{}", kind)?;
+                }
+                write!(f, ", found {}", found)
+            }
+            FromAtomError::NotBool { found } => {
+                write!(f, "expected `true` or `false`, found {}", found)
+            }
+            FromAtomError::Int(e) => write!(f, "invalid integral number: {}", e),
+            FromAtomError::Escape(e) => write!(f, "invalid string: {}", e),
+            FromAtomError::Bytes(e) => write!(f, "invalid bytes: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for FromAtomError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FromAtomError::UnexpectedKind { .. } | FromAtomError::NotBool { .. } => None,
+            FromAtomError::Int(e) => Some(e),
+            FromAtomError::Escape(e) => Some(e),
+            FromAtomError::Bytes(e) => Some(e),
+        }
+    }
+}
+
+/// The nearest f64 of an integral or decimal number
+impl<'a, 'b> TryFrom<&'b Atom<'a>> for f64 {
+    type Error = FromAtomError;
+
+    fn try_from(atom: &'b Atom<'a>) -> Result<Self, Self::Error> {
+        match atom {
+            Atom::Integral(num) => Ok(num.to_f64().0),
+            // the literal without separators is a valid float syntax
+            Atom::Decimal(dec) => Ok(dec.to_literal_string().parse().unwrap_or(f64::INFINITY)),
+            _ => Err(FromAtomError::unexpected(
+                &[AtomKind::Integral, AtomKind::Decimal],
+                atom,
+            )),
+        }
+    }
+}
+
+/// The content of a string, with the escapes resolved
+impl<'a, 'b> TryFrom<&'b Atom<'a>> for Cow<'a, str> {
+    type Error = FromAtomError;
+
+    fn try_from(atom: &'b Atom<'a>) -> Result<Self, Self::Error> {
+        match atom {
+            Atom::String(astr) => astr.try_decoded().map_err(FromAtomError::Escape),
+            _ => Err(FromAtomError::unexpected(&[AtomKind::String], atom)),
+        }
+    }
+}
+
+/// The content of a string, with the escapes resolved
+impl<'a, 'b> TryFrom<&'b Atom<'a>> for String {
+    type Error = FromAtomError;
+
+    fn try_from(atom: &'b Atom<'a>) -> Result<Self, Self::Error> {
+        Cow::try_from(atom).map(Cow::into_owned)
+    }
+}
+
+/// The decoded bytes of a bytes literal
+impl<'a, 'b> TryFrom<&'b Atom<'a>> for Vec<u8> {
+    type Error = FromAtomError;
+
+    fn try_from(atom: &'b Atom<'a>) -> Result<Self, Self::Error> {
+        match atom {
+            Atom::Bytes(bytes) => bytes.to_vec().map_err(FromAtomError::Bytes),
+            _ => Err(FromAtomError::unexpected(&[AtomKind::Bytes], atom)),
+        }
+    }
+}
+
+/// The boolean value of the idents `true` and `false`, see [`Atom::as_bool`]
+impl<'a, 'b> TryFrom<&'b Atom<'a>> for bool {
+    type Error = FromAtomError;
+
+    fn try_from(atom: &'b Atom<'a>) -> Result<Self, Self::Error> {
+        atom.as_bool()
+            .ok_or(FromAtomError::NotBool { found: atom.kind() })
+    }
 }
 
 /// Decimal Number (e.g. `1.3`)
+///
+/// Two decimal numbers are equal if they have the same value, ignoring the separators, the
+/// leading zeros of the integral part and the trailing zeros of the fractional part, e.g. `01.50`
+/// and `1.5`
 #[derive(Clone, Debug)]
 pub struct ADecimal<'a> {
     pub raw_integral: &'a str,
     pub raw_fractional: &'a str,
 }
 
+impl<'a> PartialEq for ADecimal<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        self.significant_digits() == other.significant_digits()
+    }
+}
+
+impl<'a> Eq for ADecimal<'a> {}
+
+impl<'a> PartialOrd for ADecimal<'a> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// The decimal numbers are ordered by value
+impl<'a> Ord for ADecimal<'a> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (i1, f1) = self.significant_digits();
+        let (i2, f2) = other.significant_digits();
+        // without trailing zeros, the fractional parts compare as text
+        i1.len()
+            .cmp(&i2.len())
+            .then_with(|| i1.cmp(&i2))
+            .then_with(|| f1.cmp(&f2))
+    }
+}
+
+impl<'a> Hash for ADecimal<'a> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.significant_digits().hash(state)
+    }
+}
+
+/// The literal syntax of the decimal number, with the digits as written, e.g. `1_000.50`. The
+/// alternate form `{:#}` strips the `_` separators like [`ADecimal::to_literal_string`], e.g. `1000.50`
+impl<'a> fmt::Display for ADecimal<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            write!(f, "{}.{}", self.integral(), self.fractional())
+        } else {
+            write!(f, "{}.{}", self.raw_integral, self.raw_fractional)
+        }
+    }
+}
+
 impl<'a> ADecimal<'a> {
     /// Get the data associated with the integral number. All '_' characters are filtered away
     pub fn integral(&self) -> String {
@@ -193,4 +1638,142 @@ impl<'a> ADecimal<'a> {
             .filter(|c| *c != '_')
             .collect::<String>()
     }
+
+    // integral and fractional digits without separators, leading and trailing zeros
+    fn significant_digits(&self) -> (String, String) {
+        let integral = self.integral();
+        let fractional = self.fractional();
+        (
+            integral.trim_start_matches('0').to_string(),
+            fractional.trim_end_matches('0').to_string(),
+        )
+    }
+
+    /// Get the literal of the number without the `_` separators, keeping the leading and trailing
+    /// zeros, e.g. `1000.50` for `1_000.50`, which is the alternate form of the display
+    pub fn to_literal_string(&self) -> String {
+        format!("{:#}", self)
+    }
+
+    /// Get the number of fractional digits, the '_' separators excluded, e.g. 2 for `1.50`
+    ///
+    /// The trailing zeros are counted, so this is the scale as written in the literal, see
+    /// [`ADecimal::minimal_scale`] for the scale without them.
+    ///
+    /// ```
+    /// use s_expr::ADecimal;
+    ///
+    /// let dec = ADecimal { raw_integral: "1", raw_fractional: "50" };
+    /// assert_eq!((dec.scale(), dec.minimal_scale()), (2, 1));
+    /// ```
+    pub fn scale(&self) -> usize {
+        self.raw_fractional.chars().filter(|c| *c != '_').count()
+    }
+
+    /// Get the number of zeros at the end of the fractional digits, the '_' separators excluded,
+    /// e.g. 1 for `1.50` and 2 for `1.00`
+    pub fn trailing_zeros(&self) -> usize {
+        self.raw_fractional
+            .chars()
+            .rev()
+            .filter(|c| *c != '_')
+            .take_while(|c| *c == '0')
+            .count()
+    }
+
+    /// Get the number of fractional digits without the trailing zeros, e.g. 1 for `1.50` and 0 for `1.00`
+    ///
+    /// ```
+    /// use s_expr::ADecimal;
+    ///
+    /// let dec = ADecimal { raw_integral: "1", raw_fractional: "5_00" };
+    /// assert_eq!((dec.scale(), dec.trailing_zeros(), dec.minimal_scale()), (3, 2, 1));
+    /// ```
+    pub fn minimal_scale(&self) -> usize {
+        self.scale() - self.trailing_zeros()
+    }
+
+    /// Get the value multiplied by 10^scale as an integer, without going through a float
+    ///
+    /// The fractional digits beyond the scale must be zeros, otherwise the precision loss is an
+    /// error, see [`ADecimal::to_scaled_int_rounded`] to round them instead.
+    ///
+    /// ```
+    /// use s_expr::{ADecimal, DecimalError};
+    ///
+    /// let amount = ADecimal { raw_integral: "1_000", raw_fractional: "050" };
+    /// assert_eq!(amount.to_scaled_int(2), Ok(100005));
+    /// assert_eq!(amount.to_scaled_int(4), Ok(10000500));
+    /// assert_eq!(amount.to_scaled_int(1), Err(DecimalError::PrecisionLoss));
+    /// ```
+    pub fn to_scaled_int(&self, scale: u32) -> Result<i128, DecimalError> {
+        self.scaled(scale, false)
+    }
+
+    /// Get the value multiplied by 10^scale as an integer like [`ADecimal::to_scaled_int`], rounding
+    /// half up the fractional digits beyond the scale, e.g. `1.005` is 101 with a scale of 2
+    pub fn to_scaled_int_rounded(&self, scale: u32) -> Result<i128, DecimalError> {
+        self.scaled(scale, true)
+    }
+
+    /// Get the exact value as a mantissa and a scale, the number of fractional digits,
+    /// e.g. `(150, 2)` for `1.50`
+    pub fn mantissa_and_scale(&self) -> Result<(i128, u32), DecimalError> {
+        let scale = u32::try_from(self.scale()).map_err(|_| DecimalError::Overflow)?;
+        Ok((self.scaled(scale, false)?, scale))
+    }
+
+    fn scaled(&self, scale: u32, round: bool) -> Result<i128, DecimalError> {
+        let push = |v: i128, d: u32| {
+            v.checked_mul(10)
+                .and_then(|v| v.checked_add(d as i128))
+                .ok_or(DecimalError::Overflow)
+        };
+        let digits = |s: &'a str| s.chars().filter_map(|c| c.to_digit(10));
+        let mut v = 0i128;
+        for d in digits(self.raw_integral) {
+            v = push(v, d)?;
+        }
+        let mut fractional = digits(self.raw_fractional);
+        for _ in 0..scale {
+            v = push(v, fractional.next().unwrap_or(0))?;
+        }
+        match fractional.next() {
+            None => Ok(v),
+            Some(d) if round => {
+                if d >= 5 {
+                    v.checked_add(1).ok_or(DecimalError::Overflow)
+                } else {
+                    Ok(v)
+                }
+            }
+            Some(d) => {
+                if d == 0 && fractional.all(|d| d == 0) {
+                    Ok(v)
+                } else {
+                    Err(DecimalError::PrecisionLoss)
+                }
+            }
+        }
+    }
+}
+
+/// Error converting a decimal number to an integer, see [`ADecimal::to_scaled_int`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecimalError {
+    /// The scaled value doesn't fit in an i128
+    Overflow,
+    /// There are non zero fractional digits beyond the scale
+    PrecisionLoss,
 }
+
+impl fmt::Display for DecimalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecimalError::Overflow => write!(f, "scaled value overflows an i128"),
+            DecimalError::PrecisionLoss => write!(f, "fractional digits lost beyond the scale"),
+        }
+    }
+}
+
+impl std::error::Error for DecimalError {}
@@ -0,0 +1,286 @@
+//! [`miette::Diagnostic`] implementations of the errors of the tokenizer and the parser
+//!
+//! The errors don't keep the source, which is attached to the report to render the labels:
+//!
+//! ```
+//! use miette::{NarratableReportHandler, Report};
+//! use s_expr::Parser;
+//!
+//! let source = "(a\n  (b c]";
+//! let err = Parser::new(source).next().unwrap_err();
+//! let report = Report::new(err).with_source_code(source);
+//! let mut out = String::new();
+//! NarratableReportHandler::new()
+//!     .render_report(&mut out, report.as_ref())
+//!     .unwrap();
+//! assert_eq!(
+//!     out,
+//!     "line 2, columns 2-7: closing brackets `[]` while parentheses `()` are open, expected `)`
+//!     Diagnostic severity: error
+//! Begin snippet starting at line 1, column 1
+//!
+//! snippet line 1: (a
+//! snippet line 2:   (b c]
+//!     label at line 2, column 3: parentheses `()` opened here
+//!     label at line 2, column 7: closed with `]`
+//! diagnostic help: did you mean to close the `(` opened here with `)`?
+//! diagnostic code: sexpr::unbalanced_mismatch
+//! "
+//! );
+//! ```
+
+use super::data::GroupKind;
+use super::loc::Position;
+use super::parser::ParserError;
+use super::tokenizer::TokenError;
+use core::fmt;
+use miette::{Diagnostic, LabeledSpan};
+
+type Labels<'a> = Option<Box<dyn Iterator<Item = LabeledSpan> + 'a>>;
+
+fn code<'a>(code: &'static str) -> Option<Box<dyn fmt::Display + 'a>> {
+    Some(Box::new(code))
+}
+
+fn labels<'a>(labels: Vec<LabeledSpan>) -> Labels<'a> {
+    Some(Box::new(labels.into_iter()))
+}
+
+// label of the characters from the position, the length being in bytes
+fn label(text: String, pos: Position, len: usize) -> LabeledSpan {
+    LabeledSpan::new(Some(text), pos.offset, len)
+}
+
+impl Diagnostic for TokenError {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        code(match self {
+            TokenError::DataError(_, _) => "sexpr::invalid_data",
+            TokenError::UnterminatedString(_) => "sexpr::unterminated_string",
+            TokenError::UnterminatedBytes(_) => "sexpr::unterminated_bytes",
+            TokenError::UnprocessedChar(_, _) => "sexpr::unexpected_char",
+            TokenError::UnterminatedBytesChar(_, _) => "sexpr::invalid_bytes_char",
+            TokenError::InvalidEscape(_, _) => "sexpr::invalid_escape",
+        })
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        let help = match self {
+            TokenError::UnterminatedString(_) => "close the string with a `\"`",
+            TokenError::UnterminatedBytes(_) => "close the bytes with a `#`",
+            TokenError::UnterminatedBytesChar(_, _) => {
+                "the bytes are hexadecimal digits between `#`, separated with `_`"
+            }
+            TokenError::InvalidEscape(_, _) => {
+                "the escapes are `\\n`, `\\r`, `\\t`, `\\\\`, `\\\"`, `\\0`, `\\xNN` and `\\u{N...}`"
+            }
+            TokenError::DataError(_, _) | TokenError::UnprocessedChar(_, _) => return None,
+        };
+        Some(Box::new(help))
+    }
+
+    fn labels(&self) -> Labels<'_> {
+        match self {
+            TokenError::DataError(_, offset) => labels(vec![LabeledSpan::new(
+                Some("invalid data".to_string()),
+                *offset,
+                1,
+            )]),
+            TokenError::UnterminatedString(pos) => {
+                labels(vec![label("end of the source".to_string(), *pos, 0)])
+            }
+            TokenError::UnterminatedBytes(pos) => {
+                labels(vec![label("end of the source".to_string(), *pos, 0)])
+            }
+            TokenError::UnprocessedChar(pos, ch) => labels(vec![label(
+                "not the start of a token".to_string(),
+                *pos,
+                ch.len_utf8(),
+            )]),
+            TokenError::UnterminatedBytesChar(pos, ch) => labels(vec![label(
+                "not a hexadecimal digit".to_string(),
+                *pos,
+                ch.len_utf8(),
+            )]),
+            TokenError::InvalidEscape(pos, e) => {
+                labels(vec![label(e.kind().to_string(), *pos, e.sequence().len())])
+            }
+        }
+    }
+}
+
+// the closing delimiter right before the position
+fn closing(end: Position, grp: GroupKind) -> Position {
+    Position {
+        offset: end.offset - grp.close_char().len_utf8(),
+        ..end
+    }
+}
+
+impl Diagnostic for ParserError {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        match self {
+            ParserError::UnbalancedEmpty(_, _) => code("sexpr::unbalanced_empty"),
+            ParserError::UnbalancedMismatch { .. } => code("sexpr::unbalanced_mismatch"),
+            ParserError::UnfinishedGroup(_) => code("sexpr::unfinished_group"),
+            ParserError::TokenizerError(e) => e.code(),
+        }
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        match self {
+            ParserError::UnbalancedEmpty(_, grp) => Some(Box::new(format!(
+                "remove the `{}`, or open the group with a `{}` before",
+                grp.close_char(),
+                grp.open_char()
+            ))),
+            ParserError::UnbalancedMismatch { expected, .. } => Some(Box::new(format!(
+                "did you mean to close the `{}` opened here with `{}`?",
+                expected.open_char(),
+                expected.close_char()
+            ))),
+            ParserError::UnfinishedGroup(grp) => Some(Box::new(format!(
+                "close the group with a `{}` at the end",
+                grp.close_char()
+            ))),
+            ParserError::TokenizerError(e) => e.help(),
+        }
+    }
+
+    fn labels(&self) -> Labels<'_> {
+        match self {
+            ParserError::UnbalancedEmpty(pos, grp) => labels(vec![label(
+                "no group is open".to_string(),
+                *pos,
+                grp.close_char().len_utf8(),
+            )]),
+            ParserError::UnbalancedMismatch {
+                span,
+                expected,
+                got,
+            } => labels(vec![
+                label(
+                    format!("{} opened here", expected),
+                    span.start,
+                    expected.open_char().len_utf8(),
+                ),
+                label(
+                    format!("closed with `{}`", got.close_char()),
+                    closing(span.end, *got),
+                    got.close_char().len_utf8(),
+                ),
+            ]),
+            // the end of the source is unknown
+            ParserError::UnfinishedGroup(_) => None,
+            ParserError::TokenizerError(e) => e.labels(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::tokenizer::TokenizerConfig;
+    use miette::SourceSpan;
+
+    fn error(source: &str) -> ParserError {
+        let cfg = TokenizerConfig::default().strict_escapes(true);
+        let mut parser = Parser::new_with_config(source, cfg);
+        loop {
+            match parser.next() {
+                Ok(Some(_)) => {}
+                Ok(None) => panic!("no error in {:?}", source),
+                Err(e) => return e,
+            }
+        }
+    }
+
+    // the code and the labelled text of the source with their label
+    fn diagnostic<'a>(source: &'a str, e: &dyn Diagnostic) -> (String, Vec<(&'a str, String)>) {
+        let labels = e
+            .labels()
+            .into_iter()
+            .flatten()
+            .map(|label| {
+                let span: SourceSpan = *label.inner();
+                let text = &source[span.offset()..span.offset() + span.len()];
+                (text, label.label().unwrap_or("").to_string())
+            })
+            .collect();
+        (e.code().expect("code").to_string(), labels)
+    }
+
+    #[test]
+    fn parser_diagnostics() {
+        let source = "(a\n  [é b)";
+        let (code, labels) = diagnostic(source, &error(source));
+        assert_eq!(code, "sexpr::unbalanced_mismatch");
+        assert_eq!(
+            labels,
+            [
+                ("[", "brackets `[]` opened here".to_string()),
+                (")", "closed with `)`".to_string()),
+            ]
+        );
+        let e = error(source);
+        assert_eq!(
+            e.help().map(|help| help.to_string()).as_deref(),
+            Some("did you mean to close the `[` opened here with `]`?")
+        );
+
+        let source = " (a) }";
+        let (code, labels) = diagnostic(source, &error(source));
+        assert_eq!(code, "sexpr::unbalanced_empty");
+        assert_eq!(labels, [("}", "no group is open".to_string())]);
+
+        let e = error("(a");
+        assert_eq!(
+            diagnostic("(a", &e),
+            ("sexpr::unfinished_group".to_string(), vec![])
+        );
+        assert!(e.help().is_some());
+    }
+
+    #[test]
+    fn tokenizer_diagnostics() {
+        let cases = [
+            (
+                "(a \"b",
+                "sexpr::unterminated_string",
+                "",
+                "end of the source",
+            ),
+            ("(#01", "sexpr::unterminated_bytes", "", "end of the source"),
+            (
+                "(#01é#)",
+                "sexpr::invalid_bytes_char",
+                "é",
+                "not a hexadecimal digit",
+            ),
+            (
+                "(\"a\\u{d800}\")",
+                "sexpr::invalid_escape",
+                "\\u{d800}",
+                "not a unicode scalar value",
+            ),
+        ];
+        for (source, code, text, label) in cases {
+            let e = error(source);
+            assert_eq!(
+                diagnostic(source, &e),
+                (code.to_string(), vec![(text, label.to_string())]),
+                "{:?}",
+                source
+            );
+            assert!(e.help().is_some());
+        }
+        let source = "(a é\n  \\b)";
+        assert_eq!(
+            diagnostic(source, &error(source)),
+            (
+                "sexpr::unexpected_char".to_string(),
+                vec![("\\", "not the start of a token".to_string())]
+            )
+        );
+    }
+}
@@ -0,0 +1,132 @@
+//! Reprinting of a source with some of its elements replaced
+//!
+//! The replaced elements are located by their spans in the source, and everything
+//! outside of the spans is kept byte for byte, so that the formatting and the comments
+//! of the untouched regions are preserved.
+
+use super::loc::{offset, Span};
+use super::parser::Element;
+use super::printer::to_string;
+use core::fmt;
+
+/// Error of [`reprint_with_edits`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EditError {
+    /// The span is not in the source, or ends before its start
+    OutOfBounds(Span),
+    /// The spans of two edits overlap
+    Overlapping(Span, Span),
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EditError::OutOfBounds(span) => write!(f, "span {} is not in the source", span),
+            EditError::Overlapping(s1, s2) => write!(f, "spans {} and {} overlap", s1, s2),
+        }
+    }
+}
+
+impl std::error::Error for EditError {}
+
+/// Replace the text at each span of the source by the printed element, keeping the rest of the source as is
+///
+/// The spans are the spans of the parsed elements, in any order, and may cover multiple lines.
+/// The elements are printed on one line, with the default config. An empty span inserts
+/// the element at its position.
+///
+/// ```
+/// use s_expr::{reprint_with_edits, ANum, ANumBase, Atom, Element, Parser};
+///
+/// let source = "(server  ; main\n  (port   80))";
+/// let el = Parser::new(source).next().unwrap().unwrap();
+/// let port = &el.paren().unwrap()[2].paren().unwrap()[1];
+/// let number = Atom::Integral(ANum { base: ANumBase::Decimal, dat: "8080" });
+/// let edited = reprint_with_edits(source, &[(port.span, Element::Atom(number))]);
+/// assert_eq!(edited, Ok("(server  ; main\n  (port   8080))".to_string()));
+/// ```
+pub fn reprint_with_edits(source: &str, edits: &[(Span, Element)]) -> Result<String, EditError> {
+    let mut ranges = edits
+        .iter()
+        .map(
+            |(span, el)| match (offset(source, span.start), offset(source, span.end)) {
+                (Some(start), Some(end)) if start <= end => Ok((start, end, *span, el)),
+                _ => Err(EditError::OutOfBounds(*span)),
+            },
+        )
+        .collect::<Result<Vec<_>, _>>()?;
+    ranges.sort_by_key(|(start, end, _, _)| (*start, *end));
+    for pair in ranges.windows(2) {
+        let (_, end, span1, _) = pair[0];
+        let (start, _, span2, _) = pair[1];
+        if start < end {
+            return Err(EditError::Overlapping(span1, span2));
+        }
+    }
+    let mut out = String::with_capacity(source.len());
+    let mut pos = 0;
+    for (start, end, _, el) in ranges {
+        out.push_str(&source[pos..start]);
+        out.push_str(&to_string(el));
+        pos = end;
+    }
+    out.push_str(&source[pos..]);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Atom, GroupKind};
+    use crate::loc::{Position, Spanned};
+    use crate::parser::Parser;
+
+    #[test]
+    fn edits() {
+        let source = "; config\n(a   1\n   (b \"x\"\n      2)) ; end\n[c]\n";
+        let mut parser = Parser::new(source);
+        parser.next().unwrap();
+        let el = parser.next().unwrap().unwrap();
+        let el = el.paren().unwrap().to_vec();
+        let one = Element::Atom(Atom::Ident("one"));
+        let b = Element::Group(
+            GroupKind::Paren,
+            vec![Spanned::new(Span::DUMMY, Element::Atom(Atom::Ident("b")))],
+        );
+        // the group covers multiple lines, the edits are in any order
+        assert_eq!(
+            reprint_with_edits(
+                source,
+                &[(el[2].span, b.clone()), (el[1].span, one.clone())]
+            ),
+            Ok("; config\n(a   one\n   (b)) ; end\n[c]\n".to_string())
+        );
+        assert_eq!(reprint_with_edits(source, &[]), Ok(source.to_string()));
+        // insertion at the end of a line
+        let end = Position {
+            line: 4,
+            col: 15,
+            offset: 41,
+        };
+        assert_eq!(
+            reprint_with_edits(source, &[(Span { start: end, end }, one.clone())]),
+            Ok("; config\n(a   1\n   (b \"x\"\n      2)) ; endone\n[c]\n".to_string())
+        );
+
+        let inner = el[2].paren().unwrap()[1].span;
+        assert_eq!(
+            reprint_with_edits(source, &[(el[2].span, b.clone()), (inner, one.clone())]),
+            Err(EditError::Overlapping(el[2].span, inner))
+        );
+        let outside = Span::on_line(2, 5, 20);
+        assert_eq!(
+            reprint_with_edits(source, &[(outside, one.clone())]),
+            Err(EditError::OutOfBounds(outside))
+        );
+        let reversed = Span::on_line(2, 5, 4);
+        assert_eq!(
+            reprint_with_edits(source, &[(reversed, one)]),
+            Err(EditError::OutOfBounds(reversed))
+        );
+    }
+}
@@ -0,0 +1,268 @@
+//! Escaping and unescaping of the string literals
+//!
+//! The supported escapes are `\\`, `\"`, `\n`, `\t`, `\r`, `\0`, `\xNN` for
+//! ascii values (up to 0x7F) and `\u{N..}` with 1 to 6 hexadecimal digits
+//! for any unicode scalar value.
+
+use super::loc::{Position, Span};
+use super::printer::{ControlEscape, PrintConfig};
+use core::fmt;
+
+/// Write the escaped form of a string, without the surrounding quotes
+pub(crate) fn escape<W: fmt::Write + ?Sized>(
+    out: &mut W,
+    s: &str,
+    cfg: &PrintConfig,
+) -> fmt::Result {
+    for c in s.chars() {
+        match c {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' | '\t' | '\r' if !cfg.escape_whitespace => out.write_char(c)?,
+            '\n' => out.write_str("\\n")?,
+            '\t' => out.write_str("\\t")?,
+            '\r' => out.write_str("\\r")?,
+            c if c.is_control() => match cfg.control_escape {
+                ControlEscape::Hex if (c as u32) < 0x80 => write!(out, "\\x{:02x}", c as u32)?,
+                _ => write!(out, "\\u{{{:x}}}", c as u32)?,
+            },
+            c if cfg.ascii_strings && !c.is_ascii() => write!(out, "\\u{{{:x}}}", c as u32)?,
+            c => out.write_char(c)?,
+        }
+    }
+    Ok(())
+}
+
+/// Write the escaped form of a string with the default escapes of the printer, e.g. to build
+/// the raw data of a string atom
+pub(crate) fn escape_default<W: fmt::Write + ?Sized>(out: &mut W, s: &str) -> fmt::Result {
+    escape(out, s, &PrintConfig::default())
+}
+
+/// Invalid escape in a string literal, see [`AStr::try_decoded`](crate::AStr::try_decoded)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EscapeError {
+    kind: EscapeErrorKind,
+    offset: usize,
+    sequence: String,
+}
+
+/// Reason of an invalid escape
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EscapeErrorKind {
+    /// The character after the backslash doesn't start an escape
+    Unknown,
+    /// The string ends in the middle of the escape
+    Truncated,
+    /// A digit of `\xNN` or `\u{N..}` is not an hexadecimal digit
+    InvalidDigit,
+    /// The value of `\xNN` is above 0x7F, which is not an ascii character
+    NotAscii,
+    /// `\u` is not followed by `{`
+    MissingOpeningBrace,
+    /// The string ends before the `}` of `\u{N..}`
+    MissingClosingBrace,
+    /// `\u{}` has no digits
+    EmptyUnicode,
+    /// `\u{N..}` has more than 6 digits
+    TooManyDigits,
+    /// The value of `\u{N..}` is a surrogate or is above 0x10FFFF, which is not a unicode scalar value
+    InvalidScalar,
+}
+
+impl fmt::Display for EscapeErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            EscapeErrorKind::Unknown => "unknown escape",
+            EscapeErrorKind::Truncated => "truncated escape",
+            EscapeErrorKind::InvalidDigit => "invalid hexadecimal digit",
+            EscapeErrorKind::NotAscii => "value above 0x7F",
+            EscapeErrorKind::MissingOpeningBrace => "missing `{` after `\\u`",
+            EscapeErrorKind::MissingClosingBrace => "missing closing `}`",
+            EscapeErrorKind::EmptyUnicode => "no digits in `\\u{}`",
+            EscapeErrorKind::TooManyDigits => "more than 6 digits in `\\u{}`",
+            EscapeErrorKind::InvalidScalar => "not a unicode scalar value",
+        };
+        f.write_str(reason)
+    }
+}
+
+impl EscapeError {
+    /// Reason of the invalid escape
+    pub fn kind(&self) -> EscapeErrorKind {
+        self.kind
+    }
+
+    /// Byte offset of the backslash of the invalid escape in the raw data of the string
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Invalid escape sequence, from the backslash to the first invalid character, or to the
+    /// end of the string if the sequence is truncated
+    pub fn sequence(&self) -> &str {
+        &self.sequence
+    }
+
+    /// Position of the invalid escape in the source, given the span of the string token, e.g.
+    /// to report the error at the escape rather than at the whole string
+    ///
+    /// ```
+    /// use s_expr::{Atom, Position, Token, Tokenizer};
+    ///
+    /// let mut tokenizer = Tokenizer::new("\n  \"ok\\n bad\\q\"");
+    /// let token = tokenizer.next().unwrap().unwrap();
+    /// let Token::Atom(Atom::String(astr)) = &token.inner else { panic!("not a string") };
+    /// let err = astr.try_decoded().unwrap_err();
+    /// assert_eq!(err.sequence(), "\\q");
+    /// let pos = err.position(astr.raw(), token.span);
+    /// assert_eq!((pos.line, pos.col, pos.offset), (2, 11, 12));
+    /// ```
+    pub fn position(&self, raw: &str, span: Span) -> Position {
+        // the raw data starts after the opening quote
+        let mut pos = span.start;
+        pos.advance_col();
+        pos.advance_str(&raw[..self.offset]);
+        pos
+    }
+}
+
+impl fmt::Display for EscapeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid escape `{}` at offset {}: {}",
+            self.sequence, self.offset, self.kind
+        )
+    }
+}
+
+impl std::error::Error for EscapeError {}
+
+/// Resolve the escapes of the raw data of a string literal, or return the first invalid escape
+pub(crate) fn unescape(raw: &str) -> Result<String, EscapeError> {
+    Unescape::new(raw, true)
+        .map(|r| r.map(|(c, _)| c))
+        .collect()
+}
+
+/// Iterator over the characters of the raw data of a string literal with their byte offsets
+/// in the raw data, resolving the escapes if enabled, which stops after the first invalid escape
+pub(crate) struct Unescape<'a> {
+    raw: &'a str,
+    chars: core::str::Chars<'a>,
+    escapes: bool,
+    done: bool,
+}
+
+impl<'a> Unescape<'a> {
+    pub(crate) fn new(raw: &'a str, escapes: bool) -> Self {
+        Unescape {
+            raw,
+            chars: raw.chars(),
+            escapes,
+            done: false,
+        }
+    }
+}
+
+impl<'a> Iterator for Unescape<'a> {
+    type Item = Result<(char, usize), EscapeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let offset = self.raw.len() - self.chars.as_str().len();
+        let c = self.chars.next()?;
+        if c != '\\' || !self.escapes {
+            return Some(Ok((c, offset)));
+        }
+        let rest = self.chars.as_str();
+        Some(match unescape_char(&mut self.chars) {
+            Ok(unescaped) => Ok((unescaped, offset)),
+            Err(kind) => {
+                self.done = true;
+                let consumed = rest.len() - self.chars.as_str().len();
+                Err(EscapeError {
+                    kind,
+                    offset,
+                    sequence: format!("\\{}", &rest[..consumed]),
+                })
+            }
+        })
+    }
+}
+
+/// Resolve the escapes of the raw data of a string literal, keeping the invalid escapes as is
+pub(crate) fn unescape_lossy(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        let rest = chars.clone();
+        match unescape_char(&mut chars) {
+            Ok(unescaped) => out.push(unescaped),
+            Err(_) => {
+                // the characters after the backslash are not part of the escape
+                out.push('\\');
+                chars = rest;
+            }
+        }
+    }
+    out
+}
+
+// resolve the escape following a backslash
+fn unescape_char(chars: &mut core::str::Chars) -> Result<char, EscapeErrorKind> {
+    let unescaped = match chars.next().ok_or(EscapeErrorKind::Truncated)? {
+        '\\' => '\\',
+        '"' => '"',
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        '0' => '\0',
+        'x' => {
+            let mut digit = || {
+                let c = chars.next().ok_or(EscapeErrorKind::Truncated)?;
+                c.to_digit(16).ok_or(EscapeErrorKind::InvalidDigit)
+            };
+            let hi = digit()?;
+            let lo = digit()?;
+            let v = hi << 4 | lo;
+            if v > 0x7F {
+                return Err(EscapeErrorKind::NotAscii);
+            }
+            char::from_u32(v).ok_or(EscapeErrorKind::NotAscii)?
+        }
+        'u' => {
+            if chars.next() != Some('{') {
+                return Err(EscapeErrorKind::MissingOpeningBrace);
+            }
+            let mut v = 0u32;
+            let mut digits = 0;
+            loop {
+                match chars.next() {
+                    None => return Err(EscapeErrorKind::MissingClosingBrace),
+                    Some('}') => break,
+                    Some(c) => {
+                        v = v << 4 | c.to_digit(16).ok_or(EscapeErrorKind::InvalidDigit)?;
+                        digits += 1;
+                        if digits > 6 {
+                            return Err(EscapeErrorKind::TooManyDigits);
+                        }
+                    }
+                }
+            }
+            if digits == 0 {
+                return Err(EscapeErrorKind::EmptyUnicode);
+            }
+            char::from_u32(v).ok_or(EscapeErrorKind::InvalidScalar)?
+        }
+        _ => return Err(EscapeErrorKind::Unknown),
+    };
+    Ok(unescaped)
+}
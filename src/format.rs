@@ -0,0 +1,290 @@
+//! Source-preserving formatter
+//!
+//! The formatter re-indents and normalizes the spacing of a source, keeping all the
+//! comments and the blank lines between top level elements.
+//!
+//! Comments are attached to the element they follow: a comment starting on the line where the
+//! previous element ends stays at the end of this line, any other comment is printed on its own line.
+
+use super::parser::{Element, Parser, ParserError, SpannedElement};
+use super::pretty::{trailing_comment_column, trailing_comment_padding};
+use super::printer::{CommentStyle, LineEnding, PrintConfig, Printer};
+use super::tokenizer::TokenizerConfig;
+
+/// Config for the formatter
+#[derive(Debug, Clone)]
+pub struct FormatConfig {
+    /// Config of the printer, which is always used in pretty mode
+    print: PrintConfig,
+    /// Maximum number of consecutive blank lines kept between top level elements. Default is set to 1
+    max_blank_lines: usize,
+    /// Line ending of the output, or None to keep the dominant line ending of the input. Default is set to None
+    line_ending: Option<LineEnding>,
+}
+
+impl Default for FormatConfig {
+    fn default() -> Self {
+        FormatConfig {
+            print: PrintConfig::default().pretty(true),
+            max_blank_lines: 1,
+            line_ending: None,
+        }
+    }
+}
+
+impl FormatConfig {
+    /// Set the config of the printer, e.g. to change the indentation or maximum width
+    pub fn printer(mut self, cfg: PrintConfig) -> Self {
+        self.print = cfg.pretty(true);
+        self
+    }
+
+    /// Set the maximum number of consecutive blank lines kept between top level elements
+    pub fn max_blank_lines(mut self, lines: usize) -> Self {
+        self.max_blank_lines = lines;
+        self
+    }
+
+    /// Set the line ending of the output, instead of keeping the dominant line ending of the input
+    pub fn line_ending(mut self, ending: LineEnding) -> Self {
+        self.line_ending = Some(ending);
+        self
+    }
+}
+
+// line ending used by most of the lines of the input, LF if there's no line
+fn detect_line_ending(input: &str) -> LineEnding {
+    let crlf = input.matches("\r\n").count();
+    let lf = input.matches('\n').count() - crlf;
+    if crlf > lf {
+        LineEnding::CrLf
+    } else {
+        LineEnding::Lf
+    }
+}
+
+/// Format a source, re-indenting it and normalizing the spacing between the elements
+///
+/// All the comments are preserved, and the runs of blank lines between top level elements
+/// are collapsed to the configured maximum. Formatting is idempotent, and the formatted output
+/// parse to the same elements as the input.
+///
+/// The output ends with exactly one newline, and uses the line ending of the config, or the
+/// dominant line ending of the input by default. The input is read with the CRLF line endings
+/// accepted, see [`TokenizerConfig::crlf`].
+pub fn format_str(input: &str, cfg: &FormatConfig) -> Result<String, ParserError> {
+    let line_ending = cfg.line_ending.unwrap_or_else(|| detect_line_ending(input));
+    let print = cfg.print.clone().line_ending(line_ending);
+    let newline = line_ending.as_str();
+    let mut parser = Parser::new_with_config(input, TokenizerConfig::default().crlf(true));
+    let mut items: Vec<TopLevel> = Vec::new();
+    // line where the previous top level element ends
+    let mut prev_line = None;
+    while let Some(el) = parser.next()? {
+        let mut blank_lines = 0;
+        match (prev_line, &el.inner, items.last_mut()) {
+            (Some(line), Element::Comment(comment), Some(prev)) if el.span.start.line == line => {
+                prev.comment = Some(comment);
+                continue;
+            }
+            (Some(line), _, _) => {
+                blank_lines = el.span.start.line.saturating_sub(line + 1);
+                blank_lines = blank_lines.min(cfg.max_blank_lines);
+            }
+            (None, _, _) => {}
+        }
+        let mut p = Printer::new_with_config(print.clone());
+        element(&mut p, &el);
+        let text = p.to_string().trim_end_matches(['\r', '\n']).to_string();
+        items.push(TopLevel {
+            blank_lines,
+            text,
+            comment: None,
+        });
+        prev_line = Some(el.span.end.line);
+    }
+
+    let mut out = String::new();
+    let mut i = 0;
+    while i < items.len() {
+        // run of consecutive elements with a trailing comment, aligned together
+        let mut run_end = i + 1;
+        if print.align_trailing_comments && items[i].comment.is_some() {
+            while run_end < items.len()
+                && items[run_end].comment.is_some()
+                && items[run_end].blank_lines == 0
+            {
+                run_end += 1;
+            }
+        }
+        let run = &items[i..run_end];
+        let column = trailing_comment_column(&print, run.iter().map(TopLevel::end_column));
+        for item in run {
+            for _ in 0..item.blank_lines {
+                out.push_str(newline);
+            }
+            out.push_str(&item.text);
+            if let Some(comment) = item.comment {
+                let padding = if print.align_trailing_comments {
+                    trailing_comment_padding(column, item.end_column())
+                } else {
+                    1
+                };
+                out.extend(std::iter::repeat_n(' ', padding));
+                out.push_str(comment);
+            }
+            out.push_str(newline);
+        }
+        i = run_end;
+    }
+    Ok(out)
+}
+
+// a formatted top level element
+struct TopLevel<'a> {
+    // number of blank lines before the element
+    blank_lines: usize,
+    text: String,
+    // comment following the element on its last line
+    comment: Option<&'a str>,
+}
+
+impl<'a> TopLevel<'a> {
+    fn end_column(&self) -> usize {
+        let last_line = self.text.rsplit('\n').next().unwrap_or("");
+        last_line.chars().count()
+    }
+}
+
+fn element(p: &mut Printer, el: &SpannedElement) {
+    match &el.inner {
+        Element::Atom(atom) => p.atom(atom),
+        Element::Comment(comment) => p.comment(comment),
+        Element::Group(grp, elements) => {
+            p.open(*grp);
+            // line where the previous element ends, starting with the opening delimiter
+            let mut prev_line = el.span.start.line;
+            for e in elements {
+                match &e.inner {
+                    Element::Comment(comment) => {
+                        let style = if e.span.start.line == prev_line {
+                            CommentStyle::EndOfLine
+                        } else {
+                            CommentStyle::Standalone
+                        };
+                        p.comment_with_style(comment, style)
+                    }
+                    _ => element(p, e),
+                }
+                prev_line = e.span.end.line;
+            }
+            p.close(*grp);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::PROG1;
+    #[cfg(feature = "unicode")]
+    use crate::tests::PROG2;
+
+    fn parse_all(data: &str) -> Vec<SpannedElement<'_>> {
+        let mut parser = Parser::new_with_config(data, TokenizerConfig::default().crlf(true));
+        let mut elements = Vec::new();
+        while let Some(el) = parser.next().expect("parse") {
+            elements.push(el)
+        }
+        elements
+    }
+
+    fn check_format(data: &str, cfg: &FormatConfig) -> String {
+        let formatted = format_str(data, cfg).expect("valid source");
+        let again = format_str(&formatted, cfg).expect("valid formatted source");
+        assert_eq!(formatted, again, "formatting is not idempotent");
+        let e1 = parse_all(data);
+        let e2 = parse_all(&formatted);
+        assert_eq!(e1.len(), e2.len());
+        for (e1, e2) in e1.iter().zip(e2.iter()) {
+            assert!(e1.structural_eq(&e2.inner));
+        }
+        formatted
+    }
+
+    #[test]
+    #[cfg(feature = "unicode")]
+    fn format_progs() {
+        for cfg in [
+            FormatConfig::default(),
+            FormatConfig::default().printer(PrintConfig::default().max_width(20)),
+            FormatConfig::default().max_blank_lines(0),
+        ] {
+            check_format(PROG1, &cfg);
+            check_format(PROG2, &cfg);
+        }
+    }
+
+    #[test]
+    fn format_prog1() {
+        let cfg = FormatConfig::default().printer(PrintConfig::default().max_width(30));
+        let formatted = check_format(PROG1, &cfg);
+        assert_eq!(
+            formatted,
+            r#"(define x 1) ; this is a post comment
+; this is a comment
+(define y 0x01_ab)
+(if
+  (zero? x)
+  (strip " " "abc")
+  [1 2 "def\"x"])
+"#
+        );
+    }
+
+    #[test]
+    fn format_blank_lines_and_comments() {
+        let data = "(a   b)\n\n\n\n(c ; about c\n  d\n     ; own line\n e)\n; last\n";
+        let formatted = check_format(data, &FormatConfig::default());
+        assert_eq!(
+            formatted,
+            "(a b)\n\n(c ; about c\n  d\n  ; own line\n  e)\n; last\n"
+        );
+        let formatted = check_format(data, &FormatConfig::default().max_blank_lines(2));
+        assert!(formatted.starts_with("(a b)\n\n\n(c"));
+    }
+
+    #[test]
+    fn format_aligned_comments() {
+        let cfg =
+            FormatConfig::default().printer(PrintConfig::default().align_trailing_comments(true));
+        let data = "(port 80) ; http\n(tls-port 443) ; https\n\n(a) ; a\n(config (x 1) ; x\n (long-name 2) ; long\n y ; y\n z)\n";
+        let formatted = check_format(data, &cfg);
+        assert_eq!(
+            formatted,
+            "(port 80)      ; http\n(tls-port 443) ; https\n\n(a) ; a\n(config\n  (x 1)         ; x\n  (long-name 2) ; long\n  y             ; y\n  z)\n"
+        );
+        let cfg = FormatConfig::default().printer(
+            PrintConfig::default()
+                .align_trailing_comments(true)
+                .trailing_comment_max_column(12),
+        );
+        let formatted = check_format(data, &cfg);
+        assert!(formatted.starts_with("(port 80)   ; http\n(tls-port 443) ; https\n"));
+    }
+
+    #[test]
+    fn format_line_endings() {
+        let data = "(a  b) ; c\r\n\r\n\r\n(d\r\n e)\r\n;f\n";
+        let formatted = check_format(data, &FormatConfig::default());
+        assert_eq!(formatted, "(a b) ; c\r\n\r\n(d e)\r\n;f\r\n");
+        let cfg = FormatConfig::default().line_ending(LineEnding::Lf);
+        let formatted = check_format(data, &cfg);
+        assert_eq!(formatted, "(a b) ; c\n\n(d e)\n;f\n");
+        let cfg = FormatConfig::default()
+            .printer(PrintConfig::default().max_width(4))
+            .line_ending(LineEnding::CrLf);
+        let formatted = check_format("(a b) (c\n ; d\n e)", &cfg);
+        assert_eq!(formatted, "(a\r\n  b)\r\n(c\r\n  ; d\r\n  e)\r\n");
+    }
+}
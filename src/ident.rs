@@ -0,0 +1,54 @@
+//! Rules of the ident atoms, shared by the tokenizer, the printer and the atom constructors
+
+#[cfg(feature = "unicode")]
+use unicode_xid::UnicodeXID;
+
+// check if the text is tokenized back as this exact ident, with the default tokenizer config
+pub(crate) fn is_ident(s: &str) -> bool {
+    is_ident_with(s, true)
+}
+
+// check if the text is tokenized back as this exact ident, `#` starting a bytes literal instead
+// of an ident if the bytes are supported
+pub(crate) fn is_ident_with(s: &str, support_bytes: bool) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some('#') if support_bytes => false,
+        Some(ch) => is_id_start(ch) && chars.all(is_id_continue),
+        None => false,
+    }
+}
+
+pub(crate) fn is_id_start(ch: char) -> bool {
+    #[cfg(feature = "unicode")]
+    {
+        ch.is_xid_start()
+            || ch == '_'
+            || is_ascii_operator(ch)
+            || crate::utf8::extended_math_operator(ch)
+    }
+    #[cfg(not(feature = "unicode"))]
+    {
+        ch.is_ascii_alphabetic() || ch == '_' || is_ascii_operator(ch)
+    }
+}
+
+pub(crate) fn is_id_continue(ch: char) -> bool {
+    #[cfg(feature = "unicode")]
+    {
+        ch.is_xid_continue()
+            || ch == '_'
+            || ch.is_ascii_digit()
+            || is_ascii_operator(ch)
+            || crate::utf8::extended_math_operator(ch)
+    }
+    #[cfg(not(feature = "unicode"))]
+    {
+        ch.is_ascii_alphabetic() || ch == '_' || ch.is_ascii_digit() || is_ascii_operator(ch)
+    }
+}
+
+fn is_ascii_operator(ch: char) -> bool {
+    // any ascii operator except: [] {} () " ; \\
+    "?!#@$+-*/=<>,.:|%^&~'`".contains(ch)
+}
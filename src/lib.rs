@@ -8,29 +8,65 @@
 //! let mut parser = Parser::new("(let x 1)");
 //! let r = parser.next().expect("parse data").expect("not end of stream");
 //!
-//! let elements = r.inner.paren().expect("paren group");
-//! assert_eq!(elements[0].inner.atom().and_then(|atom| atom.ident()), Some("let"));
+//! let elements = r.paren().expect("paren group");
+//! assert_eq!(elements[0].atom().and_then(|atom| atom.ident()), Some("let"));
 //! assert_eq!(elements[0].span, Span::on_line(1, 1, 4));
 //! ```
 
+#[cfg(feature = "arena")]
+mod arena;
+mod canonical;
+pub mod csexp;
 mod data;
+#[cfg(feature = "miette")]
+mod diagnostic;
+mod edit;
+mod escape;
+mod format;
+mod ident;
 mod loc;
 mod parser;
+mod pretty;
 mod printer;
+mod render;
+mod roundtrip;
+#[cfg(feature = "proptest")]
+pub mod strategies;
 mod tokenizer;
 mod utf8;
 
-pub use data::{ABytes, ADecimal, ANum, Atom, GroupKind};
-pub use loc::{Position, Span};
-pub use parser::{Element, Parser, ParserError, SpannedElement};
-pub use printer::Printer;
+#[cfg(feature = "arena")]
+pub use arena::{Arena, ArenaElement, SpannedArenaElement};
+pub use canonical::{to_canonical_string, to_canonical_string_with, CanonicalConfig};
+pub use data::{
+    ABytes, ABytesBuf, ADecimal, ANum, ANumBase, AStr, Atom, AtomBuf, AtomKind, BytesDecodeError,
+    DecimalError, FromAtomError, GroupKind, InvalidIdent, UnknownGroupKind,
+};
+pub use edit::{reprint_with_edits, EditError};
+pub use escape::{EscapeError, EscapeErrorKind};
+pub use format::{format_str, FormatConfig};
+pub use loc::{LineIndex, Position, PositionEncoding, Span, Spanned};
+pub use parser::{
+    parse_collecting_errors, Element, ElementKind, ElementOwned, Parser, ParserError,
+    SpannedElement, SpannedElementOwned,
+};
+pub use pretty::{IndentRule, IndentRules};
+#[cfg(feature = "color")]
+pub use printer::ColorScheme;
+pub use printer::{
+    to_string, CommentStyle, ControlEscape, FmtWriter, IdentCheck, IndentStyle, IoWriter,
+    LineEnding, PrintConfig, PrintNumError, Printer, PrinterError, PrinterUnbalanced, Sink,
+};
+pub use render::render_span;
+pub use roundtrip::{assert_roundtrip, check_roundtrip, RoundtripMismatch};
 pub use tokenizer::{SpannedToken, Token, TokenError, Tokenizer, TokenizerConfig};
+pub use utf8::NextCharError;
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    const PROG1: &str = r#"
+    pub(crate) const PROG1: &str = r#"
 (define x 1) ; this is a post comment
 ; this is a comment
 (define y 0x01_ab)
@@ -40,7 +76,7 @@ mod tests {
 )
 "#;
 
-    const PROG2: &str = r#"
+    pub(crate) const PROG2: &str = r#"
     (define hello world 123)
     
     ; comment space
@@ -52,6 +88,30 @@ mod tests {
     (pöjk unicode) ; unicode support
 "#;
 
+    // xorshift generator, to produce deterministic random inputs
+    pub(crate) struct Rng(pub(crate) u64);
+
+    impl Rng {
+        pub(crate) fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        pub(crate) fn string(&mut self, alphabet: &[char], max_len: usize) -> String {
+            let len = self.next() as usize % (max_len + 1);
+            (0..len)
+                .map(|_| alphabet[self.next() as usize % alphabet.len()])
+                .collect()
+        }
+    }
+
+    // the line, the column and the offset of the positions of the span
+    fn offsets(span: Span) -> [(usize, usize, usize); 2] {
+        [span.start, span.end].map(|pos| (pos.line, pos.col, pos.offset))
+    }
+
     fn collect_tokens<'a>(
         mut tokenizer: Tokenizer<'a>,
     ) -> Result<Vec<SpannedToken<'a>>, TokenError> {
@@ -63,7 +123,29 @@ mod tests {
                 Err(e) => return Err(e),
             }
         }
-        return Ok(toks);
+        Ok(toks)
+    }
+
+    // the atoms of a text containing only atoms
+    fn atoms(text: &str) -> Vec<Atom<'_>> {
+        collect_tokens(Tokenizer::new(text))
+            .expect("tokens")
+            .into_iter()
+            .map(|t| match t.inner {
+                Token::Atom(atom) => atom,
+                t => panic!("unexpected {:?}", t),
+            })
+            .collect()
+    }
+
+    // the content of a string literal with escapes, decoded from its raw data
+    fn decode(raw: &str) -> Result<String, EscapeError> {
+        AStr {
+            has_escape: true,
+            raw_data: raw.into(),
+        }
+        .try_decoded()
+        .map(|s| s.into_owned())
     }
 
     #[test]
@@ -73,11 +155,74 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "unicode")]
     fn prog2_tokenize() {
         let toks2 = collect_tokens(Tokenizer::new(PROG2));
         assert!(toks2.is_ok())
     }
 
+    fn collect_elements(data: &str) -> Vec<SpannedElement<'_>> {
+        let mut parser = Parser::new(data);
+        let mut elements = Vec::new();
+        while let Some(el) = parser.next().expect("parser error") {
+            elements.push(el)
+        }
+        elements
+    }
+
+    fn roundtrip(data: &str) {
+        let elements = collect_elements(data);
+        let mut printer = Printer::default();
+        for el in elements.iter() {
+            printer.element(&el.inner)
+        }
+        let printed = printer.to_string();
+        let reparsed = collect_elements(&printed);
+        assert_eq!(elements.len(), reparsed.len());
+        for (e1, e2) in elements.iter().zip(reparsed.iter()) {
+            assert!(
+                e1.structural_eq(&e2.inner),
+                "element {:?} printed differently: {:?}",
+                e1.inner,
+                e2.inner
+            );
+        }
+    }
+
+    #[test]
+    fn prog1_roundtrip() {
+        roundtrip(PROG1)
+    }
+
+    #[test]
+    #[cfg(feature = "unicode")]
+    fn prog2_roundtrip() {
+        roundtrip(PROG2)
+    }
+
+    #[test]
+    fn number_separators() {
+        let mut parser = Parser::new("1_000 0_1 0x_ff");
+        let mut numbers = Vec::new();
+        while let Some(el) = parser.next().expect("parser error") {
+            let num = el.atom().and_then(|a| a.number()).expect("number");
+            numbers.push(num.to_u64().expect("u64"));
+        }
+        assert_eq!(numbers, vec![1000, 1, 0xff]);
+
+        // a separator right after the first digit is in the number, like the other separators,
+        // instead of starting an ident: `1_000` is one token, not `1` and `_000`
+        for text in ["1_000", "0_1", "1__2"] {
+            let toks = collect_tokens(Tokenizer::new(text)).expect("tokens");
+            assert_eq!(toks.len(), 1, "{:?}", text);
+            assert!(
+                matches!(&toks[0].inner, Token::Atom(Atom::Integral(n)) if n.dat == text),
+                "{:?}",
+                text
+            );
+        }
+    }
+
     #[test]
     fn prog1_parser() {
         let mut parser = Parser::new_with_config(PROG1, TokenizerConfig::default().comment(false));
@@ -86,9 +231,9 @@ mod tests {
                 .next()
                 .expect("parser error")
                 .expect("not end of stream");
-            let e0 = first_element.inner.paren().expect("first group is paren");
-            assert_eq!(e0[0].inner.atom().and_then(|a| a.ident()), Some("define"));
-            assert_eq!(e0[1].inner.atom().and_then(|a| a.ident()), Some("x"));
+            let e0 = first_element.paren().expect("first group is paren");
+            assert_eq!(e0[0].atom().and_then(|a| a.ident()), Some("define"));
+            assert_eq!(e0[1].atom().and_then(|a| a.ident()), Some("x"));
             assert_eq!(
                 e0[2]
                     .inner
@@ -103,9 +248,9 @@ mod tests {
                 .next()
                 .expect("parser error")
                 .expect("not end of stream");
-            let e0 = second_element.inner.paren().expect("second group is paren");
-            assert_eq!(e0[0].inner.atom().and_then(|a| a.ident()), Some("define"));
-            assert_eq!(e0[1].inner.atom().and_then(|a| a.ident()), Some("y"));
+            let e0 = second_element.paren().expect("second group is paren");
+            assert_eq!(e0[0].atom().and_then(|a| a.ident()), Some("define"));
+            assert_eq!(e0[1].atom().and_then(|a| a.ident()), Some("y"));
             assert_eq!(
                 e0[2]
                     .inner
@@ -121,22 +266,13 @@ mod tests {
                 .next()
                 .expect("parser error")
                 .expect("not end of stream");
-            let e0 = third_element.inner.paren().expect("third group is paren");
-            assert_eq!(e0[0].inner.atom().and_then(|a| a.ident()), Some("if"));
-            let conditional = e0[1].inner.paren().expect("conditional");
-            assert_eq!(
-                conditional[0].inner.atom().and_then(|a| a.ident()),
-                Some("zero?")
-            );
-            assert_eq!(
-                conditional[1].inner.atom().and_then(|a| a.ident()),
-                Some("x")
-            );
-            let then_expr = e0[2].inner.paren().expect("then");
-            assert_eq!(
-                then_expr[0].inner.atom().and_then(|a| a.ident()),
-                Some("strip")
-            );
+            let e0 = third_element.paren().expect("third group is paren");
+            assert_eq!(e0[0].atom().and_then(|a| a.ident()), Some("if"));
+            let conditional = e0[1].paren().expect("conditional");
+            assert_eq!(conditional[0].atom().and_then(|a| a.ident()), Some("zero?"));
+            assert_eq!(conditional[1].atom().and_then(|a| a.ident()), Some("x"));
+            let then_expr = e0[2].paren().expect("then");
+            assert_eq!(then_expr[0].atom().and_then(|a| a.ident()), Some("strip"));
             assert_eq!(
                 then_expr[1]
                     .inner
@@ -154,7 +290,1958 @@ mod tests {
                 Some("abc".to_string())
             );
 
-            let _else_expr = e0[3].inner.bracket().expect("else");
+            let _else_expr = e0[3].bracket().expect("else");
+        }
+    }
+
+    #[test]
+    fn atom_display() {
+        let atoms = [
+            Atom::Ident("foo-bar?"),
+            Atom::String(AStr {
+                has_escape: true,
+                raw_data: r#"a\n\"b\" \u{1f600}"#.into(),
+            }),
+            Atom::String(AStr {
+                has_escape: false,
+                raw_data: "".into(),
+            }),
+            Atom::Bytes(ABytes("dead_BEEF")),
+            Atom::Bytes(ABytes("")),
+            Atom::Integral(ANum {
+                base: ANumBase::Hexadecimal,
+                dat: "1_f",
+            }),
+            Atom::Integral(ANum {
+                base: ANumBase::Binary,
+                dat: "0101",
+            }),
+            Atom::Integral(ANum {
+                base: ANumBase::Decimal,
+                dat: "100_000",
+            }),
+            Atom::Decimal(ADecimal {
+                raw_integral: "1_000",
+                raw_fractional: "05",
+            }),
+        ];
+        let expected = [
+            "foo-bar?",
+            r#""a\n\"b\" \u{1f600}""#,
+            r#""""#,
+            "#dead_BEEF#",
+            "##",
+            "0x1_f",
+            "0b0101",
+            "100_000",
+            "1_000.05",
+        ];
+        for (atom, expected) in atoms.iter().zip(expected) {
+            let text = atom.to_string();
+            assert_eq!(text, expected);
+            let toks = collect_tokens(Tokenizer::new(&text)).expect("tokens");
+            assert_eq!(toks.len(), 1, "{}", text);
+            match &toks[0].inner {
+                Token::Atom(a) => assert_eq!(a, atom, "{}", text),
+                t => panic!("unexpected {:?}", t),
+            }
+        }
+        assert_eq!(
+            format!("unexpected atom {}", atoms[5]),
+            "unexpected atom 0x1_f"
+        );
+    }
+
+    #[test]
+    fn anum_display() {
+        let num = |base, dat| ANum { base, dat };
+        let hex = num(ANumBase::Hexadecimal, "1_ab");
+        assert_eq!(
+            format!("value {} out of range", hex),
+            "value 0x1_ab out of range"
+        );
+        assert_eq!(format!("{:#}", hex), "0x1ab");
+        assert_eq!(format!("{:#}", num(ANumBase::Binary, "1__0_")), "0b10");
+        assert_eq!(format!("{:#}", num(ANumBase::Decimal, "1_000")), "1000");
+        assert_eq!(format!("{:#}", Atom::Integral(hex)), "0x1ab");
+        assert_eq!(ANumBase::Decimal.prefix(), "");
+    }
+
+    #[test]
+    fn atom_eq() {
+        let equals = [
+            "0x10 16 0b1_0000 016 0x0_010",
+            "0 00 0x0 0b0",
+            "1.5 01.50 0_1.500",
+            "0.0 00.00",
+            r#""a\nA" "a\x0a\x41" "a\u{a}\u{41}""#,
+            "#DE_ad# #dead# #d_e_a_d#",
+            "## #_#",
+            "foo foo",
+        ];
+        for text in equals {
+            let atoms = atoms(text);
+            for a in atoms.iter() {
+                for b in atoms.iter() {
+                    assert_eq!(a, b, "{}", text);
+                }
+            }
+        }
+        let differents = [
+            "0x10 17",
+            "1 1.0",
+            "1.5 1.05",
+            "10.0 1.0",
+            r#""a\n" "a\\n""#,
+            "#dead# #deadbeef#",
+            "foo Foo",
+            r#"foo "foo""#,
+            "#10# 0x10",
+        ];
+        for text in differents {
+            let atoms = atoms(text);
+            assert_ne!(atoms[0], atoms[1], "{}", text);
+        }
+        let a = atoms("0x10 16 0x10");
+        assert!(!a[0].raw_eq(&a[1]));
+        assert!(a[0].raw_eq(&a[2]));
+        let s = atoms(r#""a\n""#);
+        let decoded = AStr {
+            has_escape: false,
+            raw_data: "a\n".into(),
+        };
+        assert_eq!(s[0], Atom::String(decoded.clone()));
+        assert!(!s[0].raw_eq(&Atom::String(decoded)));
+    }
+
+    #[test]
+    fn atom_hash() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::collections::{HashMap, HashSet};
+        use std::hash::{Hash, Hasher};
+
+        let hash = |atom: &Atom| {
+            let mut hasher = DefaultHasher::new();
+            atom.hash(&mut hasher);
+            hasher.finish()
+        };
+        let text = r#"0x10 16 0b1_0000 1.50 01.5 "a\x41" "aA" #DE_AD# #dead# foo 17 bar"#;
+        let atoms = atoms(text);
+        for a in atoms.iter() {
+            for b in atoms.iter() {
+                if a == b {
+                    assert_eq!(hash(a), hash(b), "{} {}", a, b);
+                }
+            }
+        }
+        let set = atoms.iter().cloned().collect::<HashSet<_>>();
+        assert_eq!(set.len(), 7);
+        let mut counts = HashMap::new();
+        for atom in atoms {
+            *counts.entry(atom).or_insert(0) += 1;
+        }
+        let sixteen = Atom::Integral(ANum {
+            base: ANumBase::Decimal,
+            dat: "0016",
+        });
+        assert_eq!(counts.get(&sixteen), Some(&3));
+    }
+
+    #[test]
+    fn atom_ord() {
+        let text =
+            r#"#ff# "b" "a\x42" "a" 1.5 0.75 01.50 10.0 0x10 0b11 2 1_000 "aa" #0F# #0f_00# b a z"#;
+        let mut atoms = atoms(text);
+        atoms.sort();
+        let sorted = atoms.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            sorted,
+            [
+                "a",
+                "b",
+                "z",
+                "2",
+                "0b11",
+                "0x10",
+                "1_000",
+                "0.75",
+                "1.5",
+                "01.50",
+                "10.0",
+                r#""a""#,
+                r#""a\x42""#,
+                r#""aa""#,
+                r#""b""#,
+                "#0F#",
+                "#0f_00#",
+                "#ff#"
+            ]
+        );
+        for w in atoms.windows(2) {
+            assert_eq!(w[0] == w[1], w[0].cmp(&w[1]).is_eq());
+            assert!(w[0] <= w[1]);
+        }
+
+        let parse = |text| {
+            let mut parser = Parser::new(text);
+            parser.next().expect("element").expect("element").inner
+        };
+        let elements = ["1", "a", "(1)", "(1 2)", "(2)", "[0]", "(0x1 2)"].map(parse);
+        let ord = |i: usize, j: usize| elements[i].structural_cmp(&elements[j]);
+        assert!(ord(1, 0).is_lt());
+        assert!(ord(0, 2).is_lt());
+        assert!(ord(2, 3).is_lt());
+        assert!(ord(3, 4).is_lt());
+        assert!(ord(4, 5).is_lt());
+        assert!(ord(3, 6).is_eq());
+        assert!(elements[3].structural_eq(&elements[6]));
+    }
+
+    #[test]
+    fn atom_buf() {
+        let text = r#"foo 0x1_f 0b10 1_0 1.50 #dead# "a\nb" """#;
+        let bufs = atoms(text)
+            .iter()
+            .map(|atom| {
+                let buf = AtomBuf::from(atom);
+                assert!(buf.as_atom().raw_eq(atom));
+                buf
+            })
+            .collect::<Vec<_>>();
+        let printed = bufs.iter().map(|b| b.to_string()).collect::<Vec<_>>();
+        assert_eq!(printed.join(" "), text);
+
+        assert_eq!(bufs[1], AtomBuf::from(31u64));
+        assert_eq!(bufs[3], AtomBuf::from(10usize));
+        assert_eq!(AtomBuf::from(u128::MAX).to_string(), u128::MAX.to_string());
+        assert!(bufs[2] < bufs[1]);
+        let set = bufs
+            .iter()
+            .cloned()
+            .chain([AtomBuf::from(2u8)])
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!(set.len(), bufs.len());
+
+        let mut p = Printer::default();
+        p.open(GroupKind::Paren);
+        for b in bufs.iter() {
+            p.atom(&b.as_atom());
+        }
+        p.close(GroupKind::Paren);
+        assert_eq!(p.to_string(), format!("({})", text));
+    }
+
+    #[test]
+    fn atom_buf_constructors() {
+        // the constructed atoms parse back to the same atoms
+        let check = |buf: &AtomBuf| {
+            let text = buf.to_string();
+            match &atoms(&text)[..] {
+                [atom] => {
+                    assert_eq!(atom, &buf.as_atom(), "{}", text);
+                    assert!(atom.raw_eq(&buf.as_atom()), "{}", text);
+                }
+                atoms => panic!("not one atom {:?}", atoms),
+            }
+        };
+        let bases = [ANumBase::Binary, ANumBase::Decimal, ANumBase::Hexadecimal];
+        let alphabet: Vec<char> = "ab1_-+ #;\"()[]{}\\|.\n\t\r\0\x1bé😀".chars().collect();
+        let mut rng = Rng(177);
+        for _ in 0..500 {
+            let v = (rng.next() as u128) << (rng.next() % 64) | rng.next() as u128;
+            let num = AtomBuf::integral_u128(v, bases[rng.next() as usize % 3]);
+            check(&num);
+            assert_eq!(
+                num.as_atom().number().and_then(|n| n.to_u128().ok()),
+                Some(v)
+            );
+
+            let text = rng.string(&alphabet, 8);
+            let string = AtomBuf::string(&text);
+            check(&string);
+            assert_eq!(
+                string.as_atom().string().map(|s| s.to_string()),
+                Some(text.clone())
+            );
+            match AtomBuf::ident(&text) {
+                Ok(ident) => check(&ident),
+                Err(e) => assert_eq!(e, InvalidIdent(text.clone())),
+            }
+
+            let bytes = text.as_bytes();
+            let buf = AtomBuf::bytes(bytes);
+            check(&buf);
+            assert_eq!(
+                buf.as_atom().bytes().map(|b| b.to_vec()),
+                Some(Ok(bytes.to_vec()))
+            );
+        }
+        assert_eq!(
+            AtomBuf::integral_u128(0, ANumBase::Binary).to_string(),
+            "0b0"
+        );
+        assert_eq!(AtomBuf::bytes(&[]).to_string(), "##");
+        assert_eq!(AtomBuf::string(""), AtomBuf::string(""));
+        for ident in ["", " ", "a b", "(", "\"", "1", "#a", ";"] {
+            assert!(AtomBuf::ident(ident).is_err(), "{:?}", ident);
+        }
+    }
+
+    #[test]
+    fn atom_kind() {
+        let text = r#"1 1.5 #00# "a" a"#;
+        let atoms = atoms(text);
+        let kinds = atoms.iter().map(|a| a.kind()).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                AtomKind::Integral,
+                AtomKind::Decimal,
+                AtomKind::Bytes,
+                AtomKind::String,
+                AtomKind::Ident
+            ]
+        );
+        let names = kinds.iter().map(|k| k.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "integral number",
+                "decimal number",
+                "bytes",
+                "string",
+                "ident"
+            ]
+        );
+        let predicates = atoms
+            .iter()
+            .map(|a| (a.is_number(), a.is_bytes(), a.is_string(), a.is_ident()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            predicates,
+            [
+                (true, false, false, false),
+                (true, false, false, false),
+                (false, true, false, false),
+                (false, false, true, false),
+                (false, false, false, true)
+            ]
+        );
+    }
+
+    #[test]
+    fn group_kind_chars() {
+        for grp in GroupKind::ALL {
+            let (open, close) = grp.delimiters();
+            assert_eq!((grp.open_char(), grp.close_char()), (open, close));
+            assert_eq!(GroupKind::from_open_char(open), Some(grp));
+            assert_eq!(GroupKind::from_close_char(close), Some(grp));
+            assert_eq!(GroupKind::from_open_char(close), None);
+            assert_eq!(GroupKind::from_close_char(open), None);
+
+            let text = format!("{}{}", open, close);
+            let toks = collect_tokens(Tokenizer::new(&text)).expect("tokens");
+            assert!(matches!(toks[0].inner, Token::Left(g) if g == grp));
+            assert!(matches!(toks[1].inner, Token::Right(g) if g == grp));
+            let mut p = Printer::default();
+            p.open(grp);
+            p.close(grp);
+            assert_eq!(p.to_string(), text);
+        }
+        assert_eq!(GroupKind::from_open_char('a'), None);
+        assert_eq!(GroupKind::from_close_char('"'), None);
+    }
+
+    #[test]
+    fn group_kind_names() {
+        let names = GroupKind::ALL.map(|grp| grp.to_string());
+        assert_eq!(names, ["parentheses `()`", "braces `{}`", "brackets `[]`"]);
+        for (grp, name) in GroupKind::ALL.iter().zip(["paren", "brace", "bracket"]) {
+            assert_eq!(name.parse::<GroupKind>().as_ref(), Ok(grp));
+        }
+        assert_eq!(
+            "Paren".parse::<GroupKind>(),
+            Err(UnknownGroupKind("Paren".to_string()))
+        );
+
+        let error = |text| {
+            let mut parser = Parser::new(text);
+            loop {
+                match parser.next() {
+                    Ok(Some(_)) => {}
+                    Ok(None) => panic!("no error in {:?}", text),
+                    Err(e) => return e.to_string(),
+                }
+            }
+        };
+        assert_eq!(
+            error("(a ]"),
+            "line 1, columns 0-4: closing brackets `[]` while parentheses `()` are open, expected `)`"
+        );
+        assert_eq!(
+            error("a }"),
+            "line 1, column 2: closing braces `{}` without any open group"
+        );
+        assert_eq!(error("[a (b)"), "unfinished brackets `[]`, missing `]`");
+        assert_eq!(
+            error("(a\n b\n ]"),
+            "lines 1-3: closing brackets `[]` while parentheses `()` are open, expected `)`"
+        );
+    }
+
+    #[test]
+    fn atom_bool() {
+        assert_eq!(Atom::Ident("true").as_bool(), Some(true));
+        assert_eq!(Atom::Ident("false").as_bool(), Some(false));
+        assert_eq!(Atom::Ident("True").as_bool(), None);
+        assert_eq!(Atom::Ident("yes").as_bool(), None);
+        let string = AStr {
+            has_escape: false,
+            raw_data: "true".into(),
+        };
+        assert_eq!(Atom::String(string).as_bool(), None);
+
+        let (truthy, falsy) = (&["yes", "on"][..], &["no", "off"][..]);
+        assert_eq!(Atom::Ident("yes").as_bool_with(truthy, falsy), Some(true));
+        assert_eq!(Atom::Ident("off").as_bool_with(truthy, falsy), Some(false));
+        assert_eq!(Atom::Ident("Off").as_bool_with(truthy, falsy), None);
+        assert_eq!(
+            Atom::Ident("Off").as_bool_ignore_case(truthy, falsy),
+            Some(false)
+        );
+        assert_eq!(
+            Atom::Ident("YES").as_bool_ignore_case(truthy, falsy),
+            Some(true)
+        );
+        assert_eq!(
+            Atom::Ident("maybe").as_bool_ignore_case(truthy, falsy),
+            None
+        );
+        // the truthy idents win when an ident is in both
+        assert_eq!(Atom::Ident("x").as_bool_with(&["x"], &["x"]), Some(true));
+    }
+
+    #[test]
+    fn element_owned() {
+        let owned = {
+            let source = PROG1.to_string();
+            let mut parser = Parser::new(&source);
+            let mut elements = Vec::new();
+            while let Some(el) = parser.next().expect("element") {
+                elements.push(SpannedElementOwned {
+                    span: el.span,
+                    inner: ElementOwned::from(&el.inner),
+                });
+            }
+            elements
+        };
+        let mut parser = Parser::new(PROG1);
+        for el in owned.iter() {
+            let original = parser.next().expect("element").expect("element");
+            assert_eq!(el.span, original.span);
+            assert!(el.to_element().structural_eq(&original.inner));
+            assert_eq!(to_string(&el.to_element()), to_string(&original.inner));
+        }
+        assert!(parser.next().expect("end").is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_owned() {
+        let mut parser = Parser::new(PROG2);
+        let mut elements = Vec::new();
+        while let Some(el) = parser.next().expect("element") {
+            elements.push(SpannedElementOwned {
+                span: el.span,
+                inner: ElementOwned::from(&el.inner),
+            });
+        }
+        let check = |decoded: Vec<SpannedElementOwned>| {
+            assert_eq!(decoded.len(), elements.len());
+            for (d, el) in decoded.iter().zip(elements.iter()) {
+                assert_eq!(d.span, el.span);
+                assert!(d.to_element().structural_eq(&el.to_element()));
+            }
+        };
+        let json = serde_json::to_string(&elements).expect("json");
+        check(serde_json::from_str(&json).expect("json"));
+        let binary = bincode::serialize(&elements).expect("bincode");
+        check(bincode::deserialize(&binary).expect("bincode"));
+
+        // the representation is tagged by the names of the variants
+        let atom = ElementOwned::Atom(AtomBuf::integral_u128(16, ANumBase::Hexadecimal));
+        assert_eq!(
+            serde_json::to_string(&atom).expect("json"),
+            r#"{"Atom":{"Integral":{"base":"Hexadecimal","dat":"10"}}}"#
+        );
+        let group = ElementOwned::Group(GroupKind::Bracket, vec![]);
+        assert_eq!(
+            serde_json::to_string(&group).expect("json"),
+            r#"{"Group":["Bracket",[]]}"#
+        );
+        let span = Span::new(Position::new(2, 1, 9), Position::new(2, 3, 11));
+        assert_eq!(
+            serde_json::to_string(&span).expect("json"),
+            r#"{"start":{"line":2,"col":1,"offset":9},"end":{"line":2,"col":3,"offset":11}}"#
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_spans() {
+        let source = "(café\n  [x 0x1f])";
+        let tokens = collect_tokens(Tokenizer::new(source)).expect("tokens");
+        let spanned = tokens
+            .iter()
+            .map(|t| Spanned::new(t.span, format!("{:?}", t.inner)))
+            .collect::<Vec<_>>();
+        let json = serde_json::to_string(&spanned).expect("json");
+        let decoded: Vec<Spanned<String>> = serde_json::from_str(&json).expect("json");
+        assert_eq!(decoded.len(), spanned.len());
+        for (d, s) in decoded.iter().zip(spanned.iter()) {
+            assert_eq!((d.span, &d.inner), (s.span, &s.inner));
+        }
+        for t in tokens.iter() {
+            let json = serde_json::to_string(&t.span.start).expect("json");
+            assert_eq!(
+                serde_json::from_str::<Position>(&json).ok(),
+                Some(t.span.start)
+            );
+        }
+
+        // the representation is a map of the fields, which stays stable
+        let pos = Position {
+            line: 3,
+            col: 7,
+            offset: 20,
+        };
+        assert_eq!(
+            serde_json::to_string(&pos).expect("json"),
+            r#"{"line":3,"col":7,"offset":20}"#
+        );
+        let spanned = Spanned::new(
+            Span {
+                start: pos,
+                end: pos,
+            },
+            1u8,
+        );
+        assert_eq!(
+            serde_json::to_string(&spanned).expect("json"),
+            r#"{"span":{"start":{"line":3,"col":7,"offset":20},"end":{"line":3,"col":7,"offset":20}},"inner":1}"#
+        );
+    }
+
+    #[test]
+    fn atom_keyword() {
+        let cases = [
+            (":key", Some("key")),
+            (":a:b", Some("a:b")),
+            (":a:", Some("a:")),
+            (":-", Some("-")),
+            (":", None),
+            ("::", None),
+            (":::", None),
+            ("::key", None),
+            ("key:", None),
+            ("key", None),
+        ];
+        for (text, keyword) in cases {
+            match &atoms(text)[..] {
+                [atom] => {
+                    assert_eq!(atom.as_keyword(), keyword, "{}", text);
+                    assert_eq!(atom.is_keyword(), keyword.is_some(), "{}", text);
+                }
+                _ => panic!("not one atom {:?}", text),
+            }
+        }
+        let string = AStr {
+            has_escape: false,
+            raw_data: ":key".into(),
+        };
+        assert_eq!(Atom::String(string).as_keyword(), None);
+    }
+
+    #[test]
+    fn ident_rules() {
+        // the ident rules accept exactly the texts tokenized back as the same single ident
+        let retokenized = |text: &str, cfg: TokenizerConfig| {
+            let mut tokenizer = Tokenizer::new_with_config(text, cfg);
+            matches!(tokenizer.next(), Ok(Some(t)) if matches!(t.inner, Token::Atom(Atom::Ident(ident)) if ident == text))
+                && matches!(tokenizer.next(), Ok(None))
+        };
+        let alphabet: Vec<char> = "ab1_-+ #;\"()[]{}\\|.:\n\r\té∑".chars().collect();
+        let mut rng = Rng(0x1de7);
+        for _ in 0..2000 {
+            let text = rng.string(&alphabet, 5);
+            for cfg in [
+                TokenizerConfig::default(),
+                TokenizerConfig::default().support_bytes(false),
+                TokenizerConfig::default().braces(false).bracket(false),
+            ] {
+                assert_eq!(
+                    cfg.is_ident(&text),
+                    retokenized(&text, cfg.clone()),
+                    "{:?} {:?}",
+                    text,
+                    cfg
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "unicode")]
+    fn ident_parts() {
+        let cases = [
+            ("ns/sym", Some(("ns", "sym"))),
+            ("a/b/c", Some(("a", "b/c"))),
+            ("a//b", Some(("a", "/b"))),
+            ("/sym", None),
+            ("ns/", None),
+            ("/", None),
+            ("sym", None),
+        ];
+        for (text, parts) in cases {
+            let el = Parser::new(text).next().expect("parse").expect("element");
+            assert_eq!(
+                el.atom().and_then(|a| a.ident_parts('/')),
+                parts,
+                "{}",
+                text
+            );
+            let spanned = el.ident_parts('/').map(|(p1, p2)| (p1.inner, p2.inner));
+            assert_eq!(spanned, parts, "{}", text);
+        }
+
+        let source = "(def\n   café:crème \"a:b\")";
+        let el = Parser::new(source).next().expect("parse").expect("element");
+        let children = el.paren().expect("group");
+        let (first, second) = children[1].ident_parts(':').expect("parts");
+        let cols = |span: Span| (span.start.line, span.start.col, span.end.col);
+        assert_eq!(cols(first.span), (2, 3, 7));
+        assert_eq!(cols(second.span), (2, 8, 13));
+        let text = |span: Span| {
+            let start = loc::offset(source, span.start).expect("start");
+            let end = loc::offset(source, span.end).expect("end");
+            &source[start..end]
+        };
+        assert_eq!((text(first.span), text(second.span)), ("café", "crème"));
+        let slice = |span: Span| &source[span.start.offset..span.end.offset];
+        assert_eq!((slice(first.span), slice(second.span)), ("café", "crème"));
+        assert!(children[2].ident_parts(':').is_none());
+        assert!(children[0].ident_parts(':').is_none());
+    }
+
+    #[test]
+    fn abytes_iter() {
+        // reference decoder of the digits in pairs, independent of the iterator
+        fn reference(text: &str) -> Result<Vec<u8>, BytesDecodeError> {
+            let mut out = Vec::new();
+            let mut high = None;
+            for (offset, ch) in text.char_indices() {
+                if ch == '_' || ch.is_ascii_whitespace() {
+                    continue;
+                }
+                let v = ch
+                    .to_digit(16)
+                    .ok_or(BytesDecodeError::InvalidChar { offset, ch })?
+                    as u8;
+                match high.take() {
+                    None => high = Some((offset, v)),
+                    Some((_, h)) => out.push(h << 4 | v),
+                }
+            }
+            match high {
+                None => Ok(out),
+                Some((offset, _)) => Err(BytesDecodeError::OddLength { offset }),
+            }
+        }
+
+        let alphabet: Vec<char> = "0123456789abcdefABCDEF_ g\u{e9}".chars().collect();
+        let mut rng = Rng(186);
+        for _ in 0..2000 {
+            let text = rng.string(&alphabet, 12);
+            let bytes = ABytes(&text);
+            let iterated = bytes.iter_bytes().collect::<Result<Vec<u8>, _>>();
+            assert_eq!(iterated, reference(&text), "{:?}", text);
+            assert_eq!(iterated, bytes.to_vec(), "{:?}", text);
+            // the iterator stops at the first error
+            let errors = bytes.iter_bytes().filter(|b| b.is_err()).count();
+            assert_eq!(errors, usize::from(iterated.is_err()), "{:?}", text);
+            if iterated.is_err() {
+                assert!(bytes.iter_bytes().last().is_some_and(|b| b.is_err()));
+            }
+        }
+        assert_eq!(ABytes("").iter_bytes().next(), None);
+        assert_eq!(ABytes("__").iter_bytes().next(), None);
+    }
+
+    #[test]
+    fn astr_chars() {
+        let alphabet: Vec<char> = r#"ab\\"ntrx0u{}7f9é"#.chars().collect();
+        let mut rng = Rng(187);
+        for _ in 0..2000 {
+            let raw = rng.string(&alphabet, 10);
+            let astr = AStr {
+                has_escape: raw.contains('\\'),
+                raw_data: raw.as_str().into(),
+            };
+            let decoded = astr.try_decoded();
+            let chars = astr.chars().collect::<Result<Vec<_>, _>>();
+            let string = chars
+                .clone()
+                .map(|cs| cs.iter().map(|(c, _)| c).collect::<String>());
+            assert_eq!(string.as_deref(), decoded.as_deref(), "{:?}", raw);
+            assert_eq!(
+                astr.decoded_len(),
+                decoded.clone().map(|d| d.chars().count()),
+                "{:?}",
+                raw
+            );
+            assert_eq!(
+                astr.is_empty(),
+                decoded.as_ref().is_ok_and(|d| d.is_empty())
+            );
+            // the offsets point at the character, or at the backslash of an escape
+            for (c, offset) in chars.unwrap_or_default() {
+                let at = raw[offset..].chars().next();
+                assert!(at == Some(c) || at == Some('\\'), "{:?} {}", raw, offset);
+            }
+        }
+
+        let plain = AStr {
+            has_escape: false,
+            raw_data: r"a\n".into(),
+        };
+        assert_eq!(plain.decoded_len(), Ok(3));
+        let chars = plain.chars().collect::<Result<Vec<_>, _>>();
+        assert_eq!(chars, Ok(vec![('a', 0), ('\\', 1), ('n', 2)]));
+        let invalid = AStr {
+            has_escape: true,
+            raw_data: r"ab\q".into(),
+        };
+        let mut chars = invalid.chars();
+        assert_eq!(chars.next(), Some(Ok(('a', 0))));
+        assert_eq!(chars.next(), Some(Ok(('b', 1))));
+        assert_eq!(
+            chars.next().map(|r| r.map_err(|e| e.offset())),
+            Some(Err(2))
+        );
+        assert_eq!(chars.next(), None);
+        assert!(invalid.decoded_len().is_err());
+    }
+
+    #[test]
+    fn adecimal_literal() {
+        let dec = |raw_integral, raw_fractional| ADecimal {
+            raw_integral,
+            raw_fractional,
+        };
+        let values = [
+            (dec("1_000", "5_0"), "1_000.5_0", "1000.50"),
+            (dec("012", "50"), "012.50", "012.50"),
+            (dec("0", "0"), "0.0", "0.0"),
+        ];
+        for (d, display, literal) in values {
+            assert_eq!(d.to_string(), display);
+            assert_eq!(d.to_literal_string(), literal);
+            assert_eq!(format!("{:#}", d), literal);
+            match &atoms(literal)[0] {
+                Atom::Decimal(parsed) => assert_eq!(parsed, &d),
+                a => panic!("unexpected {:?}", a),
+            }
+        }
+        // there is no negative decimal literal
+        assert_eq!(atoms("-0.0"), [Atom::Ident("-0.0")]);
+    }
+
+    #[test]
+    #[cfg(feature = "unicode")]
+    fn position_offsets() {
+        let source = "(café ; ünïcode\n  \"crème\\n\" #ff#\r\n  [日本 0x1_0 1.5])";
+        let crlf = TokenizerConfig::default().crlf(true);
+        let tokens =
+            collect_tokens(Tokenizer::new_with_config(source, crlf.clone())).expect("tokens");
+        let texts = tokens
+            .iter()
+            .map(|t| &source[t.span.start.offset..t.span.end.offset])
+            .collect::<Vec<_>>();
+        assert_eq!(
+            texts,
+            [
+                "(",
+                "café",
+                "; ünïcode",
+                "\"crème\\n\"",
+                "#ff#",
+                "[",
+                "日本",
+                "0x1_0",
+                "1.5",
+                "]",
+                ")"
+            ]
+        );
+        for data in [source, PROG1, PROG2] {
+            for t in collect_tokens(Tokenizer::new_with_config(data, crlf.clone())).expect("tokens")
+            {
+                for pos in [t.span.start, t.span.end] {
+                    assert_eq!(Some(pos.offset), loc::offset(data, pos), "{:?}", pos);
+                }
+            }
+        }
+        let el = Parser::new_with_config(source, crlf)
+            .next()
+            .expect("parse")
+            .expect("element");
+        assert_eq!(el.span.start.offset, 0);
+        assert_eq!(el.span.end.offset, source.len());
+    }
+
+    #[test]
+    fn span_contains() {
+        let tokens = collect_tokens(Tokenizer::new("a (bc)")).expect("tokens");
+        let a = tokens[0].span;
+        assert!(a.contains(a.start));
+        assert!(!a.contains(a.end));
+        assert!(!a.contains(tokens[1].span.start));
+        assert!(a.contains_span(&a));
+        assert!(!a.contains_span(&tokens[1].span));
+
+        let source = "(first\n  second\n  third)";
+        let el = Parser::new(source).next().expect("parse").expect("element");
+        let group = el.span;
+        let pos = |line, col| Position {
+            line,
+            col,
+            offset: loc::offset(
+                source,
+                Position {
+                    line,
+                    col,
+                    offset: 0,
+                },
+            )
+            .unwrap_or(0),
+        };
+        assert!(group.contains(pos(1, 0)));
+        assert!(group.contains(pos(1, 100)));
+        assert!(group.contains(pos(2, 0)));
+        assert!(group.contains(pos(3, 7)));
+        assert!(!group.contains(pos(3, 8)));
+        assert!(!group.contains(pos(4, 0)));
+        let second = el.paren().expect("paren")[1].span;
+        assert!(group.contains_span(&second));
+        assert!(!second.contains_span(&group));
+        assert!(second.contains(pos(2, 2)));
+        assert!(!second.contains(pos(1, 3)));
+        let empty = Span {
+            start: second.end,
+            end: second.end,
+        };
+        assert!(!empty.contains(second.end));
+        assert!(second.contains_span(&empty));
+    }
+
+    #[test]
+    fn span_merge_intersection() {
+        let source = "(a\n  (b c)\n  d)";
+        let el = Parser::new(source).next().expect("parse").expect("element");
+        let children = el.paren().expect("paren");
+        let (a, bc, d) = (children[0].span, children[1].span, children[2].span);
+        let b = bc.start;
+        assert_eq!(d.merge(&a), a.merge(&d));
+        assert_eq!(a.merge(&d).start, a.start);
+        assert_eq!(a.merge(&d).end, d.end);
+        assert_eq!(bc.merge(&bc), bc);
+        assert_eq!(el.span.merge(&bc), el.span);
+        assert_eq!(a.extend(&d), a.merge(&d));
+
+        assert!(el.span.intersects(&bc));
+        assert_eq!(el.span.intersection(&bc), Some(bc));
+        assert_eq!(bc.intersection(&el.span), Some(bc));
+        assert!(!a.intersects(&d));
+        assert_eq!(a.intersection(&bc), None);
+        let empty = Span { start: b, end: b };
+        assert!(!bc.intersects(&empty));
+        let first_lines = a.merge(&bc);
+        let last_lines = bc.merge(&d);
+        assert_eq!(first_lines.intersection(&last_lines), Some(bc));
+        let before = Span {
+            start: el.span.start,
+            end: bc.start,
+        };
+        assert!(!before.intersects(&bc));
+    }
+
+    #[test]
+    #[cfg(feature = "unicode")]
+    fn line_index() {
+        let multibyte = "(café ; ünïcode\n  \"crème\\n\n\" #ff#\r\n\t[日本 0x1_0])\n";
+        for data in [PROG1, PROG2, multibyte] {
+            let index = LineIndex::new(data);
+            let crlf = TokenizerConfig::default().crlf(true);
+            for t in collect_tokens(Tokenizer::new_with_config(data, crlf)).expect("tokens") {
+                for pos in [t.span.start, t.span.end] {
+                    assert_eq!(index.position_at(pos.offset), pos);
+                    assert_eq!(index.offset_at(pos), Some(pos.offset));
+                }
+            }
+            assert_eq!(index.line_count(), data.split('\n').count());
+            for (i, line) in data.split('\n').enumerate() {
+                assert_eq!(index.line_text(i + 1), Some(line));
+            }
+        }
+
+        let index = LineIndex::new(multibyte);
+        assert_eq!(index.line_text(0), None);
+        assert_eq!(index.line_text(5), Some(""));
+        assert_eq!(index.line_text(6), None);
+        // inside the `é` of `café`
+        assert_eq!(index.position_at(5), index.position_at(4));
+        let end = index.position_at(1000);
+        assert_eq!((end.line, end.col, end.offset), (5, 0, multibyte.len()));
+        let pos = |line, col| Position {
+            line,
+            col,
+            offset: 0,
+        };
+        assert_eq!(
+            index.offset_at(pos(1, 15)),
+            Some(multibyte.find('\n').unwrap())
+        );
+        assert_eq!(index.offset_at(pos(1, 16)), None);
+        assert_eq!(index.offset_at(pos(9, 0)), None);
+
+        let empty = LineIndex::new("");
+        assert_eq!(empty.line_count(), 1);
+        assert_eq!(empty.position_at(0), Position::default());
+        assert_eq!(empty.offset_at(Position::default()), Some(0));
+    }
+
+    #[test]
+    fn spanned_combinators() {
+        let tokens = collect_tokens(Tokenizer::new("  abc")).expect("tokens");
+        let token = tokens.into_iter().next().expect("token");
+        let span = token.span;
+        let mut ident = token.map(|t| match t {
+            Token::Atom(Atom::Ident(ident)) => ident.to_string(),
+            t => panic!("unexpected {:?}", t),
+        });
+        assert_eq!((ident.span, ident.as_str()), (span, "abc"));
+        assert_eq!(ident.as_spanned_ref().map(|s| s.len()).inner, 3);
+        ident.as_spanned_mut().inner.push('d');
+        assert_eq!(ident.as_spanned_ref().inner, "abcd");
+        let replaced = ident.replace(42);
+        assert_eq!((replaced.span, replaced.inner), (span, 42));
+        assert_eq!(
+            SpannedElementOwned::new(span, ElementOwned::Comment(";".into())).span,
+            span
+        );
+        assert_eq!(replaced.into_inner(), 42);
+    }
+
+    #[test]
+    #[cfg(feature = "unicode")]
+    fn position_order() {
+        let mut tokens = collect_tokens(Tokenizer::new(PROG2)).expect("tokens");
+        let sorted = tokens.iter().map(|t| t.span).collect::<Vec<_>>();
+        assert!(sorted.windows(2).all(|w| w[0] < w[1]));
+        // the order of the positions follows the order of the offsets
+        for w in sorted.windows(2) {
+            assert!(w[0].start < w[1].start && w[0].start.offset < w[1].start.offset);
+            assert!(w[0].end <= w[1].start);
+        }
+        tokens.reverse();
+        let mut spans = tokens.iter().map(|t| t.span).collect::<Vec<_>>();
+        spans.sort();
+        assert_eq!(spans, sorted);
+        let key = sorted[sorted.len() / 2];
+        assert_eq!(sorted.binary_search(&key), Ok(sorted.len() / 2));
+
+        let pos = |line, col| Position {
+            line,
+            col,
+            offset: 0,
+        };
+        assert!(pos(1, 80) < pos(2, 0));
+        assert!(pos(2, 3) < pos(2, 4));
+        let outer = Span {
+            start: pos(1, 0),
+            end: pos(3, 1),
+        };
+        let inner = Span {
+            start: pos(1, 0),
+            end: pos(1, 5),
+        };
+        assert!(inner < outer);
+    }
+
+    #[test]
+    fn spanned_eq() {
+        use std::collections::HashSet;
+
+        let source = "a b a";
+        let tokens = collect_tokens(Tokenizer::new(source)).expect("tokens");
+        let idents = tokens
+            .iter()
+            .map(|t| {
+                t.as_spanned_ref()
+                    .replace(&source[t.span.start.offset..t.span.end.offset])
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(idents[0], Spanned::new(Span::on_line(1, 0, 1), "a"));
+        assert_ne!(idents[0], idents[2]);
+        assert!(idents[0].inner_eq(&idents[2]));
+        assert!(!idents[0].inner_eq(&idents[1]));
+        assert!(idents[0]
+            .as_spanned_ref()
+            .inner_eq(&idents[2].as_spanned_ref()));
+
+        let set = idents.iter().cloned().collect::<HashSet<_>>();
+        assert_eq!(set.len(), 3);
+        let values = idents.into_iter().map(|s| s.inner).collect::<HashSet<_>>();
+        assert_eq!(values.len(), 2);
+    }
+
+    #[test]
+    fn position_encoding() {
+        let encodings = [
+            PositionEncoding::Native,
+            PositionEncoding::OneBased,
+            PositionEncoding::ZeroBased,
+        ];
+        let tokens = collect_tokens(Tokenizer::new(PROG1)).expect("tokens");
+        for pos in tokens.iter().flat_map(|t| [t.span.start, t.span.end]) {
+            assert_eq!(
+                pos.to_encoding(PositionEncoding::Native),
+                (pos.line, pos.col)
+            );
+            assert_eq!(pos.to_one_based(), (pos.line, pos.col + 1));
+            assert_eq!(pos.to_zero_based(), (pos.line - 1, pos.col));
+            for encoding in encodings {
+                let (line, col) = pos.to_encoding(encoding);
+                assert_eq!(
+                    Position::from_encoding(encoding, line, col, pos.offset),
+                    pos
+                );
+                assert_eq!(
+                    pos.display(encoding).to_string(),
+                    format!("{}:{}", line, col)
+                );
+            }
+            assert_eq!(
+                pos.display(PositionEncoding::Native).to_string(),
+                pos.to_string()
+            );
+        }
+        // below the first line or column of the convention
+        let pos = Position::from_encoding(PositionEncoding::OneBased, 0, 0, 0);
+        assert_eq!(pos, Position::default());
+        let span = Span::on_line(1, 2, 3);
+        assert_eq!(
+            span.display(PositionEncoding::ZeroBased).to_string(),
+            "0:2-0:3"
+        );
+        assert_eq!(
+            span.display(PositionEncoding::Native).to_string(),
+            span.to_string()
+        );
+    }
+
+    #[test]
+    fn line_index_utf16() {
+        let source = "(a \"😀é\" \"𝄞\")\n(b)";
+        let index = LineIndex::new(source);
+        let tokens = collect_tokens(Tokenizer::new(source)).expect("tokens");
+        let cols = tokens.iter().map(|t| t.span.start.col).collect::<Vec<_>>();
+        assert_eq!(cols, [0, 1, 3, 8, 11, 0, 1, 2]);
+        let utf16 = tokens
+            .iter()
+            .map(|t| index.to_utf16_col(t.span.start.line, t.span.start.col))
+            .collect::<Vec<_>>();
+        let expected = [0, 1, 3, 9, 13, 0, 1, 2];
+        assert_eq!(utf16, expected.map(Some));
+        for (t, units) in tokens.iter().zip(expected) {
+            let line = t.span.start.line;
+            assert_eq!(index.from_utf16_col(line, units), Some(t.span.start.col));
+        }
+        // the end of the first line, after the closing parenthesis
+        assert_eq!(index.to_utf16_col(1, 12), Some(14));
+        assert_eq!(index.from_utf16_col(1, 14), Some(12));
+        assert_eq!(index.to_utf16_col(1, 13), None);
+        assert_eq!(index.from_utf16_col(1, 15), None);
+        assert_eq!(index.to_utf16_col(3, 0), None);
+        // inside the surrogate pairs of the astral characters
+        assert_eq!(index.from_utf16_col(1, 5), Some(4));
+        assert_eq!(index.from_utf16_col(1, 11), Some(9));
+    }
+
+    #[test]
+    fn offset_by_base() {
+        let document = "some text (x\n y) then\n  (a \"é\"\n   [b #00# c])";
+        let start = document.find("(a").unwrap();
+        let index = LineIndex::new(document);
+        let base = index.position_at(start);
+        let snippet = &document[start..];
+
+        let inner = collect_tokens(Tokenizer::new(snippet)).expect("tokens");
+        let outer = collect_tokens(Tokenizer::new_at(snippet, base)).expect("tokens");
+        let whole = collect_tokens(Tokenizer::new(document)).expect("tokens");
+        assert_eq!(outer.len(), inner.len());
+        for ((i, o), w) in inner.iter().zip(outer.iter()).zip(whole[7..].iter()) {
+            assert_eq!(offsets(i.span.offset_by(base)), offsets(o.span));
+            assert_eq!(offsets(o.span), offsets(w.span));
+        }
+
+        // the errors are in the document too
+        let snippet = "(a\n \"b";
+        let base = Position {
+            line: 5,
+            col: 3,
+            offset: 40,
+        };
+        let err = Parser::new_at(snippet, base).next().unwrap_err();
+        let eof = Position {
+            line: 6,
+            col: 3,
+            offset: 46,
+        };
+        match err {
+            ParserError::TokenizerError(TokenError::UnterminatedString(pos)) => {
+                assert_eq!(offsets(Span::point(pos)), offsets(Span::point(eof)))
+            }
+            e => panic!("unexpected {:?}", e),
+        }
+        assert_eq!(Position::default().offset_by(base), base);
+    }
+
+    #[test]
+    fn position_advance_str() {
+        let alphabet = ['a', ' ', '\n', '\r', '\t', 'é', '€', '😀', '(', '\n'];
+        let mut rng = Rng(0x5eed_1234_abcd_0042);
+        for _ in 0..1000 {
+            let text = rng.string(&alphabet, 24);
+            let start = Position {
+                line: 1 + rng.next() as usize % 4,
+                col: rng.next() as usize % 8,
+                offset: rng.next() as usize % 64,
+            };
+            let mut expected = start;
+            for c in text.chars() {
+                expected.advance(c);
+            }
+            let mut pos = start;
+            pos.advance_str(&text);
+            assert_eq!(
+                offsets(Span::point(pos)),
+                offsets(Span::point(expected)),
+                "{:?}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn spanned_deref() {
+        fn len<T: AsRef<str>>(s: T) -> usize {
+            s.as_ref().len()
+        }
+        let s = Spanned::new(Span::on_line(1, 0, 3), String::from("abc"));
+        assert_eq!(s.len(), 3);
+        assert_eq!(s.to_uppercase(), "ABC");
+        let inner: &String = s.as_ref();
+        assert_eq!(inner, "abc");
+        assert_eq!(len(s.as_ref() as &String), 3);
+        assert_eq!(s.as_spanned_ref().inner, "abc");
+
+        let el = Parser::new("(a b)").next().unwrap().unwrap();
+        assert_eq!(el.kind(), ElementKind::Group(GroupKind::Paren));
+        let children = el.paren().unwrap();
+        assert!(children[1].structural_eq(&children[1]));
+    }
+
+    #[test]
+    fn span_constructors() {
+        const SPANS: [Span; 3] = [
+            Span::on_line(1, 0, 2),
+            Span::on_lines(1, 3, 2, 2),
+            Span::point(Position::new(2, 2, 5)),
+        ];
+        let source = "(a\n b)";
+        let tokens = collect_tokens(Tokenizer::new(source)).expect("tokens");
+        assert_eq!(tokens[1].span, Span::on_line(1, 1, 2));
+        let start = Position::new(1, 0, 0);
+        let end = Position::new(2, 3, 6);
+        let group = Span::new(start, end);
+        assert_eq!(group, tokens[0].span.merge(&tokens[3].span));
+        assert_eq!(SPANS[0], Span::new(start, Position::new(1, 2, 2)));
+        assert_eq!((SPANS[1].start.line, SPANS[1].end.line), (1, 2));
+        assert_eq!((SPANS[1].start.col, SPANS[1].end.col), (3, 2));
+        assert_eq!(SPANS[2], Span::new(tokens[2].span.end, tokens[2].span.end));
+    }
+
+    #[test]
+    fn span_default_dummy() {
+        const EXPECTED: [Span; 2] = [Span::on_line(1, 0, 1), Span::on_line(1, 2, 3)];
+        let tokens = collect_tokens(Tokenizer::new("a b")).expect("tokens");
+        let spans = tokens.iter().map(|t| t.span).collect::<Vec<_>>();
+        assert_eq!(spans, EXPECTED);
+        assert!(spans.iter().all(|span| !span.is_dummy()));
+
+        // the default span is the one of an empty source, not the dummy span
+        let empty = Span::default();
+        assert!(!empty.is_dummy());
+        assert_eq!(empty, Span::on_line(1, 0, 0));
+        assert_ne!(empty, Span::DUMMY);
+        assert!(Span::DUMMY.is_dummy());
+        assert_eq!(Span::DUMMY.display_human().to_string(), "line 0, column 0");
+
+        // the conversions and the translations of the dummy span don't underflow its line 0
+        let dummy = Span::DUMMY.start;
+        assert_eq!(dummy.to_one_based(), (1, 1));
+        assert_eq!(dummy.to_zero_based(), (0, 0));
+        assert_eq!(dummy.display(PositionEncoding::OneBased).to_string(), "1:1");
+        assert_eq!(
+            Span::DUMMY.display(PositionEncoding::ZeroBased).to_string(),
+            "0:0-0:0"
+        );
+        let base = Position::new(3, 4, 20);
+        assert!(Span::DUMMY.offset_by(base).is_dummy());
+        let mut el = Spanned::new(
+            Span::on_line(1, 0, 3),
+            Element::Group(
+                GroupKind::Paren,
+                vec![Spanned::new(Span::DUMMY, Element::Atom(Atom::Ident("a")))],
+            ),
+        );
+        el.rewrite_spans(|span| span.offset_by(base));
+        assert_eq!(offsets(el.span), [(3, 4, 20), (3, 7, 23)]);
+        assert!(el.paren().unwrap()[0].span.is_dummy());
+    }
+
+    #[test]
+    fn rewrite_spans() {
+        let document = "; header\n  (a (b \"é\")\n     [c])";
+        let start = document.find('(').unwrap();
+        let base = LineIndex::new(document).position_at(start);
+        let mut el = Parser::new(&document[start..]).next().unwrap().unwrap();
+        let mut seen = Vec::new();
+        el.rewrite_spans(|span| {
+            seen.push(span);
+            span.offset_by(base)
+        });
+        let expected = Parser::new_at(&document[start..], base)
+            .next()
+            .unwrap()
+            .unwrap();
+        fn spans(el: &SpannedElement, out: &mut Vec<Span>) {
+            out.push(el.span);
+            for child in el.group(GroupKind::Paren).into_iter().flatten() {
+                spans(child, out);
+            }
+            for child in el.group(GroupKind::Bracket).into_iter().flatten() {
+                spans(child, out);
+            }
+        }
+        let (mut rewritten, mut translated) = (Vec::new(), Vec::new());
+        spans(&el, &mut rewritten);
+        spans(&expected, &mut translated);
+        assert_eq!(rewritten.len(), 7);
+        assert_eq!(
+            rewritten.into_iter().map(offsets).collect::<Vec<_>>(),
+            translated.into_iter().map(offsets).collect::<Vec<_>>()
+        );
+        // in the order of the source
+        assert!(seen.windows(2).all(|w| w[0].start < w[1].start));
+
+        let x = Spanned::new(Span::on_line(1, 0, 1), "x");
+        let moved = x.clone().with_span(Span::DUMMY);
+        assert!(moved.span.is_dummy() && moved.inner_eq(&x));
+        assert_eq!(x.map_span(|_| Span::on_line(2, 0, 1)).span.start.line, 2);
+    }
+
+    #[test]
+    fn token_error_messages() {
+        use std::error::Error;
+
+        let error = |text: &str| {
+            let cfg = TokenizerConfig::default().strict_escapes(true);
+            collect_tokens(Tokenizer::new_with_config(text, cfg)).unwrap_err()
+        };
+        let messages = [
+            (
+                "(a \"b",
+                "line 1, column 5: unterminated string, missing the closing `\"`",
+            ),
+            (
+                "#00ab",
+                "line 1, column 5: unterminated bytes, missing the closing `#`",
+            ),
+            ("(a \\)", "line 1, column 3: unexpected character '\\\\'"),
+            (
+                "\n #0g#",
+                "line 2, column 3: unexpected character 'g' in bytes, expected hexadecimal digits or `#`",
+            ),
+            (
+                "\"a\\q\"",
+                "line 1, column 2: invalid escape `\\q`: unknown escape",
+            ),
+        ];
+        for (text, message) in messages {
+            assert_eq!(error(text).to_string(), message, "{:?}", text);
+        }
+        assert!(error("\"a\\q\"").source().is_some());
+        assert!(error("#00ab").source().is_none());
+
+        let e = TokenError::DataError(NextCharError::IncompleteUtf8Sequence(0xe2), 7);
+        assert_eq!(
+            e.to_string(),
+            "invalid data at the byte offset 7: incomplete UTF-8 sequence starting with the byte 0xe2"
+        );
+        assert_eq!(
+            e.source().map(|e| e.to_string()).as_deref(),
+            Some("incomplete UTF-8 sequence starting with the byte 0xe2")
+        );
+        let messages = [
+            (NextCharError::EmptyDataStream, "empty data stream"),
+            (NextCharError::InvalidUtf8Sequence, "invalid UTF-8 sequence"),
+            (
+                NextCharError::InvalidUtf8ContByte,
+                "invalid UTF-8 continuation byte",
+            ),
+        ];
+        for (e, message) in messages {
+            assert_eq!(e.to_string(), message);
+        }
+
+        // the parser errors chain the tokenizer errors
+        let e = Parser::new("(a\n  \"b").next().unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "line 2, column 4: unterminated string, missing the closing `\"`"
+        );
+        let boxed: Box<dyn Error> = Box::new(e);
+        assert!(boxed.source().is_some_and(|e| e.is::<TokenError>()));
+    }
+
+    #[test]
+    fn adecimal_scaled() {
+        let dec = |raw_integral, raw_fractional| ADecimal {
+            raw_integral,
+            raw_fractional,
+        };
+        assert_eq!(dec("1", "5_0").scale(), 2);
+        assert_eq!(dec("1", "50").mantissa_and_scale(), Ok((150, 2)));
+        assert_eq!(dec("0", "0").mantissa_and_scale(), Ok((0, 1)));
+        assert_eq!(
+            dec("1", "005").to_scaled_int(2),
+            Err(DecimalError::PrecisionLoss)
+        );
+        assert_eq!(dec("1", "005").to_scaled_int_rounded(2), Ok(101));
+        assert_eq!(dec("1", "004_9").to_scaled_int_rounded(2), Ok(100));
+        assert_eq!(dec("1", "99_5").to_scaled_int_rounded(2), Ok(200));
+        assert_eq!(dec("1", "2000").to_scaled_int(1), Ok(12));
+        assert_eq!(
+            dec("12", "3").to_scaled_int(0),
+            Err(DecimalError::PrecisionLoss)
+        );
+        assert_eq!(dec("12", "3").to_scaled_int_rounded(0), Ok(12));
+
+        let max = i128::MAX.to_string();
+        assert_eq!(dec(&max, "0").to_scaled_int(0), Ok(i128::MAX));
+        assert_eq!(dec(&max, "0").to_scaled_int(1), Err(DecimalError::Overflow));
+        assert_eq!(
+            dec(&max, "5").to_scaled_int_rounded(0),
+            Err(DecimalError::Overflow)
+        );
+        assert_eq!(
+            dec("1", &"0".repeat(39)).mantissa_and_scale(),
+            Err(DecimalError::Overflow)
+        );
+
+        match &atoms("19.99")[0] {
+            Atom::Decimal(d) => assert_eq!(d.to_scaled_int(2), Ok(1999)),
+            a => panic!("unexpected {:?}", a),
+        }
+    }
+
+    #[test]
+    fn anum_width() {
+        let num = |base, dat| ANum { base, dat };
+        let hex = num(ANumBase::Hexadecimal, "00_ff");
+        assert_eq!(
+            (
+                hex.digit_count(),
+                hex.leading_zero_digits(),
+                hex.byte_width()
+            ),
+            (4, 2, Some(2))
+        );
+        let hex = num(ANumBase::Hexadecimal, "fff");
+        assert_eq!(
+            (
+                hex.digit_count(),
+                hex.leading_zero_digits(),
+                hex.byte_width()
+            ),
+            (3, 0, Some(2))
+        );
+        let hex = num(ANumBase::Hexadecimal, "0_0");
+        assert_eq!(
+            (
+                hex.digit_count(),
+                hex.leading_zero_digits(),
+                hex.byte_width()
+            ),
+            (2, 2, Some(1))
+        );
+        let dec = num(ANumBase::Decimal, "001_000");
+        assert_eq!(
+            (
+                dec.digit_count(),
+                dec.leading_zero_digits(),
+                dec.byte_width()
+            ),
+            (6, 2, None)
+        );
+        assert_eq!(num(ANumBase::Binary, "0101").byte_width(), None);
+
+        match &atoms("0x0000_00ff")[0] {
+            Atom::Integral(num) => assert_eq!(num.byte_width(), Some(4)),
+            a => panic!("unexpected {:?}", a),
+        }
+    }
+
+    #[test]
+    fn anum_to_f64() {
+        let num = |base, dat| ANum { base, dat };
+        assert_eq!(num(ANumBase::Decimal, "3").to_f64(), (3.0, true));
+        assert_eq!(num(ANumBase::Decimal, "000").to_f64(), (0.0, true));
+        assert_eq!(num(ANumBase::Binary, "1_0000").to_f64(), (16.0, true));
+        assert_eq!(
+            num(ANumBase::Hexadecimal, "20_0000_0000_0000").to_f64(),
+            (9007199254740992.0, true)
+        );
+        assert_eq!(
+            num(ANumBase::Hexadecimal, "20_0000_0000_0001").to_f64(),
+            (9007199254740992.0, false)
+        );
+        assert_eq!(
+            num(ANumBase::Hexadecimal, "20_0000_0000_0002").to_f64(),
+            (9007199254740994.0, true)
+        );
+        let max = num(ANumBase::Decimal, "18446744073709551615").to_f64();
+        assert_eq!(max, (u64::MAX as f64, false));
+        let pow = num(
+            ANumBase::Hexadecimal,
+            "1_0000_0000_0000_0000_0000_0000_0000_0000",
+        )
+        .to_f64();
+        assert_eq!(pow, (2f64.powi(128), true));
+        let huge = "f".repeat(300);
+        assert_eq!(
+            num(ANumBase::Hexadecimal, &huge).to_f64(),
+            (f64::INFINITY, false)
+        );
+        let mut rng = Rng(189);
+        for _ in 0..500 {
+            let v = rng.next() >> (rng.next() % 64);
+            let text = v.to_string();
+            let dec = ANum {
+                base: ANumBase::Decimal,
+                dat: &text,
+            };
+            let (f, exact) = dec.to_f64();
+            assert_eq!(f, v as f64);
+            assert_eq!(exact, f as u128 == v as u128, "{}", v);
+        }
+    }
+
+    #[test]
+    fn atom_try_from() {
+        use std::borrow::Cow;
+        let text = r#"300 0x10 1.5 "a\nb" "plain" #00ff# true false yes "bad\q" #0f0#"#;
+        let atoms = atoms(text);
+        let unexpected = |expected: &'static [AtomKind], found| FromAtomError::UnexpectedKind {
+            expected,
+            found,
+        };
+
+        assert_eq!(u32::try_from(&atoms[0]), Ok(300));
+        assert!(matches!(
+            u8::try_from(&atoms[0]),
+            Err(FromAtomError::Int(_))
+        ));
+        let n: Result<i64, _> = (&atoms[1]).try_into();
+        assert_eq!(n, Ok(16));
+        assert_eq!(
+            u64::try_from(&atoms[2]),
+            Err(unexpected(&[AtomKind::Integral], AtomKind::Decimal))
+        );
+
+        assert_eq!(f64::try_from(&atoms[0]), Ok(300.0));
+        assert_eq!(f64::try_from(&atoms[2]), Ok(1.5));
+        assert_eq!(
+            f64::try_from(&atoms[3]),
+            Err(unexpected(
+                &[AtomKind::Integral, AtomKind::Decimal],
+                AtomKind::String
+            ))
+        );
+
+        assert_eq!(String::try_from(&atoms[3]), Ok("a\nb".to_string()));
+        assert!(matches!(
+            Cow::<str>::try_from(&atoms[4]),
+            Ok(Cow::Borrowed("plain"))
+        ));
+        assert!(matches!(
+            String::try_from(&atoms[9]),
+            Err(FromAtomError::Escape(_))
+        ));
+        assert_eq!(
+            String::try_from(&atoms[7]),
+            Err(unexpected(&[AtomKind::String], AtomKind::Ident))
+        );
+
+        assert_eq!(Vec::<u8>::try_from(&atoms[5]), Ok(vec![0x00, 0xff]));
+        assert!(matches!(
+            Vec::<u8>::try_from(&atoms[10]),
+            Err(FromAtomError::Bytes(_))
+        ));
+        assert_eq!(
+            Vec::<u8>::try_from(&atoms[0]),
+            Err(unexpected(&[AtomKind::Bytes], AtomKind::Integral))
+        );
+
+        assert_eq!(bool::try_from(&atoms[6]), Ok(true));
+        assert_eq!(bool::try_from(&atoms[7]), Ok(false));
+        let err = bool::try_from(&atoms[8]).unwrap_err();
+        assert_eq!(err.to_string(), "expected `true` or `false`, found ident");
+        assert_eq!(
+            bool::try_from(&atoms[0]),
+            Err(FromAtomError::NotBool {
+                found: AtomKind::Integral
+            })
+        );
+        assert_eq!(
+            f64::try_from(&atoms[3]).unwrap_err().to_string(),
+            "expected integral number or decimal number, found string"
+        );
+
+        let err: Box<dyn std::error::Error> = u8::try_from(&atoms[0]).unwrap_err().into();
+        assert!(err.source().is_some());
+    }
+
+    #[test]
+    fn astr_owned() {
+        let strings = atoms(r#""tab\there" "plain""#)
+            .into_iter()
+            .map(|atom| match atom {
+                Atom::String(astr) => astr,
+                a => panic!("unexpected {:?}", a),
+            })
+            .collect::<Vec<_>>();
+        assert!(strings.iter().all(|astr| astr.is_borrowed()));
+        assert!(strings[0].has_escape);
+        assert!(!strings[1].has_escape);
+        assert!(matches!(
+            strings[1].decoded(),
+            std::borrow::Cow::Borrowed("plain")
+        ));
+
+        let constructed = AStr::from_decoded("tab\there");
+        assert!(!constructed.is_borrowed());
+        assert_eq!(constructed, strings[0]);
+        assert_eq!(constructed.decoded(), "tab\there");
+        let plain = AStr::from_decoded("plain");
+        assert!(!plain.has_escape);
+        assert_eq!(plain.raw(), "plain");
+        assert_eq!(plain, strings[1]);
+
+        let owned: AStr<'static> = strings[0].clone().into_owned();
+        assert_eq!(owned.raw(), strings[0].raw());
+        assert_eq!(owned.has_escape, strings[0].has_escape);
+
+        let atom = Atom::String(AStr::from_decoded("line\n\"quoted\""));
+        let printed = atom.to_string();
+        let tokens = collect_tokens(Tokenizer::new(&printed)).expect("tokens");
+        assert_eq!(tokens.len(), 1);
+        assert!(matches!(&tokens[0].inner, Token::Atom(a) if a.raw_eq(&atom)));
+    }
+
+    #[test]
+    fn abytes_from_bytes() {
+        let mut rng = Rng(192);
+        for len in 0..64 {
+            let data = (0..len).map(|_| rng.next() as u8).collect::<Vec<_>>();
+            let bytes = ABytes::from_bytes(&data);
+            assert_eq!(bytes.as_abytes().to_vec(), Ok(data.clone()));
+            let cfg = PrintConfig::default().bytes_uppercase(true).bytes_group(3);
+            let grouped = ABytes::from_bytes_with_config(&data, &cfg);
+            assert_eq!(grouped.as_abytes().to_vec(), Ok(data.clone()));
+            assert_eq!(grouped, bytes);
+
+            let mut p = Printer::new_with_config(cfg);
+            p.bytes(&data);
+            assert_eq!(p.to_string(), grouped.to_string());
+            assert_eq!(AtomBuf::bytes(&data), AtomBuf::from(bytes));
+        }
+        assert_eq!(ABytes::from_bytes(&[]).to_string(), "##");
+        assert_eq!(
+            ABytes::from_hex_str("").map(|b| b.into_string()),
+            Ok(String::new())
+        );
+        assert_eq!(
+            ABytes::from_hex_str("0x"),
+            Err(BytesDecodeError::InvalidChar { offset: 1, ch: 'x' })
+        );
+        assert_eq!(
+            ABytes::from_hex_str("0A_0b").unwrap(),
+            ABytes::from_bytes(&[10, 11])
+        );
+    }
+
+    #[test]
+    fn anum_normalized() {
+        let mut rng = Rng(193);
+        for _ in 0..1000 {
+            let v = (rng.next() as u128) << (rng.next() % 64) | rng.next() as u128;
+            let cases = [
+                (ANumBase::Binary, format!("{:b}", v)),
+                (ANumBase::Decimal, format!("000{}", v)),
+                (ANumBase::Hexadecimal, format!("{:X}", v)),
+            ];
+            for (base, dat) in cases.iter() {
+                let num = ANum { base: *base, dat };
+                assert_eq!(num.normalized(), v.to_string());
+            }
+        }
+        let max = ANum {
+            base: ANumBase::Hexadecimal,
+            dat: "ffff_ffff_ffff_ffff_ffff_ffff_ffff_ffff",
+        };
+        assert_eq!(max.normalized(), u128::MAX.to_string());
+        let above = ANum {
+            base: ANumBase::Hexadecimal,
+            dat: "1_0000_0000_0000_0000_0000_0000_0000_0000",
+        };
+        assert_eq!(
+            above.normalized(),
+            "340282366920938463463374607431768211456"
+        );
+        let zero = ANum {
+            base: ANumBase::Binary,
+            dat: "0_0",
+        };
+        let mut out = String::from("n=");
+        zero.write_normalized(&mut out).unwrap();
+        assert_eq!(out, "n=0");
+    }
+
+    #[test]
+    fn anum_signed() {
+        use core::num::IntErrorKind;
+        let num = |base, dat| ANum { base, dat };
+        let dec = |dat| num(ANumBase::Decimal, dat);
+        assert_eq!(dec("127").to_i8(), Ok(127));
+        assert_eq!(
+            dec("128").to_i8().map_err(|e| *e.kind()),
+            Err(IntErrorKind::PosOverflow)
+        );
+        assert_eq!(num(ANumBase::Hexadecimal, "7f_ff").to_i16(), Ok(i16::MAX));
+        assert!(num(ANumBase::Hexadecimal, "80_00").to_i16().is_err());
+        assert_eq!(dec("2_147_483_647").to_i32(), Ok(i32::MAX));
+        assert!(dec("2147483648").to_i32().is_err());
+        assert_eq!(dec("2147483648").to_i64(), Ok(1 << 31));
+        assert_eq!(num(ANumBase::Binary, "0").to_i64(), Ok(0));
+        assert!(num(ANumBase::Hexadecimal, "8000000000000000")
+            .to_i64()
+            .is_err());
+        assert_eq!(
+            num(ANumBase::Hexadecimal, "8000000000000000").to_i128(),
+            Ok(1 << 63)
+        );
+        assert!(num(ANumBase::Hexadecimal, &"f".repeat(32))
+            .to_i128()
+            .is_err());
+    }
+
+    #[test]
+    fn anum_pointer_width() {
+        let dec = |dat: &str| {
+            let num = ANum {
+                base: ANumBase::Decimal,
+                dat,
+            };
+            (num.to_usize().ok(), num.to_isize().ok())
+        };
+        // the value, and if it fits in an usize and in an isize of the target
+        #[cfg(target_pointer_width = "32")]
+        let expected: [(u128, bool, bool); 5] = [
+            (u32::MAX as u128, true, false),
+            (1 << 32, false, false),
+            (i32::MAX as u128, true, true),
+            (1 << 31, true, false),
+            (u64::MAX as u128, false, false),
+        ];
+        #[cfg(target_pointer_width = "64")]
+        let expected: [(u128, bool, bool); 5] = [
+            (u32::MAX as u128, true, true),
+            (1 << 32, true, true),
+            (i32::MAX as u128, true, true),
+            (1 << 31, true, true),
+            (u64::MAX as u128, true, false),
+        ];
+        for (v, fits_usize, fits_isize) in expected {
+            let text = v.to_string();
+            assert_eq!(
+                dec(&text),
+                (
+                    fits_usize.then_some(v as usize),
+                    fits_isize.then_some(v as isize)
+                ),
+                "{}",
+                text
+            );
+        }
+        let max = usize::MAX as u128;
+        assert_eq!(dec(&max.to_string()), (Some(usize::MAX), None));
+        assert_eq!(dec(&(max + 1).to_string()), (None, None));
+        let max = isize::MAX as u128;
+        assert_eq!(
+            dec(&max.to_string()),
+            (Some(isize::MAX as usize), Some(isize::MAX))
+        );
+        assert_eq!(
+            dec(&(max + 1).to_string()),
+            (Some(isize::MAX as usize + 1), None)
+        );
+        assert_eq!(
+            usize::try_from(&ANum {
+                base: ANumBase::Binary,
+                dat: "1_0"
+            }),
+            Ok(2)
+        );
+    }
+
+    #[test]
+    fn anum_try_from() {
+        fn convert<T: for<'a> TryFrom<&'a ANum<'a>>>(num: &ANum) -> Option<T> {
+            T::try_from(num).ok()
+        }
+        let num = ANum {
+            base: ANumBase::Hexadecimal,
+            dat: "1_00",
+        };
+        assert_eq!(u16::try_from(&num), Ok(256));
+        assert!(u8::try_from(&num).is_err());
+        let port: Result<i32, _> = (&num).try_into();
+        assert_eq!(port, Ok(256));
+        assert_eq!(convert::<u128>(&num), Some(256));
+        assert_eq!(convert::<i8>(&num), None);
+        let err: Box<dyn std::error::Error> = i8::try_from(&num).unwrap_err().into();
+        assert!(!err.to_string().is_empty());
+    }
+
+    #[test]
+    fn abytes_decode() {
+        assert_eq!(ABytes("").to_vec(), Ok(vec![]));
+        assert_eq!(
+            ABytes("0123456789abcdefABCDEF").to_vec(),
+            Ok(vec![
+                0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef, 0xab, 0xcd, 0xef
+            ])
+        );
+        assert_eq!(ABytes("d_e a\n\td").to_vec(), Ok(vec![0xde, 0xad]));
+        assert_eq!(
+            ABytes("abc").to_vec(),
+            Err(BytesDecodeError::OddLength { offset: 2 })
+        );
+        assert_eq!(
+            ABytes("a_").to_vec(),
+            Err(BytesDecodeError::OddLength { offset: 0 })
+        );
+        let err = ABytes("00é1").to_vec().unwrap_err();
+        assert_eq!(
+            err,
+            BytesDecodeError::InvalidChar {
+                offset: 2, ch: 'é'
+            }
+        );
+        assert_eq!(err.offset(), 2);
+
+        let mut buf = vec![1, 2];
+        assert_eq!(ABytes("0a0B").decode_into(&mut buf), Ok(()));
+        assert_eq!(buf, [1, 2, 0x0a, 0x0b]);
+        assert!(ABytes("ff0").decode_into(&mut buf).is_err());
+        assert_eq!(buf, [1, 2, 0x0a, 0x0b]);
+
+        assert_eq!(ABytes("").byte_len(), Ok(0));
+        assert_eq!(ABytes("0a 0B_ff").byte_len(), Ok(3));
+        assert_eq!(
+            ABytes("0a0").byte_len(),
+            Err(BytesDecodeError::OddLength { offset: 2 })
+        );
+        assert_eq!(
+            ABytes("0x").validate(),
+            Err(BytesDecodeError::InvalidChar { offset: 1, ch: 'x' })
+        );
+        assert_eq!(ABytes("0a_0B").validate(), Ok(()));
+        assert!(ABytes("").is_empty());
+        assert!(ABytes("__").is_empty());
+        assert!(!ABytes("_0").is_empty());
+
+        match &atoms("#DE_ad_BE_ef#")[0] {
+            Atom::Bytes(bytes) => assert_eq!(bytes.to_vec(), Ok(vec![0xde, 0xad, 0xbe, 0xef])),
+            a => panic!("unexpected {:?}", a),
+        }
+    }
+
+    #[test]
+    fn astr_decoded() {
+        let data = r#"("plain" "def\"x" "a\\b\n\t\r\0" "\x41\u{1F600}" "bad \q \x9 \u{}")"#;
+        let mut parser = Parser::new(data);
+        let el = parser.next().expect("parse").expect("element");
+        let strings: Vec<_> = el
+            .inner
+            .paren()
+            .expect("paren")
+            .iter()
+            .map(|e| {
+                e.inner
+                    .atom()
+                    .and_then(|a| a.string())
+                    .expect("string")
+                    .clone()
+            })
+            .collect();
+        assert!(matches!(
+            strings[0].decoded(),
+            std::borrow::Cow::Borrowed("plain")
+        ));
+        assert_eq!(strings[1].decoded(), "def\"x");
+        assert_eq!(strings[1].to_string(), "def\"x");
+        assert_eq!(strings[2].decoded(), "a\\b\n\t\r\0");
+        assert_eq!(strings[3].decoded(), "A😀");
+        assert_eq!(strings[4].decoded(), r"bad \q \x9 \u{}");
+
+        for (i, s) in strings.iter().enumerate().take(4) {
+            assert_eq!(s.try_decoded(), Ok(s.decoded()), "{}", i);
+        }
+        let err = strings[4].try_decoded().expect_err("invalid escape");
+        assert_eq!((err.offset(), err.sequence()), (4, r"\q"));
+        assert_eq!(
+            err.to_string(),
+            r"invalid escape `\q` at offset 4: unknown escape"
+        );
+        let el_span = el.paren().expect("paren")[4].span;
+        assert_eq!(
+            offsets(Span::point(err.position(strings[4].raw(), el_span))),
+            [(1, 54, 54); 2]
+        );
+        for (raw, offset, sequence) in [
+            (r"a\x1", 1, r"\x1"),
+            (r"\x9 ", 0, r"\x9 "),
+            (r"ab\", 2, r"\"),
+        ] {
+            let err = decode(raw).expect_err("invalid escape");
+            assert_eq!(
+                (err.offset(), err.sequence()),
+                (offset, sequence),
+                "{}",
+                raw
+            );
+        }
+    }
+
+    #[test]
+    fn unicode_escapes() {
+        assert_eq!(decode(r"\u{1F600}").as_deref(), Ok("😀"));
+        assert_eq!(
+            decode(r"\u{41}\u{0}\u{10FFFF}").as_deref(),
+            Ok("A\0\u{10ffff}")
+        );
+        let cases = [
+            (r"ab\u{}", EscapeErrorKind::EmptyUnicode, 2, r"\u{}"),
+            (
+                r"\u{1234567}",
+                EscapeErrorKind::TooManyDigits,
+                0,
+                r"\u{1234567",
+            ),
+            (r"\u{D800}", EscapeErrorKind::InvalidScalar, 0, r"\u{D800}"),
+            (
+                r"\u{110000}",
+                EscapeErrorKind::InvalidScalar,
+                0,
+                r"\u{110000}",
+            ),
+            (r"x\u{41", EscapeErrorKind::MissingClosingBrace, 1, r"\u{41"),
+            (r"\u{4g}", EscapeErrorKind::InvalidDigit, 0, r"\u{4g"),
+            (r"\u41", EscapeErrorKind::MissingOpeningBrace, 0, r"\u4"),
+        ];
+        for (raw, kind, offset, sequence) in cases {
+            let err = decode(raw).expect_err(raw);
+            assert_eq!(
+                (err.kind(), err.offset(), err.sequence()),
+                (kind, offset, sequence)
+            );
+        }
+
+        // the tokenizer accepts any escape, unless strict
+        let data = "(\"ok \\u{1F600}\"\n \"bad \\u{D800}\")";
+        let toks = collect_tokens(Tokenizer::new(data)).expect("tokens");
+        assert_eq!(toks.len(), 4);
+        let strict = TokenizerConfig::default().strict_escapes(true);
+        match collect_tokens(Tokenizer::new_with_config(data, strict)) {
+            Err(TokenError::InvalidEscape(pos, e)) => {
+                assert_eq!(offsets(Span::point(pos)), [(2, 6, 22); 2]);
+                assert_eq!(e.kind(), EscapeErrorKind::InvalidScalar);
+            }
+            r => panic!("unexpected {:?}", r.map(|t| t.len())),
+        }
+    }
+
+    #[test]
+    fn hex_escapes() {
+        assert_eq!(decode(r"\x41\x7f\x00\x1B").as_deref(), Ok("A\x7f\0\x1b"));
+        let cases = [
+            (r"\x9", EscapeErrorKind::Truncated, 0, r"\x9"),
+            (r"a\x9 ", EscapeErrorKind::InvalidDigit, 1, r"\x9 "),
+            (r"ab\xZZ", EscapeErrorKind::InvalidDigit, 2, r"\xZ"),
+            (r"\x80", EscapeErrorKind::NotAscii, 0, r"\x80"),
+            (r"\xff", EscapeErrorKind::NotAscii, 0, r"\xff"),
+        ];
+        for (raw, kind, offset, sequence) in cases {
+            let err = decode(raw).expect_err(raw);
+            assert_eq!(
+                (err.kind(), err.offset(), err.sequence()),
+                (kind, offset, sequence)
+            );
+        }
+
+        let strict = TokenizerConfig::default().strict_escapes(true);
+        let toks = collect_tokens(Tokenizer::new_with_config(r#""\x41\x1b""#, strict.clone()));
+        assert_eq!(toks.map(|t| t.len()), Ok(1));
+        match collect_tokens(Tokenizer::new_with_config(r#"  "ok\xZZ""#, strict)) {
+            Err(TokenError::InvalidEscape(pos, e)) => {
+                assert_eq!(offsets(Span::point(pos)), [(1, 5, 5); 2]);
+                assert_eq!(e.kind(), EscapeErrorKind::InvalidDigit);
+            }
+            r => panic!("unexpected {:?}", r.map(|t| t.len())),
+        }
+
+        // the C0 control characters are printed as `\xNN`
+        let mut p = Printer::default();
+        p.string("\x01\x1f\x7f");
+        let out = p.to_string();
+        assert_eq!(out, r#""\x01\x1f\x7f""#);
+        match &atoms(&out)[0] {
+            Atom::String(astr) => assert_eq!(astr.decoded(), "\x01\x1f\x7f"),
+            a => panic!("unexpected {:?}", a),
         }
     }
 }
@@ -1,21 +1,50 @@
 use std::fmt;
 
-/// A file position for human composed of the line (starting at 1), and column (starting a 0)
-#[derive(Clone, Copy, PartialEq, Eq)]
+/// A file position for human composed of the line (starting at 1), and column (starting a 0),
+/// with the byte offset from the start of the text (starting at 0)
+///
+/// The positions of the tokenizer and the parser allow to slice the source with the offsets:
+///
+/// ```
+/// use s_expr::Parser;
+///
+/// let source = "(\"café\"\n  \"crème\")";
+/// let el = Parser::new(source).next().unwrap().unwrap();
+/// let creme = el.paren().unwrap()[1].span;
+/// assert_eq!(&source[creme.start.offset..creme.end.offset], "\"crème\"");
+/// assert_eq!((creme.start.line, creme.start.col, creme.start.offset), (2, 2, 11));
+/// ```
+///
+/// The lines and the columns of other conventions, e.g. of the editors or of the language server
+/// protocol, are converted with a [`PositionEncoding`]. The `Display` of a position uses the
+/// convention of the position, [`Position::display`] another convention.
+///
+/// The positions are ordered by line, then by column, which is also the order of the offsets
+/// of the positions of a text.
+///
+/// With the `serde` feature, a position is serialized as a map of its fields, e.g.
+/// `{"line":2,"col":2,"offset":9}` in JSON, and a span as a map of its `start` and `end` positions.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Position {
     pub line: usize,
     pub col: usize,
+    pub offset: usize,
 }
 
 impl Default for Position {
     fn default() -> Self {
-        Self { line: 1, col: 0 }
+        Self {
+            line: 1,
+            col: 0,
+            offset: 0,
+        }
     }
 }
 
 impl fmt::Debug for Position {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}:{}", self.line, self.col)
+        write!(f, "{}:{}@{}", self.line, self.col, self.offset)
     }
 }
 
@@ -26,32 +55,293 @@ impl fmt::Display for Position {
 }
 
 impl Position {
+    /// Create the position at the line (starting at 1) and the column (starting at 0), with
+    /// the byte offset from the start of the text
+    pub const fn new(line: usize, col: usize, offset: usize) -> Self {
+        Self { line, col, offset }
+    }
+
+    /// Create the position from the line and the column in the convention of the encoding
+    ///
+    /// A line or a column below the first of the convention is the first one.
+    ///
+    /// ```
+    /// use s_expr::{Position, PositionEncoding};
+    ///
+    /// let pos = Position::from_encoding(PositionEncoding::ZeroBased, 1, 2, 5);
+    /// assert_eq!((pos.line, pos.col), (2, 2));
+    /// assert_eq!(pos.to_one_based(), (2, 3));
+    /// ```
+    pub fn from_encoding(
+        encoding: PositionEncoding,
+        line: usize,
+        col: usize,
+        offset: usize,
+    ) -> Self {
+        let (first_line, first_col) = encoding.first();
+        Self {
+            line: line.saturating_sub(first_line) + 1,
+            col: col.saturating_sub(first_col),
+            offset,
+        }
+    }
+
+    /// The line and the column in the convention of the encoding
+    ///
+    /// The line 0 of the positions of [`Span::DUMMY`] is the first line of the convention.
+    pub fn to_encoding(&self, encoding: PositionEncoding) -> (usize, usize) {
+        let (first_line, first_col) = encoding.first();
+        (
+            self.line.saturating_sub(1) + first_line,
+            self.col + first_col,
+        )
+    }
+
+    /// The line and the column both starting at 1
+    pub fn to_one_based(&self) -> (usize, usize) {
+        self.to_encoding(PositionEncoding::OneBased)
+    }
+
+    /// The line and the column both starting at 0
+    pub fn to_zero_based(&self) -> (usize, usize) {
+        self.to_encoding(PositionEncoding::ZeroBased)
+    }
+
+    /// Display the position as `line:col` in the convention of the encoding
+    ///
+    /// ```
+    /// use s_expr::{Position, PositionEncoding};
+    ///
+    /// let pos = Position { line: 2, col: 0, offset: 4 };
+    /// assert_eq!(pos.to_string(), "2:0");
+    /// assert_eq!(pos.display(PositionEncoding::OneBased).to_string(), "2:1");
+    /// assert_eq!(pos.display(PositionEncoding::ZeroBased).to_string(), "1:0");
+    /// ```
+    pub fn display(&self, encoding: PositionEncoding) -> impl fmt::Display {
+        let (line, col) = self.to_encoding(encoding);
+        DisplayLineCol(line, col)
+    }
+
+    /// Translate a position of a text embedded in a document to the position in the document,
+    /// the base being the position of the start of the text in the document
+    ///
+    /// The columns of the first line of the text are after the column of the base, the columns
+    /// of the other lines are unchanged. The positions of [`Span::DUMMY`] are unchanged.
+    ///
+    /// ```
+    /// use s_expr::Position;
+    ///
+    /// let base = Position { line: 10, col: 4, offset: 120 };
+    /// let first = Position { line: 1, col: 2, offset: 2 };
+    /// let next = Position { line: 2, col: 2, offset: 8 };
+    /// let (first, next) = (first.offset_by(base), next.offset_by(base));
+    /// assert_eq!((first.line, first.col, first.offset), (10, 6, 122));
+    /// assert_eq!((next.line, next.col, next.offset), (11, 2, 128));
+    /// ```
+    pub fn offset_by(&self, base: Position) -> Position {
+        if self.line == 0 {
+            return *self;
+        }
+        Position {
+            line: base.line + self.line - 1,
+            col: if self.line == 1 {
+                base.col + self.col
+            } else {
+                self.col
+            },
+            offset: base.offset + self.offset,
+        }
+    }
+
+    /// Display the position for the messages to the users, e.g. `line 2, column 4`
+    ///
+    /// The `Display` of the position is the compact `2:4`.
+    pub fn display_human(&self) -> impl fmt::Display {
+        HumanPosition(*self)
+    }
+
+    /// Move to the start of the next line, after a `\n`
     pub fn advance_line(&mut self) {
         self.line += 1;
         self.col = 0;
+        self.offset += 1;
     }
 
+    /// Move to the next column, after an ascii character
     pub fn advance_col(&mut self) {
         self.col += 1;
+        self.offset += 1;
     }
 
+    /// Move after the character, the offset advancing by its UTF-8 length
     pub fn advance(&mut self, c: char) {
         if c == '\n' {
             self.advance_line()
         } else {
-            self.advance_col()
+            self.col += 1;
+            self.offset += c.len_utf8();
+        }
+    }
+
+    /// Move after the text, to the same position as advancing after each of its characters
+    ///
+    /// ```
+    /// use s_expr::Position;
+    ///
+    /// let mut pos = Position::default();
+    /// pos.advance_str("(a\n  é");
+    /// assert_eq!((pos.line, pos.col, pos.offset), (2, 3, 7));
+    /// ```
+    pub fn advance_str(&mut self, s: &str) {
+        let bytes = s.as_bytes();
+        match bytes.iter().rposition(|b| *b == b'\n') {
+            None => self.col += s.chars().count(),
+            Some(last) => {
+                self.line += bytes.iter().filter(|b| **b == b'\n').count();
+                self.col = s[last + 1..].chars().count();
+            }
         }
+        self.offset += s.len();
+    }
+}
+
+/// Convention of the numbering of the lines and the columns
+///
+/// The columns count the characters in all the conventions, so the columns of a protocol
+/// counting the UTF-16 code units, like the default of the language server protocol, only match
+/// on the lines with characters below U+10000, and are converted with
+/// [`LineIndex::to_utf16_col`] and [`LineIndex::from_utf16_col`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PositionEncoding {
+    /// Lines starting at 1 and columns starting at 0, the convention of [`Position`]
+    #[default]
+    Native,
+    /// Lines and columns starting at 1, the convention of most editors and compilers
+    OneBased,
+    /// Lines and columns starting at 0, the convention of the language server protocol
+    ZeroBased,
+}
+
+impl PositionEncoding {
+    // the number of the first line and of the first column
+    fn first(self) -> (usize, usize) {
+        match self {
+            PositionEncoding::Native => (1, 0),
+            PositionEncoding::OneBased => (1, 1),
+            PositionEncoding::ZeroBased => (0, 0),
+        }
+    }
+}
+
+struct HumanPosition(Position);
+
+impl fmt::Display for HumanPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.0.line, self.0.col)
+    }
+}
+
+struct DisplayLineCol(usize, usize);
+
+impl fmt::Display for DisplayLineCol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.0, self.1)
     }
 }
 
 /// Span defined by 2 positions, defining a range between start and end
-#[derive(Clone, Copy, PartialEq, Eq)]
+///
+/// The spans are ordered by start, then by end, so a span is before the spans it contains
+/// which start after it, and after the spans it contains which start at the same position:
+///
+/// ```
+/// use s_expr::Span;
+///
+/// let mut spans = vec![Span::on_line(1, 4, 6), Span::on_line(1, 0, 8), Span::on_line(1, 0, 2)];
+/// spans.sort();
+/// assert_eq!(spans, [Span::on_line(1, 0, 2), Span::on_line(1, 0, 8), Span::on_line(1, 4, 6)]);
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Span {
     pub start: Position,
     pub end: Position,
 }
 
+impl Default for Span {
+    /// The empty span at the default position, the start of a text
+    fn default() -> Self {
+        Self::point(Position::default())
+    }
+}
+
 impl Span {
+    /// Span of the elements which don't come from a source, e.g. built by the code or generated
+    ///
+    /// Its positions are on the line 0, which isn't a line of any text, so it's not the span of
+    /// any parsed element, contrary to the [`Default`] span at the start of the text.
+    pub const DUMMY: Span = Span::point(Position::new(0, 0, 0));
+
+    /// Check if the span is [`Span::DUMMY`], the span of an element which doesn't come from a source
+    ///
+    /// ```
+    /// use s_expr::Span;
+    ///
+    /// assert!(Span::DUMMY.is_dummy());
+    /// assert!(!Span::default().is_dummy());
+    /// ```
+    pub const fn is_dummy(&self) -> bool {
+        self.start.line == 0 && self.end.line == 0
+    }
+
+    /// Create the span between the start and the end positions
+    pub const fn new(start: Position, end: Position) -> Self {
+        Self { start, end }
+    }
+
+    /// Create the empty span at a position, e.g. to mark a position in a diagnostic
+    ///
+    /// ```
+    /// use s_expr::{Position, Span};
+    ///
+    /// const EOF: Span = Span::point(Position::new(3, 1, 12));
+    /// assert_eq!(EOF.start, EOF.end);
+    /// assert_eq!(EOF.to_string(), "3:1-3:1");
+    /// ```
+    pub const fn point(pos: Position) -> Self {
+        Self {
+            start: pos,
+            end: pos,
+        }
+    }
+
+    /// Check if the position is in the span, which includes the start position and excludes
+    /// the end position, as the end of the spans of the tokenizer is just past the token
+    ///
+    /// An empty span contains no position.
+    ///
+    /// ```
+    /// use s_expr::{Position, Span};
+    ///
+    /// let span = Span::on_line(1, 2, 5);
+    /// let pos = |col| Position { line: 1, col, offset: col };
+    /// assert!(span.contains(pos(2)) && span.contains(pos(4)));
+    /// assert!(!span.contains(pos(1)) && !span.contains(pos(5)));
+    /// ```
+    pub fn contains(&self, pos: Position) -> bool {
+        self.start <= pos && pos < self.end
+    }
+
+    /// Check if the other span is in the span, starting at or after its start and ending at
+    /// or before its end
+    ///
+    /// A span contains itself, and the empty spans at its start and at its end.
+    pub fn contains_span(&self, other: &Span) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    /// Span from the start of this span to the end of the other span, which must not start
+    /// before this span, see [`Span::merge`] for spans in any order
     pub fn extend(&self, other: &Self) -> Self {
         Self {
             start: self.start,
@@ -59,20 +349,82 @@ impl Span {
         }
     }
 
-    pub fn on_line(line: usize, start_col: usize, end_col: usize) -> Self {
+    /// Smallest span covering both spans, whatever their order
+    ///
+    /// ```
+    /// use s_expr::Span;
+    ///
+    /// let (a, b) = (Span::on_line(1, 2, 4), Span::on_line(1, 6, 9));
+    /// assert_eq!(a.merge(&b), Span::on_line(1, 2, 9));
+    /// assert_eq!(b.merge(&a), Span::on_line(1, 2, 9));
+    /// ```
+    pub fn merge(&self, other: &Self) -> Self {
         Self {
-            start: Position {
-                line,
-                col: start_col,
-            },
-            end: Position { line, col: end_col },
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+
+    /// Check if the spans have some positions in common, see [`Span::contains`]
+    ///
+    /// The spans which only touch, one ending where the other starts, don't intersect, and the
+    /// empty spans intersect nothing.
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.intersection(other).is_some()
+    }
+
+    /// Get the positions common to the spans, or None if they don't intersect
+    ///
+    /// ```
+    /// use s_expr::Span;
+    ///
+    /// let (a, b) = (Span::on_line(1, 2, 6), Span::on_line(1, 4, 9));
+    /// assert_eq!(a.intersection(&b), Some(Span::on_line(1, 4, 6)));
+    /// assert_eq!(a.intersection(&Span::on_line(1, 6, 7)), None);
+    /// ```
+    pub fn intersection(&self, other: &Self) -> Option<Self> {
+        let start = self.start.max(other.start);
+        let end = self.end.min(other.end);
+        (start < end).then_some(Self { start, end })
+    }
+
+    /// Translate a span of a text embedded in a document to the span in the document, the base
+    /// being the position of the start of the text in the document, see [`Position::offset_by`]
+    pub fn offset_by(&self, base: Position) -> Span {
+        Span {
+            start: self.start.offset_by(base),
+            end: self.end.offset_by(base),
+        }
+    }
+
+    /// Span between two columns of a line, the offsets being the columns
+    ///
+    /// The offsets are exact for the first line of an ascii text only, the other spans
+    /// need to be built from positions with their offsets, e.g. from [`LineIndex::position_at`].
+    pub const fn on_line(line: usize, start_col: usize, end_col: usize) -> Self {
+        Self::on_lines(line, start_col, line, end_col)
+    }
+
+    /// Span between a column of a line and a column of another line, the offsets being the columns
+    ///
+    /// As for [`Span::on_line`], the offsets are not the ones of a text, so the span is only
+    /// equal to the spans of the parser with the same offsets.
+    pub const fn on_lines(
+        start_line: usize,
+        start_col: usize,
+        end_line: usize,
+        end_col: usize,
+    ) -> Self {
+        Self {
+            start: Position::new(start_line, start_col, start_col),
+            end: Position::new(end_line, end_col, end_col),
         }
     }
 }
 
 impl fmt::Debug for Span {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}-{}", self.start, self.end)
+        write!(f, "{:?}-{:?}", self.start, self.end)
     }
 }
 
@@ -82,9 +434,321 @@ impl fmt::Display for Span {
     }
 }
 
-/// A type with the span (start and end positions) associated
+impl Span {
+    /// Display the span as `start-end` with the positions in the convention of the encoding
+    ///
+    /// ```
+    /// use s_expr::{PositionEncoding, Span};
+    ///
+    /// let span = Span::on_line(3, 0, 4);
+    /// assert_eq!(span.display(PositionEncoding::OneBased).to_string(), "3:1-3:5");
+    /// ```
+    pub fn display(&self, encoding: PositionEncoding) -> impl fmt::Display {
+        let (start, end) = (self.start.display(encoding), self.end.display(encoding));
+        DisplaySpan(start, end)
+    }
+
+    /// Display the span for the messages to the users, with the columns of a span on one line,
+    /// and the lines only of a span on multiple lines
+    ///
+    /// The `Display` of the span is the compact `1:4-1:9`.
+    ///
+    /// ```
+    /// use s_expr::{Position, Span};
+    ///
+    /// assert_eq!(Span::on_line(1, 4, 9).display_human().to_string(), "line 1, columns 4-9");
+    /// assert_eq!(Span::on_line(1, 4, 4).display_human().to_string(), "line 1, column 4");
+    /// let start = Position { line: 2, col: 0, offset: 10 };
+    /// let end = Position { line: 5, col: 1, offset: 40 };
+    /// assert_eq!(Span { start, end }.display_human().to_string(), "lines 2-5");
+    /// ```
+    pub fn display_human(&self) -> impl fmt::Display {
+        HumanSpan(*self)
+    }
+}
+
+struct HumanSpan(Span);
+
+impl fmt::Display for HumanSpan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Span { start, end } = self.0;
+        if start.line != end.line {
+            write!(f, "lines {}-{}", start.line, end.line)
+        } else if start.col != end.col {
+            write!(f, "line {}, columns {}-{}", start.line, start.col, end.col)
+        } else {
+            write!(f, "{}", start.display_human())
+        }
+    }
+}
+
+struct DisplaySpan<P>(P, P);
+
+impl<P: fmt::Display> fmt::Display for DisplaySpan<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.0, self.1)
+    }
+}
+
+/// Index of the lines of a text, to convert between the byte offsets and the positions without
+/// tokenizing the text again
+///
+/// The positions are the ones of the tokenizer: the lines start at 1 and end with `\n`, and the
+/// columns count the characters, a `\r` or a tab being one character.
+///
+/// ```
+/// use s_expr::{LineIndex, Position};
+///
+/// let index = LineIndex::new("(a\n  é b)");
+/// assert_eq!(index.line_count(), 2);
+/// assert_eq!(index.line_text(2), Some("  é b)"));
+/// let b = Position { line: 2, col: 4, offset: 8 };
+/// assert_eq!(index.position_at(8), b);
+/// assert_eq!(index.offset_at(b), Some(8));
+/// ```
 #[derive(Clone, Debug)]
+pub struct LineIndex<'a> {
+    text: &'a str,
+    // offset of the first byte of each line
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    /// Index the lines of the text
+    pub fn new(text: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { text, line_starts }
+    }
+
+    /// Get the number of lines, which is one more than the number of `\n`
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Get the text of a line, without its `\n`, or None if there's no such line
+    pub fn line_text(&self, line: usize) -> Option<&'a str> {
+        let start = *self.line_starts.get(line.checked_sub(1)?)?;
+        let end = self
+            .line_starts
+            .get(line)
+            .map_or(self.text.len(), |next| next - 1);
+        Some(&self.text[start..end])
+    }
+
+    /// Get the position at a byte offset
+    ///
+    /// An offset past the end of the text is the position of the end of the text, and an offset
+    /// inside a character is the position of this character.
+    pub fn position_at(&self, offset: usize) -> Position {
+        let mut offset = offset.min(self.text.len());
+        while !self.text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let line = self.line_starts.partition_point(|start| *start <= offset);
+        let start = self.line_starts[line - 1];
+        Position {
+            line,
+            col: self.text[start..offset].chars().count(),
+            offset,
+        }
+    }
+
+    /// Get the byte offset of the line and the column of a position, or None if the position is
+    /// not in the text
+    ///
+    /// The column can be the one just after the last character of the line, which is the
+    /// position of its `\n`, or of the end of the text on the last line.
+    pub fn offset_at(&self, pos: Position) -> Option<usize> {
+        let line = self.line_text(pos.line)?;
+        let start = self.line_starts[pos.line - 1];
+        match line.char_indices().nth(pos.col) {
+            Some((i, _)) => Some(start + i),
+            None if pos.col == line.chars().count() => Some(start + line.len()),
+            None => None,
+        }
+    }
+
+    /// Convert a column of a line counting the characters to a column counting the UTF-16 code
+    /// units, or None if the column is not in the line
+    ///
+    /// The characters above U+FFFF, e.g. most emojis, are 2 UTF-16 code units.
+    ///
+    /// ```
+    /// use s_expr::LineIndex;
+    ///
+    /// let index = LineIndex::new("(smile 😀 b)");
+    /// assert_eq!(index.to_utf16_col(1, 9), Some(10));
+    /// assert_eq!(index.from_utf16_col(1, 10), Some(9));
+    /// ```
+    pub fn to_utf16_col(&self, line: usize, col: usize) -> Option<usize> {
+        let text = self.line_text(line)?;
+        let mut chars = text.chars();
+        let units = chars.by_ref().take(col).map(char::len_utf16).sum();
+        let counted = text.chars().count() - chars.count();
+        (counted == col).then_some(units)
+    }
+
+    /// Convert a column of a line counting the UTF-16 code units to a column counting the
+    /// characters, or None if the column is not in the line
+    ///
+    /// A column between the 2 code units of a character is the column of this character.
+    pub fn from_utf16_col(&self, line: usize, utf16_col: usize) -> Option<usize> {
+        let text = self.line_text(line)?;
+        let mut units = 0;
+        for (col, c) in text.chars().enumerate() {
+            if units + c.len_utf16() > utf16_col {
+                return Some(col);
+            }
+            units += c.len_utf16();
+        }
+        (units == utf16_col).then(|| text.chars().count())
+    }
+}
+
+// byte offset of a position in the text, the columns counting the characters,
+// or None if the position is not in the text
+pub(crate) fn offset(text: &str, pos: Position) -> Option<usize> {
+    if pos.line == 0 {
+        return None;
+    }
+    let mut line_start = 0;
+    for _ in 1..pos.line {
+        line_start += text[line_start..].find('\n')? + 1;
+    }
+    let line = &text[line_start..];
+    let line = &line[..line.find('\n').unwrap_or(line.len())];
+    if pos.col == line.chars().count() {
+        return Some(line_start + line.len());
+    }
+    line.char_indices()
+        .nth(pos.col)
+        .map(|(i, _)| line_start + i)
+}
+
+/// A type with the span (start and end positions) associated
+///
+/// Two spanned values are equal when both their spans and their values are equal,
+/// use [`Spanned::inner_eq`] to compare only the values.
+///
+/// The spanned value dereferences to the value, so the methods of the value are called directly,
+/// except the methods named like the ones of `Spanned`, e.g. `map`:
+///
+/// ```
+/// use s_expr::Parser;
+///
+/// let el = Parser::new("(let x 1)").next().unwrap().unwrap();
+/// let children = el.paren().expect("paren group");
+/// assert_eq!(children[0].atom().and_then(|atom| atom.ident()), Some("let"));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Spanned<T> {
     pub span: Span,
     pub inner: T,
 }
+
+impl<T> core::ops::Deref for Spanned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T> AsRef<T> for Spanned<T> {
+    fn as_ref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T> Spanned<T> {
+    /// Associate the span to the value
+    pub fn new(span: Span, inner: T) -> Self {
+        Self { span, inner }
+    }
+
+    /// Transform the value, keeping the span
+    ///
+    /// ```
+    /// use s_expr::Parser;
+    ///
+    /// let el = Parser::new("  (a b)").next().unwrap().unwrap();
+    /// let len = el.as_spanned_ref().map(|el| el.paren().map_or(0, |children| children.len()));
+    /// assert_eq!((len.inner, len.span), (2, el.span));
+    /// ```
+    pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> Spanned<U> {
+        Spanned {
+            span: self.span,
+            inner: f(self.inner),
+        }
+    }
+
+    /// Borrow the value, keeping the span
+    pub fn as_spanned_ref(&self) -> Spanned<&T> {
+        Spanned {
+            span: self.span,
+            inner: &self.inner,
+        }
+    }
+
+    /// Borrow the value mutably, keeping the span
+    pub fn as_spanned_mut(&mut self) -> Spanned<&mut T> {
+        Spanned {
+            span: self.span,
+            inner: &mut self.inner,
+        }
+    }
+
+    /// Replace the value by another value, of any type, keeping the span
+    pub fn replace<U>(self, inner: U) -> Spanned<U> {
+        Spanned {
+            span: self.span,
+            inner,
+        }
+    }
+
+    /// Replace the span, keeping the value
+    pub fn with_span(self, span: Span) -> Self {
+        Self { span, ..self }
+    }
+
+    /// Transform the span, keeping the value
+    ///
+    /// ```
+    /// use s_expr::{Position, Span, Spanned};
+    ///
+    /// let x = Spanned::new(Span::on_line(1, 2, 3), 'x');
+    /// let moved = x.map_span(|span| span.offset_by(Position::new(4, 0, 30))).span;
+    /// assert_eq!((moved.start.line, moved.start.col, moved.start.offset), (4, 2, 32));
+    /// ```
+    pub fn map_span<F: FnOnce(Span) -> Span>(self, f: F) -> Self {
+        Self {
+            span: f(self.span),
+            ..self
+        }
+    }
+
+    /// Get the value, dropping the span
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Compare the values, ignoring the spans
+    ///
+    /// ```
+    /// use s_expr::{Span, Spanned};
+    ///
+    /// let a = Spanned::new(Span::on_line(1, 0, 1), 'a');
+    /// let b = Spanned::new(Span::on_line(2, 4, 5), 'a');
+    /// assert!(a.inner_eq(&b));
+    /// assert_ne!(a, b);
+    /// ```
+    pub fn inner_eq<U>(&self, other: &Spanned<U>) -> bool
+    where
+        T: PartialEq<U>,
+    {
+        self.inner == other.inner
+    }
+}
@@ -1,6 +1,9 @@
-use super::data::{Atom, GroupKind};
-use super::loc::{Position, Span, Spanned};
+use super::data::{Atom, AtomBuf, AtomKind, GroupKind};
+use super::loc::{LineIndex, Position, Span, Spanned};
+use super::render::render_span;
 use super::tokenizer::{Token, TokenError, Tokenizer, TokenizerConfig};
+use core::cmp::Ordering;
+use core::fmt;
 
 /// Element of S-Expr
 #[derive(Debug, Clone)]
@@ -10,7 +13,47 @@ pub enum Element<'a> {
     Comment(&'a str),
 }
 
+/// Kind of an element, without its data, e.g. to report what was found in an error message
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ElementKind {
+    /// Group of the given kind
+    Group(GroupKind),
+    /// Atom of the given kind
+    Atom(AtomKind),
+    /// Comment
+    Comment,
+}
+
+impl fmt::Display for ElementKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ElementKind::Group(grp) => grp.fmt(f),
+            ElementKind::Atom(atom) => atom.fmt(f),
+            ElementKind::Comment => f.write_str("comment"),
+        }
+    }
+}
+
 impl<'a> Element<'a> {
+    /// Get the kind of the element
+    ///
+    /// ```
+    /// use s_expr::{AtomKind, ElementKind, GroupKind, Parser};
+    ///
+    /// let el = Parser::new("(a \"b\") ; c").next().unwrap().unwrap();
+    /// assert_eq!(el.kind(), ElementKind::Group(GroupKind::Paren));
+    /// let children = el.paren().unwrap();
+    /// assert_eq!(children[1].kind(), ElementKind::Atom(AtomKind::String));
+    /// assert_eq!(format!("found {}", el.kind()), "found parentheses `()`");
+    /// ```
+    pub fn kind(&self) -> ElementKind {
+        match self {
+            Element::Group(grp, _) => ElementKind::Group(*grp),
+            Element::Atom(atom) => ElementKind::Atom(atom.kind()),
+            Element::Comment(_) => ElementKind::Comment,
+        }
+    }
+
     /// Return the atom if the element is an atom, otherwise None
     pub fn atom(&self) -> Option<&Atom<'a>> {
         match self {
@@ -44,19 +87,199 @@ impl<'a> Element<'a> {
     pub fn brace(&self) -> Option<&[SpannedElement<'a>]> {
         self.group(GroupKind::Brace)
     }
+
+    /// Check if two elements have the same structure and content, ignoring their spans
+    pub fn structural_eq(&self, other: &Element) -> bool {
+        match (self, other) {
+            (Element::Atom(a1), Element::Atom(a2)) => a1 == a2,
+            (Element::Comment(c1), Element::Comment(c2)) => c1 == c2,
+            (Element::Group(grp1, e1), Element::Group(grp2, e2)) => {
+                grp1 == grp2
+                    && e1.len() == e2.len()
+                    && e1
+                        .iter()
+                        .zip(e2.iter())
+                        .all(|(e1, e2)| e1.structural_eq(&e2.inner))
+            }
+            _ => false,
+        }
+    }
+
+    /// Compare two elements by structure and content, ignoring their spans, consistently
+    /// with [`Element::structural_eq`]
+    ///
+    /// The atoms are before the groups, which are before the comments. The atoms are ordered
+    /// as defined by [`Atom`], and the groups by kind then by their elements. This can be used
+    /// to sort the elements of a group into a canonical order:
+    ///
+    /// ```
+    /// use s_expr::{Element, Parser};
+    ///
+    /// let mut el = Parser::new("(b 2 (a) 0x1 a)").next().unwrap().unwrap();
+    /// if let Element::Group(_, children) = &mut el.inner {
+    ///     children.sort_by(|e1, e2| e1.structural_cmp(&e2.inner));
+    /// }
+    /// assert_eq!(s_expr::to_string(&el.inner), "(a b 0x1 2 (a))");
+    /// ```
+    pub fn structural_cmp(&self, other: &Element) -> Ordering {
+        match (self, other) {
+            (Element::Atom(a1), Element::Atom(a2)) => a1.cmp(a2),
+            (Element::Comment(c1), Element::Comment(c2)) => c1.cmp(c2),
+            (Element::Group(grp1, e1), Element::Group(grp2, e2)) => {
+                grp1.cmp(grp2).then_with(|| {
+                    e1.iter()
+                        .zip(e2.iter())
+                        .map(|(e1, e2)| e1.structural_cmp(&e2.inner))
+                        .find(|o| o.is_ne())
+                        .unwrap_or_else(|| e1.len().cmp(&e2.len()))
+                })
+            }
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+
+    // rank of the variant in the ordering of the elements
+    fn rank(&self) -> u8 {
+        match self {
+            Element::Atom(_) => 0,
+            Element::Group(_, _) => 1,
+            Element::Comment(_) => 2,
+        }
+    }
 }
 
 /// Spanned Element
 pub type SpannedElement<'a> = Spanned<Element<'a>>;
 
+/// Owned element, with the same variants as [`Element`] and the data held in `String`s, e.g. to
+/// keep a parsed tree after the source is dropped
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ElementOwned {
+    Group(GroupKind, Vec<SpannedElementOwned>),
+    Atom(AtomBuf),
+    Comment(String),
+}
+
+/// Spanned owned element
+pub type SpannedElementOwned = Spanned<ElementOwned>;
+
+impl<'a> SpannedElement<'a> {
+    /// Split an ident element on the first separator like [`Atom::ident_parts`], with the span
+    /// of each part in the source, e.g. to report an error on the name part only
+    ///
+    /// ```
+    /// use s_expr::{Parser, Span};
+    ///
+    /// let el = Parser::new("  module:name").next().unwrap().unwrap();
+    /// let (module, name) = el.ident_parts(':').unwrap();
+    /// assert_eq!((module.inner, module.span), ("module", Span::on_line(1, 2, 8)));
+    /// assert_eq!((name.inner, name.span), ("name", Span::on_line(1, 9, 13)));
+    /// ```
+    pub fn ident_parts(&self, sep: char) -> Option<(Spanned<&'a str>, Spanned<&'a str>)> {
+        let (first, second) = self.atom()?.ident_parts(sep)?;
+        let start = self.span.start;
+        let mut sep_pos = start;
+        sep_pos.advance_str(first);
+        let mut after_sep = sep_pos;
+        after_sep.advance(sep);
+        let first = Spanned::new(
+            Span {
+                start,
+                end: sep_pos,
+            },
+            first,
+        );
+        let second = Spanned::new(
+            Span {
+                start: after_sep,
+                end: self.span.end,
+            },
+            second,
+        );
+        Some((first, second))
+    }
+
+    /// Transform the span of the element and the spans of all the elements of its groups
+    ///
+    /// The spans are transformed in the order of the source, each group before its elements.
+    /// The elements with the [`Span::DUMMY`] span, which don't come from the source, keep it.
+    ///
+    /// ```
+    /// use s_expr::{Parser, Position};
+    ///
+    /// let document = "prefix (a\n b)";
+    /// let mut el = Parser::new(&document[7..]).next().unwrap().unwrap();
+    /// el.rewrite_spans(|span| span.offset_by(Position::new(1, 7, 7)));
+    /// let b = el.paren().unwrap()[1].span;
+    /// assert_eq!(&document[b.start.offset..b.end.offset], "b");
+    /// ```
+    pub fn rewrite_spans<F: FnMut(Span) -> Span>(&mut self, mut f: F) {
+        rewrite_spans(self, &mut f)
+    }
+}
+
+fn rewrite_spans(el: &mut SpannedElement, f: &mut dyn FnMut(Span) -> Span) {
+    if !el.span.is_dummy() {
+        el.span = f(el.span);
+    }
+    if let Element::Group(_, children) = &mut el.inner {
+        for child in children.iter_mut() {
+            rewrite_spans(child, f)
+        }
+    }
+}
+
+impl ElementOwned {
+    /// Get a view of the owned element as an [`Element`], which allocates the groups
+    pub fn to_element(&self) -> Element<'_> {
+        match self {
+            ElementOwned::Group(grp, elements) => Element::Group(
+                *grp,
+                elements
+                    .iter()
+                    .map(|el| Spanned {
+                        span: el.span,
+                        inner: el.to_element(),
+                    })
+                    .collect(),
+            ),
+            ElementOwned::Atom(atom) => Element::Atom(atom.as_atom()),
+            ElementOwned::Comment(comment) => Element::Comment(comment),
+        }
+    }
+}
+
+impl<'a> From<&Element<'a>> for ElementOwned {
+    fn from(el: &Element<'a>) -> Self {
+        match el {
+            Element::Group(grp, elements) => ElementOwned::Group(
+                *grp,
+                elements
+                    .iter()
+                    .map(|el| Spanned {
+                        span: el.span,
+                        inner: ElementOwned::from(&el.inner),
+                    })
+                    .collect(),
+            ),
+            Element::Atom(atom) => ElementOwned::Atom(AtomBuf::from(atom)),
+            Element::Comment(comment) => ElementOwned::Comment(comment.to_string()),
+        }
+    }
+}
+
+// a group in construction: its kind, the span of the opening token and the elements so far
+type OpenGroup<'a> = (GroupKind, Span, Vec<SpannedElement<'a>>);
+
 /// S-Expr Parser
 pub struct Parser<'a> {
-    tokenizer: Tokenizer<'a>,
+    pub(crate) tokenizer: Tokenizer<'a>,
 }
 
 /// Parser Error, which are either token error or some error related to group balancing
 /// like unterminated group, or mismatch of group
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ParserError {
     UnbalancedEmpty(Position, GroupKind),
     UnbalancedMismatch {
@@ -68,6 +291,96 @@ pub enum ParserError {
     TokenizerError(TokenError),
 }
 
+impl ParserError {
+    /// Render the error with the lines of the source where it occurs, see [`render_span`](crate::render_span)
+    ///
+    /// An unfinished group is reported at the end of the source.
+    ///
+    /// ```
+    /// use s_expr::Parser;
+    ///
+    /// let source = "(a\n  (b c]";
+    /// let err = Parser::new(source).next().unwrap_err();
+    /// assert_eq!(
+    ///     err.render(source),
+    ///     "line 2, columns 2-7: closing brackets `[]` while parentheses `()` are open, expected `)`
+    ///  --> 2:2
+    ///   |
+    /// 2 |   (b c]
+    ///   |   ^^^^^
+    /// "
+    /// );
+    /// ```
+    pub fn render(&self, source: &str) -> String {
+        let index = LineIndex::new(source);
+        let span = match self {
+            ParserError::UnbalancedEmpty(pos, _) => {
+                // the closing delimiter
+                let mut end = *pos;
+                end.advance_col();
+                Span { start: *pos, end }
+            }
+            ParserError::UnbalancedMismatch { span, .. } => *span,
+            ParserError::UnfinishedGroup(_) => {
+                let end = index.position_at(source.len());
+                Span { start: end, end }
+            }
+            ParserError::TokenizerError(e) => {
+                let start = match e {
+                    TokenError::DataError(_, offset) => index.position_at(*offset),
+                    TokenError::UnterminatedString(pos)
+                    | TokenError::UnterminatedBytes(pos)
+                    | TokenError::UnprocessedChar(pos, _)
+                    | TokenError::UnterminatedBytesChar(pos, _)
+                    | TokenError::InvalidEscape(pos, _) => *pos,
+                };
+                Span { start, end: start }
+            }
+        };
+        render_span(source, span, &self.to_string())
+    }
+}
+
+impl fmt::Display for ParserError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParserError::UnbalancedEmpty(pos, grp) => {
+                write!(
+                    f,
+                    "{}: closing {} without any open group",
+                    pos.display_human(),
+                    grp
+                )
+            }
+            ParserError::UnbalancedMismatch {
+                span,
+                expected,
+                got,
+            } => write!(
+                f,
+                "{}: closing {} while {} are open, expected `{}`",
+                span.display_human(),
+                got,
+                expected,
+                expected.close_char()
+            ),
+            ParserError::UnfinishedGroup(grp) => {
+                write!(f, "unfinished {}, missing `{}`", grp, grp.close_char())
+            }
+            ParserError::TokenizerError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ParserError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParserError::TokenizerError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
 impl From<TokenError> for ParserError {
     fn from(t: TokenError) -> ParserError {
         ParserError::TokenizerError(t)
@@ -87,8 +400,45 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Create a parser of the data embedded in a document at the base position, e.g. a code
+    /// block of a markdown file, the spans of the elements and the errors being in the document
+    ///
+    /// ```
+    /// use s_expr::{Parser, Position, Span};
+    ///
+    /// let document = "# Example\n\n    (a\n  b)\n";
+    /// let base = Position { line: 3, col: 4, offset: 15 };
+    /// let el = Parser::new_at(&document[15..], base).next().unwrap().unwrap();
+    /// let b = el.paren().unwrap()[1].span;
+    /// assert_eq!((b.start.line, b.start.col), (4, 2));
+    /// assert_eq!(&document[b.start.offset..b.end.offset], "b");
+    /// ```
+    pub fn new_at(data: &'a str, base: Position) -> Self {
+        Parser {
+            tokenizer: Tokenizer::new_at(data, base),
+        }
+    }
+
+    /// Create a parser of the data embedded in a document at the base position, with
+    /// an associated config
+    pub fn new_with_config_at(data: &'a str, cfg: TokenizerConfig, base: Position) -> Self {
+        Parser {
+            tokenizer: Tokenizer::new_with_config_at(data, cfg, base),
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Result<Option<SpannedElement<'a>>, ParserError> {
-        let mut out: Vec<(GroupKind, Span, Vec<SpannedElement<'a>>)> = vec![];
+        let mut out = vec![];
+        self.next_with_stack(&mut out)
+    }
+
+    // parse the next element, keeping the groups being built on the given stack,
+    // so that on error the caller can find at which depth the error happened
+    fn next_with_stack(
+        &mut self,
+        out: &mut Vec<OpenGroup<'a>>,
+    ) -> Result<Option<SpannedElement<'a>>, ParserError> {
         loop {
             match self.tokenizer.next()? {
                 None => match out.last() {
@@ -131,15 +481,15 @@ impl<'a> Parser<'a> {
                         Some((inner_grp, inner_start, inner_elements)) => {
                             if inner_grp != grp {
                                 return Err(ParserError::UnbalancedMismatch {
-                                    span: inner_start.extend(&tok.span),
+                                    span: inner_start.merge(&tok.span),
                                     expected: inner_grp,
                                     got: grp,
                                 });
                             }
-                            let inner = Spanned {
-                                span: inner_start.extend(&tok.span),
-                                inner: Element::Group(grp, inner_elements),
-                            };
+                            let inner = Spanned::new(
+                                inner_start.merge(&tok.span),
+                                Element::Group(grp, inner_elements),
+                            );
                             match out.last_mut() {
                                 None => return Ok(Some(inner)),
                                 Some((_, _, elements)) => {
@@ -152,4 +502,155 @@ impl<'a> Parser<'a> {
             }
         }
     }
+
+    // skip tokens until the group nesting get back to the top level, starting at
+    // the given depth. Any tokenizer errors found on the way are collected.
+    fn skip_to_toplevel(&mut self, mut depth: usize, errors: &mut Vec<ParserError>) {
+        while depth > 0 {
+            match self.tokenizer.next() {
+                Err(e) => push_error(errors, e.into()),
+                Ok(None) => return,
+                Ok(Some(tok)) => match tok.inner {
+                    Token::Left(_) => depth += 1,
+                    Token::Right(_) => depth -= 1,
+                    Token::Comment(_) | Token::Atom(_) => {}
+                },
+            }
+        }
+    }
+}
+
+// add an error to the list, unless the same error, at the same position, is already reported
+fn push_error(errors: &mut Vec<ParserError>, e: ParserError) {
+    if !errors.contains(&e) {
+        errors.push(e)
+    }
+}
+
+/// Parse all the elements of the data stream, reporting every error instead of stopping at the first one.
+///
+/// After an error, the parser resynchronizes by skipping to the next top-level form,
+/// so that one unbalanced group doesn't cascade into many follow-on errors.
+/// The errors are returned in source order, each error being reported once.
+pub fn parse_collecting_errors(
+    data: &str,
+    cfg: TokenizerConfig,
+) -> (Vec<SpannedElement<'_>>, Vec<ParserError>) {
+    let mut parser = Parser::new_with_config(data, cfg);
+    let mut elements = Vec::new();
+    let mut errors = Vec::new();
+    loop {
+        let mut out = Vec::new();
+        match parser.next_with_stack(&mut out) {
+            Ok(None) => break,
+            Ok(Some(el)) => elements.push(el),
+            Err(e) => {
+                let depth = match e {
+                    // the mismatched closing token didn't close the group
+                    ParserError::UnbalancedMismatch { .. } => out.len() + 1,
+                    // reached the end of stream, nothing left to skip
+                    ParserError::UnfinishedGroup(_) => 0,
+                    _ => out.len(),
+                };
+                push_error(&mut errors, e);
+                parser.skip_to_toplevel(depth, &mut errors);
+            }
+        }
+    }
+    (elements, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect(data: &str) -> (Vec<SpannedElement<'_>>, Vec<ParserError>) {
+        parse_collecting_errors(data, TokenizerConfig::default())
+    }
+
+    #[test]
+    fn render_errors() {
+        let render = |source: &str| Parser::new(source).next().unwrap_err().render(source);
+        assert_eq!(
+            render(" )"),
+            "line 1, column 1: closing parentheses `()` without any open group\n --> 1:1\n  |\n1 |  )\n  |  ^\n"
+        );
+        assert_eq!(
+            render("(a\n b"),
+            "unfinished parentheses `()`, missing `)`\n --> 2:2\n  |\n2 |  b\n  |   ^\n"
+        );
+        assert!(render("(a \"b").ends_with("\n --> 1:5\n  |\n1 | (a \"b\n  |      ^\n"));
+        assert_eq!(
+            render("(a\n \\)"),
+            "line 2, column 1: unexpected character '\\\\'\n --> 2:1\n  |\n2 |  \\)\n  |  ^\n"
+        );
+    }
+
+    #[test]
+    fn collect_no_errors() {
+        let (elements, errors) = collect("(a b) c [d]");
+        assert_eq!(elements.len(), 3);
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn collect_resync_after_mismatch() {
+        let (elements, errors) = collect("(a (b ] c) d) (ok 1) ) (x");
+        assert_eq!(elements.len(), 1);
+        assert_eq!(
+            elements[0].inner.paren().and_then(|e| e[0].atom()?.ident()),
+            Some("ok")
+        );
+        assert_eq!(errors.len(), 3);
+        assert!(matches!(
+            errors[0],
+            ParserError::UnbalancedMismatch {
+                expected: GroupKind::Paren,
+                got: GroupKind::Bracket,
+                ..
+            }
+        ));
+        match errors[1] {
+            ParserError::UnbalancedEmpty(pos, GroupKind::Paren) => {
+                assert_eq!((pos.line, pos.col, pos.offset), (1, 21, 21))
+            }
+            ref e => panic!("unexpected {:?}", e),
+        }
+        assert_eq!(errors[2], ParserError::UnfinishedGroup(GroupKind::Paren));
+    }
+
+    #[test]
+    fn collect_single_missing_paren() {
+        let (elements, errors) =
+            collect("(define x 1)\n(define y (f 1)\n(define z 2)\n(define w 3)\n");
+        assert_eq!(elements.len(), 1);
+        assert_eq!(errors, vec![ParserError::UnfinishedGroup(GroupKind::Paren)]);
+    }
+
+    #[test]
+    fn collect_tokenizer_errors() {
+        let (elements, errors) = collect("(a \\) (b) #12x# (c)");
+        assert_eq!(elements.len(), 3);
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(
+            errors[0],
+            ParserError::TokenizerError(TokenError::UnprocessedChar(_, '\\'))
+        ));
+        assert!(matches!(
+            errors[1],
+            ParserError::TokenizerError(TokenError::UnterminatedBytesChar(_, 'x'))
+        ));
+
+        // the errors at different positions are all reported, each once
+        let (_, errors) = collect("(a \\ \\) ] (b \\) ]");
+        let positions = errors
+            .iter()
+            .map(|e| match e {
+                ParserError::TokenizerError(TokenError::UnprocessedChar(pos, _))
+                | ParserError::UnbalancedEmpty(pos, _) => pos.col,
+                e => panic!("unexpected {:?}", e),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(positions, [3, 5, 8, 13, 16]);
+    }
 }
@@ -0,0 +1,731 @@
+//! Layout of the groups for the pretty printer
+//!
+//! The printer accumulates the content of a group as a tree of nodes, and once
+//! the outermost group is closed, the tree is laid out in one go, since the layout
+//! of a group depends on all its children.
+
+use super::data::GroupKind;
+use super::loc::{Position, Span};
+use super::printer::{write_delimiter, CommentStyle, IndentStyle, PrintConfig};
+use core::fmt;
+use core::ops::Range;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+/// Indentation of a broken group in pretty mode, selected by the ident at the head of the group
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IndentRule {
+    /// Number of elements after the head kept on the head line
+    pub head_args: usize,
+    /// Number of spaces to indent the other elements with, relative to the group
+    pub indent: usize,
+    /// Number of elements after the head line indented by `special_indent` instead, e.g. the then
+    /// branch of an `if`
+    pub special_args: usize,
+    /// Number of spaces to indent the special elements with, relative to the group
+    pub special_indent: usize,
+}
+
+impl IndentRule {
+    /// Create a rule keeping `head_args` elements on the head line and indenting the rest by `indent` spaces
+    pub fn new(head_args: usize, indent: usize) -> Self {
+        IndentRule {
+            head_args,
+            indent,
+            special_args: 0,
+            special_indent: indent,
+        }
+    }
+
+    /// Indent the `args` elements following the head line by `indent` spaces instead, e.g.
+    /// `IndentRule::new(1, 2).special(1, 4)` for `(if c\n    then\n  else)`
+    ///
+    /// The special indentation only applies to the indentation with spaces.
+    pub fn special(mut self, args: usize, indent: usize) -> Self {
+        self.special_args = args;
+        self.special_indent = indent;
+        self
+    }
+}
+
+/// Table of the indentation rules by head ident
+///
+/// The groups with an unknown head, or without an ident as head, are broken with the
+/// generic rule: the head on the opening line, and all the other elements on their own line.
+#[derive(Clone, Debug, Default)]
+pub struct IndentRules(BTreeMap<String, IndentRule>);
+
+impl IndentRules {
+    /// Create an empty table, where all the groups use the generic rule
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Table for some common lisp special forms: `if`, `let`, `define` and `lambda`
+    ///
+    /// The then branch of an `if` is indented by 4 spaces and the else branch by 2, the bindings
+    /// of a `let` and the arguments of a `define` or a `lambda` are kept on the head line.
+    ///
+    /// ```
+    /// use s_expr::{IndentRules, Parser, PrintConfig, Printer};
+    ///
+    /// let el = Parser::new("(if (< n 2) n (+ (fib (- n 1)) (fib (- n 2))))").next().unwrap().unwrap();
+    /// let cfg = PrintConfig::default().pretty(true).max_width(30).indent_rules(IndentRules::lisp());
+    /// let mut p = Printer::new_with_config(cfg);
+    /// p.element(&el.inner);
+    /// assert_eq!(p.to_string(), "(if (< n 2)\n    n\n  (+\n    (fib (- n 1))\n    (fib (- n 2))))");
+    /// ```
+    pub fn lisp() -> Self {
+        IndentRules::new()
+            .rule("if", IndentRule::new(1, 2).special(1, 4))
+            .rule("let", IndentRule::new(1, 2))
+            .rule("define", IndentRule::new(1, 2))
+            .rule("lambda", IndentRule::new(1, 2))
+    }
+
+    /// Set the rule of the groups with the ident `head` as first element
+    pub fn rule(mut self, head: &str, rule: IndentRule) -> Self {
+        self.0.insert(head.to_string(), rule);
+        self
+    }
+
+    /// Get the rule of the groups with the ident `head` as first element
+    pub fn get(&self, head: &str) -> Option<IndentRule> {
+        self.0.get(head).copied()
+    }
+}
+
+/// Layout of a group in pretty mode, forced by `Printer::flat`, `Printer::broken`, `Printer::fill`,
+/// `Printer::pairs` and `Printer::table`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum GroupLayout {
+    /// On one line if the group fits in the maximum width, otherwise broken over multiple lines
+    Auto,
+    /// Always on one line, with all the nested groups, unless it contains a comment
+    Flat,
+    /// Always broken over multiple lines
+    Broken,
+    /// On one line if the group fits, otherwise with as many elements per line as fit
+    Fill,
+    /// On one line if the group fits, otherwise with as many pairs of elements per line as fit
+    Pairs,
+    /// On one line if the group fits, otherwise with its groups of the same length on their own line,
+    /// with their elements aligned in columns
+    Table,
+}
+
+/// Content of a group being printed
+#[derive(Clone, Debug)]
+pub(crate) enum Node<K> {
+    /// Some text, e.g. an atom in its literal form
+    Text(String),
+    /// A line comment including its leader, which need to be followed by a newline
+    Comment(String, CommentStyle),
+    /// A forced line break, ending the line and adding the given number minus one of blank lines
+    Newline(usize),
+    /// A group with its layout and all its children
+    Group(GroupKind, GroupLayout, Vec<Node<K>>),
+    /// A node with its span recorded in the source map with the given key
+    Mapped(K, Box<Node<K>>),
+}
+
+impl<K> Node<K> {
+    // width of the node printed on a single line, or None if it contains a comment,
+    // since a comment cannot be followed by anything on the same line, or a broken group
+    fn flat_width(&self) -> Option<usize> {
+        match self {
+            Node::Text(text) => Some(advance_col(0, text, 1)),
+            Node::Comment(_, _) | Node::Newline(_) => None,
+            Node::Group(_, GroupLayout::Broken, children) if children.len() > 1 => None,
+            Node::Mapped(_, node) => node.flat_width(),
+            Node::Group(_, _, children) => {
+                let mut width = 2 + children.len().saturating_sub(1);
+                for child in children {
+                    width += child.flat_width()?;
+                }
+                Some(width)
+            }
+        }
+    }
+
+    // check if the node can be printed on a single line, whatever the layout of its groups
+    fn can_be_flat(&self) -> bool {
+        match self {
+            Node::Text(_) => true,
+            Node::Comment(_, _) | Node::Newline(_) => false,
+            Node::Group(_, _, children) => children.iter().all(Node::can_be_flat),
+            Node::Mapped(_, node) => node.can_be_flat(),
+        }
+    }
+
+    // the node itself, or the mapped node
+    pub(crate) fn inner(&self) -> &Node<K> {
+        match self {
+            Node::Mapped(_, node) => node.inner(),
+            node => node,
+        }
+    }
+}
+
+// check if the node is followed by a trailing comment, starting a run of aligned comments
+fn has_trailing_comment<K>(children: &[Node<K>], i: usize) -> bool {
+    !matches!(
+        children.get(i).map(Node::inner),
+        None | Some(Node::Comment(_, _) | Node::Newline(_))
+    ) && matches!(
+        children.get(i + 1).map(Node::inner),
+        Some(Node::Comment(_, CommentStyle::EndOfLine))
+    )
+}
+
+/// Column after a text without newline printed from the given column, the tabs moving to the next tab stop
+pub(crate) fn advance_col(col: usize, s: &str, tab_width: usize) -> usize {
+    let tab_width = tab_width.max(1);
+    let mut col = col;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\t' => col = (col / tab_width + 1) * tab_width,
+            // the ANSI escape sequences of the colors take no column
+            '\x1b' if chars.as_str().starts_with('[') => {
+                chars.next();
+                chars.by_ref().find(|c| ('@'..='~').contains(c));
+            }
+            _ => col += 1,
+        }
+    }
+    col
+}
+
+// the text without the ANSI escape sequences of the colors
+fn uncolored(text: &str) -> Cow<'_, str> {
+    if !text.contains('\x1b') {
+        return Cow::Borrowed(text);
+    }
+    let mut plain = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' && chars.as_str().starts_with('[') {
+            chars.next();
+            chars.by_ref().find(|c| ('@'..='~').contains(c));
+        } else {
+            plain.push(c);
+        }
+    }
+    Cow::Owned(plain)
+}
+
+/// Column of the trailing comments of a run of elements ending at the given columns
+pub(crate) fn trailing_comment_column<I: Iterator<Item = usize>>(
+    cfg: &PrintConfig,
+    ends: I,
+) -> usize {
+    let column = ends.max().unwrap_or(0) + 1;
+    column.min(cfg.trailing_comment_max_column)
+}
+
+/// Number of spaces before a trailing comment aligned on a column, after an element ending at `end`
+pub(crate) fn trailing_comment_padding(column: usize, end: usize) -> usize {
+    column.saturating_sub(end).max(1)
+}
+
+struct Layout<'a, W, K> {
+    cfg: &'a PrintConfig,
+    out: &'a mut W,
+    line: usize,
+    col: usize,
+    // bytes written since the start of the output
+    offset: usize,
+    // spans of the mapped nodes, by key
+    source_map: &'a mut Vec<(K, Span)>,
+    // inside a flat group, forcing all the nested groups flat
+    flat: bool,
+    // number of tabs indenting the current line, with the tabs indent style
+    level: usize,
+}
+
+/// Lay out a node starting at the given position, recording the spans of the mapped nodes
+pub(crate) fn layout<W: fmt::Write, K: Clone>(
+    cfg: &PrintConfig,
+    node: &Node<K>,
+    out: &mut W,
+    start: Position,
+    source_map: &mut Vec<(K, Span)>,
+) -> fmt::Result {
+    let mut layout = Layout {
+        cfg,
+        out,
+        line: start.line,
+        col: start.col,
+        offset: start.offset,
+        source_map,
+        flat: false,
+        level: 0,
+    };
+    layout.node(node)
+}
+
+impl<'a, W: fmt::Write, K: Clone> Layout<'a, W, K> {
+    fn position(&self) -> Position {
+        Position {
+            line: self.line,
+            col: self.col,
+            offset: self.offset,
+        }
+    }
+
+    // write to the output, without moving the line and the column
+    fn emit(&mut self, s: &str) -> fmt::Result {
+        self.offset += s.len();
+        self.out.write_str(s)
+    }
+
+    // write a text, which may contain newlines when strings are printed without escaping them
+    fn write(&mut self, s: &str) -> fmt::Result {
+        match s.rfind('\n') {
+            None => self.col = advance_col(self.col, s, self.cfg.tab_width),
+            Some(i) => {
+                self.line += s.matches('\n').count();
+                self.col = advance_col(0, &s[i + 1..], self.cfg.tab_width);
+            }
+        }
+        self.emit(s)
+    }
+
+    fn write_char(&mut self, c: char) -> fmt::Result {
+        self.col += 1;
+        self.emit(c.encode_utf8(&mut [0; 4]))
+    }
+
+    fn write_delimiter(&mut self, c: char) -> fmt::Result {
+        let mut delimiter = String::new();
+        write_delimiter(&mut delimiter, self.cfg, c)?;
+        self.write(&delimiter)
+    }
+
+    // start a new line with the line prefix and the tabs of the current level,
+    // and indent it up to the given column with spaces
+    fn newline(&mut self, indent: usize) -> fmt::Result {
+        self.emit(self.cfg.line_ending.as_str())?;
+        self.line += 1;
+        self.col = 0;
+        let cfg = self.cfg;
+        self.write(&cfg.line_prefix)?;
+        for _ in 0..self.level {
+            self.write("\t")?;
+        }
+        while self.col < indent {
+            self.write_char(' ')?;
+        }
+        Ok(())
+    }
+
+    fn node(&mut self, node: &Node<K>) -> fmt::Result {
+        self.node_followed_by(node, 0)
+    }
+
+    // lay out a node that will be followed on the same line by `trailing` characters
+    // (the closing delimiters of the parent groups), which need to fit too
+    fn node_followed_by(&mut self, node: &Node<K>, trailing: usize) -> fmt::Result {
+        match node {
+            Node::Text(text) | Node::Comment(text, _) => self.write(text),
+            // the last newline is written by the separator of the next element
+            Node::Newline(count) => {
+                for _ in 1..*count {
+                    self.emit(self.cfg.line_ending.as_str())?;
+                    self.line += 1;
+                }
+                Ok(())
+            }
+            Node::Mapped(key, node) => {
+                let start = self.position();
+                self.node_followed_by(node, trailing)?;
+                let end = self.position();
+                self.source_map.push((key.clone(), Span { start, end }));
+                Ok(())
+            }
+            Node::Group(grp, group_layout, children) => {
+                let group_col = self.col;
+                let fits = match group_layout {
+                    _ if self.flat => node.can_be_flat(),
+                    GroupLayout::Flat => node.can_be_flat(),
+                    GroupLayout::Broken => false,
+                    GroupLayout::Auto
+                    | GroupLayout::Fill
+                    | GroupLayout::Pairs
+                    | GroupLayout::Table => node
+                        .flat_width()
+                        .is_some_and(|w| group_col + w + trailing <= self.cfg.max_width),
+                };
+                let was_flat = self.flat;
+                self.flat = was_flat || *group_layout == GroupLayout::Flat;
+                let rule = match children.first().map(Node::inner) {
+                    Some(Node::Text(head)) => self.cfg.indent_rules.get(&uncolored(head)),
+                    _ => None,
+                }
+                .unwrap_or(IndentRule::new(
+                    0,
+                    match self.cfg.indent {
+                        IndentStyle::Spaces(spaces) => spaces,
+                        IndentStyle::Tabs => 0,
+                    },
+                ));
+                // the groups starting with an ident are calls rather than lists
+                let head_ident = match children.first().map(Node::inner) {
+                    Some(Node::Text(head)) => self.cfg.tokenizer.is_ident(&uncolored(head)),
+                    _ => false,
+                };
+                // the rows are aligned on the first row, after the opening delimiter
+                let table = match group_layout {
+                    _ if fits => None,
+                    GroupLayout::Table => self.table_columns(children, group_col + 1, trailing),
+                    GroupLayout::Auto if self.cfg.tabular_groups => {
+                        self.table_columns(children, group_col + 1, trailing)
+                    }
+                    _ => None,
+                };
+                let pairs = !fits
+                    && table.is_none()
+                    && match group_layout {
+                        GroupLayout::Pairs => true,
+                        GroupLayout::Auto => self.cfg.pair_braces && *grp == GroupKind::Brace,
+                        _ => false,
+                    };
+                let fill = !fits
+                    && !pairs
+                    && match group_layout {
+                        GroupLayout::Fill => true,
+                        GroupLayout::Auto => {
+                            self.cfg.fill_atoms
+                                && !head_ident
+                                && children.iter().all(|c| matches!(c.inner(), Node::Text(_)))
+                        }
+                        GroupLayout::Flat
+                        | GroupLayout::Broken
+                        | GroupLayout::Pairs
+                        | GroupLayout::Table => false,
+                    };
+                self.write_delimiter(grp.open_char())?;
+                if let Some(widths) = table {
+                    self.table(children, &widths)?;
+                } else if pairs {
+                    self.pairs(children, trailing)?;
+                } else if fill {
+                    self.fill(children, trailing)?;
+                } else {
+                    self.children(children, group_col, fits, rule, trailing)?;
+                }
+                // a comment would swallow the closing delimiter
+                if let Some(Node::Comment(_, _) | Node::Newline(_)) =
+                    children.last().map(Node::inner)
+                {
+                    self.newline(group_col)?;
+                }
+                self.flat = was_flat;
+                self.write_delimiter(grp.close_char())
+            }
+        }
+    }
+
+    // lay out the run of elements followed by trailing comments starting at `start`, the first
+    // element at the current column and the others on their own line, returning the text and
+    // the end column of each element
+    fn trailing_run(
+        &mut self,
+        children: &[Node<K>],
+        start: usize,
+        indent: usize,
+    ) -> Result<Vec<(String, Position, Range<usize>)>, fmt::Error> {
+        let mut rendered = Vec::new();
+        let mut line = self.line;
+        let mut col = self.col;
+        let mut i = start;
+        while has_trailing_comment(children, i) {
+            let mut text = String::new();
+            let mapped = self.source_map.len();
+            let mut layout = Layout {
+                cfg: self.cfg,
+                out: &mut text,
+                line,
+                col,
+                offset: 0,
+                source_map: &mut *self.source_map,
+                flat: self.flat,
+                level: self.level,
+            };
+            layout.node(&children[i])?;
+            let end = layout.position();
+            rendered.push((text, end, mapped..self.source_map.len()));
+            // the next element is on its own line, after the trailing comment
+            line = end.line + 1;
+            col = indent;
+            i += 2;
+        }
+        Ok(rendered)
+    }
+
+    // lay out the children of a group, on one line if the group fits or each on their own line
+    fn children(
+        &mut self,
+        children: &[Node<K>],
+        group_col: usize,
+        fits: bool,
+        rule: IndentRule,
+        trailing: usize,
+    ) -> fmt::Result {
+        let last = children.len().saturating_sub(1);
+        // the elements on their own line are indented by one more tab than the line of the group
+        let saved_level = self.level;
+        let (indent, special_indent, level) = match self.cfg.indent {
+            IndentStyle::Spaces(_) => (
+                group_col + rule.indent,
+                group_col + rule.special_indent,
+                saved_level,
+            ),
+            IndentStyle::Tabs => {
+                let prefix_col = advance_col(0, &self.cfg.line_prefix, self.cfg.tab_width);
+                let tabs = "\t".repeat(saved_level + 1);
+                let indent = advance_col(prefix_col, &tabs, self.cfg.tab_width);
+                (indent, indent, saved_level + 1)
+            }
+        };
+        // the special elements follow the elements of the head line, the comments being indented
+        // like the element following them
+        let special = rule.head_args + 1..=rule.head_args + rule.special_args;
+        let mut arg = 0;
+        // elements followed by aligned trailing comments, laid out in advance up to `run_end`
+        let mut run = Vec::new().into_iter();
+        let mut run_end = 0;
+        let mut run_column = 0;
+        for (i, child) in children.iter().enumerate() {
+            let indent = if special.contains(&arg) {
+                special_indent
+            } else {
+                indent
+            };
+            // a comment or a forced line break ends the line of the previous element
+            let after_break = i > 0
+                && matches!(
+                    children[i - 1].inner(),
+                    Node::Comment(_, _) | Node::Newline(_)
+                );
+            match child.inner() {
+                // the line is ended by the separator of the next element
+                Node::Newline(_) => {}
+                Node::Comment(_, CommentStyle::EndOfLine) if i == 0 => {}
+                Node::Comment(_, CommentStyle::EndOfLine) if i < run_end => {
+                    for _ in 0..trailing_comment_padding(run_column, self.col) {
+                        self.write_char(' ')?;
+                    }
+                }
+                Node::Comment(_, CommentStyle::EndOfLine) if !after_break => {
+                    self.write_char(' ')?
+                }
+                Node::Comment(_, _) => self.indented_newline(indent, level)?,
+                _ if i == 0 => {}
+                _ if after_break => self.indented_newline(indent, level)?,
+                _ if fits || i <= rule.head_args => self.write_char(' ')?,
+                _ => self.indented_newline(indent, level)?,
+            }
+            if self.cfg.align_trailing_comments && i >= run_end && has_trailing_comment(children, i)
+            {
+                let rendered = self.trailing_run(children, i, indent)?;
+                run_end = i + 2 * rendered.len();
+                run_column =
+                    trailing_comment_column(self.cfg, rendered.iter().map(|(_, end, _)| end.col));
+                run = rendered.into_iter();
+            }
+            match child.inner() {
+                Node::Comment(_, _) | Node::Newline(_) => self.node(child)?,
+                _ if i < run_end => {
+                    let (text, end, mapped) = run.next().ok_or(fmt::Error)?;
+                    // the offsets of the element laid out in advance are relative to its start
+                    for (_, span) in self.source_map[mapped].iter_mut() {
+                        span.start.offset += self.offset;
+                        span.end.offset += self.offset;
+                    }
+                    self.emit(&text)?;
+                    self.line = end.line;
+                    self.col = end.col;
+                }
+                _ => {
+                    let child_trailing = if i == last { trailing + 1 } else { 0 };
+                    self.node_followed_by(child, child_trailing)?;
+                }
+            }
+            if !matches!(child.inner(), Node::Comment(_, _) | Node::Newline(_)) {
+                arg += 1;
+            }
+        }
+        self.level = saved_level;
+        Ok(())
+    }
+
+    fn indented_newline(&mut self, indent: usize, level: usize) -> fmt::Result {
+        self.level = level;
+        self.newline(indent)
+    }
+
+    // lay out the children of a group with as many children per line as fit, aligned on the first child
+    fn fill(&mut self, children: &[Node<K>], trailing: usize) -> fmt::Result {
+        let indent = self.col;
+        let last = children.len().saturating_sub(1);
+        for (i, child) in children.iter().enumerate() {
+            let after_break = i > 0
+                && matches!(
+                    children[i - 1].inner(),
+                    Node::Comment(_, _) | Node::Newline(_)
+                );
+            let child_trailing = if i == last { trailing + 1 } else { 0 };
+            match child.inner() {
+                Node::Newline(_) => {}
+                Node::Comment(_, CommentStyle::EndOfLine) if i == 0 => {}
+                Node::Comment(_, CommentStyle::EndOfLine) if !after_break => {
+                    self.write_char(' ')?
+                }
+                Node::Comment(_, _) => self.newline(indent)?,
+                _ if i == 0 => {}
+                _ if after_break => self.newline(indent)?,
+                _ => {
+                    let fits = child
+                        .flat_width()
+                        .is_some_and(|w| self.col + 1 + w + child_trailing <= self.cfg.max_width);
+                    if fits {
+                        self.write_char(' ')?
+                    } else {
+                        self.newline(indent)?
+                    }
+                }
+            }
+            self.node_followed_by(child, child_trailing)?;
+        }
+        Ok(())
+    }
+
+    // widths of the columns of the children of a group laid out as a table starting at `indent`, if
+    // the children are groups of the same length, without comments, and every row fits on its line
+    fn table_columns(
+        &self,
+        children: &[Node<K>],
+        indent: usize,
+        trailing: usize,
+    ) -> Option<Vec<usize>> {
+        let mut widths: Vec<usize> = Vec::new();
+        for child in children {
+            match child.inner() {
+                Node::Group(_, _, cells) if widths.is_empty() || cells.len() == widths.len() => {
+                    widths.resize(cells.len(), 0);
+                    for (width, cell) in widths.iter_mut().zip(cells) {
+                        *width = (*width).max(cell.flat_width()?);
+                    }
+                }
+                _ => return None,
+            }
+        }
+        // a table of one column doesn't need any alignment
+        if widths.len() < 2 {
+            return None;
+        }
+        let row_width = widths.iter().sum::<usize>() + widths.len() - 1 + 2;
+        // the last row is followed by the closing delimiter of the group
+        let closing = trailing + 1;
+        (indent + row_width + closing <= self.cfg.max_width).then_some(widths)
+    }
+
+    // lay out the rows of a table each on their own line, padding the cells to the widths of the columns
+    fn table(&mut self, rows: &[Node<K>], widths: &[usize]) -> fmt::Result {
+        let indent = self.col;
+        let was_flat = self.flat;
+        self.flat = true;
+        for (i, row) in rows.iter().enumerate() {
+            if i > 0 {
+                self.newline(indent)?;
+            }
+            self.table_row(row, widths)?;
+        }
+        self.flat = was_flat;
+        Ok(())
+    }
+
+    fn table_row(&mut self, row: &Node<K>, widths: &[usize]) -> fmt::Result {
+        match row {
+            Node::Mapped(key, row) => {
+                let start = self.position();
+                self.table_row(row, widths)?;
+                let end = self.position();
+                self.source_map.push((key.clone(), Span { start, end }));
+                Ok(())
+            }
+            Node::Group(grp, _, cells) => {
+                self.write_delimiter(grp.open_char())?;
+                let last = cells.len().saturating_sub(1);
+                for (j, (cell, width)) in cells.iter().zip(widths).enumerate() {
+                    if j > 0 {
+                        self.write_char(' ')?;
+                    }
+                    let start = self.col;
+                    self.node(cell)?;
+                    while j < last && self.col < start + width {
+                        self.write_char(' ')?;
+                    }
+                }
+                self.write_delimiter(grp.close_char())
+            }
+            _ => self.node(row),
+        }
+    }
+
+    // lay out the children of a group like `fill`, keeping each pair of elements on the same line,
+    // e.g. the keys and values of a map. The comments don't count in the pairs
+    fn pairs(&mut self, children: &[Node<K>], trailing: usize) -> fmt::Result {
+        let indent = self.col;
+        let last = children.len().saturating_sub(1);
+        let is_element =
+            |node: &Node<K>| !matches!(node.inner(), Node::Comment(_, _) | Node::Newline(_));
+        let mut key = true;
+        for (i, child) in children.iter().enumerate() {
+            let after_break = i > 0
+                && matches!(
+                    children[i - 1].inner(),
+                    Node::Comment(_, _) | Node::Newline(_)
+                );
+            let child_trailing = if i == last { trailing + 1 } else { 0 };
+            match child.inner() {
+                Node::Newline(_) => {}
+                Node::Comment(_, CommentStyle::EndOfLine) if i == 0 => {}
+                Node::Comment(_, CommentStyle::EndOfLine) if !after_break => {
+                    self.write_char(' ')?
+                }
+                Node::Comment(_, _) => self.newline(indent)?,
+                _ if i == 0 => {}
+                _ if after_break => self.newline(indent)?,
+                // the value always follows its key
+                _ if !key => self.write_char(' ')?,
+                _ => {
+                    // the width of the pair, with the value only if it directly follows the key
+                    let width = match children.get(i + 1) {
+                        Some(value) if is_element(value) => {
+                            let value_trailing = if i + 1 == last { trailing + 1 } else { 0 };
+                            child
+                                .flat_width()
+                                .zip(value.flat_width())
+                                .map(|(k, v)| k + 1 + v + value_trailing)
+                        }
+                        _ => child.flat_width().map(|w| w + child_trailing),
+                    };
+                    let fits = width.is_some_and(|w| self.col + 1 + w <= self.cfg.max_width);
+                    if fits {
+                        self.write_char(' ')?
+                    } else {
+                        self.newline(indent)?
+                    }
+                }
+            }
+            if is_element(child) {
+                key = !key;
+            }
+            self.node_followed_by(child, child_trailing)?;
+        }
+        Ok(())
+    }
+}
@@ -1,70 +1,2104 @@
-use super::data::GroupKind;
+use super::data::{self, ABytes, ABytesBuf, ANum, ANumBase, Atom, GroupKind};
+use super::escape;
+use super::loc::{Position, Span};
+use super::parser::Element;
+use super::pretty::{self, advance_col, GroupLayout, IndentRules, Node};
+use super::tokenizer::TokenizerConfig;
+use core::fmt::{self, Write};
+use std::io;
+
+/// Config for the printer
+#[derive(Debug, Clone)]
+pub struct PrintConfig {
+    /// Print the groups on multiple lines with indentation. Default is set to false
+    pub(crate) pretty: bool,
+    /// Indentation of the nested groups in pretty mode. Default is set to 2 spaces
+    pub(crate) indent: IndentStyle,
+    /// Maximum width of a line in pretty mode before breaking groups. Default is set to 80
+    pub(crate) max_width: usize,
+    /// Escape newlines, tabs and carriage returns in strings. Default is set to true
+    pub(crate) escape_whitespace: bool,
+    /// Escape used for the other control characters in strings. Default is set to hexadecimal
+    pub(crate) control_escape: ControlEscape,
+    /// Escape the non-ascii characters in strings. Default is set to false
+    pub(crate) ascii_strings: bool,
+    /// Print bytes with uppercase hexadecimal digits. Default is set to false
+    pub(crate) bytes_uppercase: bool,
+    /// Number of bytes between `_` separators in bytes, or 0 for no separators. Default is set to 0
+    pub(crate) bytes_group: usize,
+    /// Separate the digits of the numbers with `_`. Default is set to false
+    pub(crate) number_separators: bool,
+    /// Indentation rules of the broken groups by head ident in pretty mode. Default is set to an empty table
+    pub(crate) indent_rules: IndentRules,
+    /// Check of the ident atoms which wouldn't parse back as the same ident. Default is set to unchecked
+    pub(crate) ident_check: IdentCheck,
+    /// Config of the tokenizer reading the output back, which the idents are checked against. Default is set to the default config
+    pub(crate) tokenizer: TokenizerConfig,
+    /// Align the trailing comments of consecutive elements in pretty mode. Default is set to false
+    pub(crate) align_trailing_comments: bool,
+    /// Maximum column of the aligned trailing comments. Default is set to 40
+    pub(crate) trailing_comment_max_column: usize,
+    /// Fill the lines of the broken groups containing only atoms, except an ident head, in pretty mode. Default is set to true
+    pub(crate) fill_atoms: bool,
+    /// Keep the keys and values of the brace groups together when breaking them in pretty mode. Default is set to false
+    pub(crate) pair_braces: bool,
+    /// Align the groups of the same length of the broken groups in columns in pretty mode. Default is set to false
+    pub(crate) tabular_groups: bool,
+    /// Text between the delimiters of the empty groups, by group kind. Default is set to no text
+    pub(crate) empty_groups: [String; 3],
+    /// Prefix printed at the start of every line. Default is set to no prefix
+    pub(crate) line_prefix: String,
+    /// Print the line prefix on the first line too. Default is set to true
+    pub(crate) prefix_first_line: bool,
+    /// Number of columns of a tab when measuring the width of a line. Default is set to 8
+    pub(crate) tab_width: usize,
+    /// Line ending ending the lines printed by the printer. Default is set to LF
+    pub(crate) line_ending: LineEnding,
+    /// End the output with exactly one newline. Default is set to false
+    pub(crate) ensure_trailing_newline: bool,
+    /// ANSI colors of the tokens. Default is set to no colors
+    #[cfg(feature = "color")]
+    pub(crate) color: Option<ColorScheme>,
+}
+
+impl Default for PrintConfig {
+    fn default() -> Self {
+        PrintConfig {
+            pretty: false,
+            indent: IndentStyle::Spaces(2),
+            max_width: 80,
+            escape_whitespace: true,
+            control_escape: ControlEscape::Hex,
+            ascii_strings: false,
+            bytes_uppercase: false,
+            bytes_group: 0,
+            number_separators: false,
+            indent_rules: IndentRules::new(),
+            ident_check: IdentCheck::Unchecked,
+            tokenizer: TokenizerConfig::default(),
+            align_trailing_comments: false,
+            trailing_comment_max_column: 40,
+            fill_atoms: true,
+            pair_braces: false,
+            tabular_groups: false,
+            empty_groups: Default::default(),
+            line_prefix: String::new(),
+            prefix_first_line: true,
+            tab_width: 8,
+            line_ending: LineEnding::Lf,
+            ensure_trailing_newline: false,
+            #[cfg(feature = "color")]
+            color: None,
+        }
+    }
+}
+
+impl PrintConfig {
+    /// Pretty print the groups on multiple lines, or print everything on one line
+    ///
+    /// In pretty mode, each top level element is printed on its own line, and groups that don't fit
+    /// in the maximum width are broken over multiple lines with their elements indented. Closing
+    /// delimiters follow the last element of the group.
+    pub fn pretty(mut self, enabled: bool) -> Self {
+        self.pretty = enabled;
+        self
+    }
+
+    /// Set the number of spaces used to indent the elements of a broken group in pretty mode
+    pub fn indent(mut self, spaces: usize) -> Self {
+        self.indent = IndentStyle::Spaces(spaces);
+        self
+    }
+
+    /// Set the indentation of the elements of a broken group in pretty mode, with spaces or tabs
+    ///
+    /// With tabs, the elements of a broken group are indented by one more tab than the line of
+    /// the group, instead of relatively to the column of the group, and the indentation of the
+    /// [`IndentRule`](crate::IndentRule)s is not used. The alignments within a line, e.g. of the
+    /// filled lines or of the trailing comments, are still made with spaces after the tabs.
+    ///
+    /// ```
+    /// use s_expr::{IndentStyle, Parser, PrintConfig, Printer};
+    ///
+    /// let cfg = PrintConfig::default().pretty(true).max_width(30).indent_style(IndentStyle::Tabs);
+    /// let el = Parser::new("(define (f x) (if (zero? x) 1 (* x 2)))").next().unwrap().unwrap();
+    /// let mut p = Printer::new_with_config(cfg);
+    /// p.element(&el.inner);
+    /// assert_eq!(p.to_string(), "(define\n\t(f x)\n\t(if\n\t\t(zero? x)\n\t\t1\n\t\t(* x 2)))");
+    /// ```
+    pub fn indent_style(mut self, style: IndentStyle) -> Self {
+        self.indent = style;
+        self
+    }
+
+    /// Set the maximum width of a line in pretty mode
+    ///
+    /// A group that would exceed the width when printed on one line is broken over multiple lines.
+    /// Atoms are never split, so a line with a long string or bytes atom may still exceed the width.
+    pub fn max_width(mut self, width: usize) -> Self {
+        self.max_width = width;
+        self
+    }
+
+    /// Escape the newlines, tabs and carriage returns as `\n`, `\t` and `\r` in the strings printed
+    /// by [`Printer::string`], or print them as is
+    pub fn escape_whitespace(mut self, enabled: bool) -> Self {
+        self.escape_whitespace = enabled;
+        self
+    }
+
+    /// Set the escape used for the control characters in the strings printed by [`Printer::string`]
+    pub fn control_escape(mut self, escape: ControlEscape) -> Self {
+        self.control_escape = escape;
+        self
+    }
+
+    /// Escape the non-ascii characters as `\u{N..}` in the strings printed by [`Printer::string`], so that
+    /// the strings are pure ascii, or print them as UTF-8
+    ///
+    /// ```
+    /// use s_expr::{PrintConfig, Printer};
+    ///
+    /// let mut p = Printer::new_with_config(PrintConfig::default().ascii_strings(true));
+    /// p.string("café 😀");
+    /// assert_eq!(p.to_string(), r#""caf\u{e9} \u{1f600}""#);
+    /// ```
+    pub fn ascii_strings(mut self, enabled: bool) -> Self {
+        self.ascii_strings = enabled;
+        self
+    }
+
+    /// Print the bytes of [`Printer::bytes`] with uppercase or lowercase hexadecimal digits
+    pub fn bytes_uppercase(mut self, enabled: bool) -> Self {
+        self.bytes_uppercase = enabled;
+        self
+    }
+
+    /// Separate every N bytes of [`Printer::bytes`] with a `_`, or don't separate them if 0
+    pub fn bytes_group(mut self, bytes: usize) -> Self {
+        self.bytes_group = bytes;
+        self
+    }
+
+    /// Separate the digits of the numbers printed by [`Printer::uint`] with `_`, every 3 digits for decimal
+    /// numbers and every 4 digits for hexadecimal and binary numbers
+    pub fn number_separators(mut self, enabled: bool) -> Self {
+        self.number_separators = enabled;
+        self
+    }
+
+    /// Set the indentation rules of the broken groups in pretty mode, looked up by the ident at the
+    /// head of the group, e.g. [`IndentRules::lisp`] to keep the condition of an `if` on its line
+    pub fn indent_rules(mut self, rules: IndentRules) -> Self {
+        self.indent_rules = rules;
+        self
+    }
+
+    /// Set the check of the ident atoms printed by [`Printer::atom`] and [`Printer::element`]
+    ///
+    /// An ident constructed programmatically may contain spaces, quotes or delimiters, and printing
+    /// it as is would not parse back to the same element.
+    pub fn ident_check(mut self, check: IdentCheck) -> Self {
+        self.ident_check = check;
+        self
+    }
+
+    /// Set the config of the tokenizer reading the output back
+    ///
+    /// The idents are checked against this config, and the idents at the head of the groups are
+    /// recognized with it in pretty mode, e.g. `#ff#` is an ident to a tokenizer without bytes.
+    ///
+    /// ```
+    /// use s_expr::{Atom, IdentCheck, PrintConfig, Printer, TokenizerConfig};
+    ///
+    /// let cfg = PrintConfig::default()
+    ///     .ident_check(IdentCheck::Reject)
+    ///     .tokenizer_config(TokenizerConfig::default().support_bytes(false));
+    /// let mut p = Printer::new_with_config(cfg);
+    /// assert!(p.try_atom(&Atom::Ident("#ff#")).is_ok());
+    /// assert_eq!(p.to_string(), "#ff#");
+    /// ```
+    pub fn tokenizer_config(mut self, cfg: TokenizerConfig) -> Self {
+        self.tokenizer = cfg;
+        self
+    }
+
+    /// Align the trailing comments of consecutive elements in pretty mode, padding with spaces so
+    /// that the comments start on the same column
+    ///
+    /// The elements of a broken group each followed by a comment of style [`CommentStyle::EndOfLine`]
+    /// have their comments aligned, and so do the consecutive top level elements formatted by
+    /// [`format_str`](crate::format_str).
+    pub fn align_trailing_comments(mut self, enabled: bool) -> Self {
+        self.align_trailing_comments = enabled;
+        self
+    }
+
+    /// Set the maximum column of the aligned trailing comments. The comments of elements ending
+    /// after this column are only separated by one space
+    pub fn trailing_comment_max_column(mut self, column: usize) -> Self {
+        self.trailing_comment_max_column = column;
+        self
+    }
+
+    /// Fill the lines of the groups containing only atoms in pretty mode when they don't fit on one line,
+    /// or put each atom on its own line
+    ///
+    /// When filling, the atoms are packed on each line up to the maximum width, and the lines are aligned
+    /// on the first atom. The groups starting with an ident, like function calls, are never filled
+    /// automatically, but any group can be filled with [`Printer::fill`].
+    pub fn fill_atoms(mut self, enabled: bool) -> Self {
+        self.fill_atoms = enabled;
+        self
+    }
+
+    /// Lay out the brace groups as alternating keys and values in pretty mode, like [`Printer::pairs`]
+    ///
+    /// ```
+    /// use s_expr::{Parser, PrintConfig, Printer};
+    ///
+    /// let mut p = Printer::new_with_config(PrintConfig::default().pretty(true).max_width(24).pair_braces(true));
+    /// let el = Parser::new(r#"{name "x" port 80 tls true}"#).next().unwrap().unwrap();
+    /// p.element(&el.inner);
+    /// assert_eq!(p.to_string(), "{name \"x\" port 80\n tls true}");
+    /// ```
+    pub fn pair_braces(mut self, enabled: bool) -> Self {
+        self.pair_braces = enabled;
+        self
+    }
+
+    /// Lay out the broken groups containing only groups of the same length as tables in pretty mode,
+    /// like [`Printer::table`]
+    ///
+    /// ```
+    /// use s_expr::{Parser, PrintConfig, Printer};
+    ///
+    /// let mut p = Printer::new_with_config(PrintConfig::default().pretty(true).max_width(20).tabular_groups(true));
+    /// let el = Parser::new(r#"((a 1 "x") (bb 22 "yy") (c 3 "z"))"#).next().unwrap().unwrap();
+    /// p.element(&el.inner);
+    /// assert_eq!(p.to_string(), "((a  1  \"x\")\n (bb 22 \"yy\")\n (c  3  \"z\"))");
+    /// ```
+    pub fn tabular_groups(mut self, enabled: bool) -> Self {
+        self.tabular_groups = enabled;
+        self
+    }
+
+    /// Set the text printed between the delimiters of the empty groups of the given kind, e.g. `" "`
+    /// to print `( )`
+    ///
+    /// An empty group is never broken over multiple lines in pretty mode, and is measured like an atom.
+    ///
+    /// ```
+    /// use s_expr::{GroupKind, Parser, PrintConfig, Printer};
+    ///
+    /// let mut p = Printer::new_with_config(PrintConfig::default().pretty(true).empty_group(GroupKind::Paren, " "));
+    /// let el = Parser::new("(f () [] x)").next().unwrap().unwrap();
+    /// p.element(&el.inner);
+    /// assert_eq!(p.to_string(), "(f ( ) [] x)");
+    /// ```
+    pub fn empty_group(mut self, grp: GroupKind, text: &str) -> Self {
+        self.empty_groups[group_index(grp)] = text.to_string();
+        self
+    }
+
+    /// Indent every line of the output by the given number of spaces, e.g. to embed the output in other text
+    ///
+    /// The indentation counts in the width of the lines, so the lines still fit in the maximum width
+    /// in pretty mode. This is equivalent to a line prefix made of spaces.
+    pub fn initial_indent(self, spaces: usize) -> Self {
+        self.line_prefix(&" ".repeat(spaces))
+    }
+
+    /// Print a prefix at the start of every line of the output, except the empty lines
+    ///
+    /// The prefix counts in the width of the lines, with the tabs counted up to the next multiple
+    /// of the tab width. The newlines printed as part of the strings don't start a new prefixed line,
+    /// since the prefix would change their content.
+    pub fn line_prefix(mut self, prefix: &str) -> Self {
+        self.line_prefix = prefix.to_string();
+        self
+    }
+
+    /// Print the line prefix on the first line, or only from the second line, e.g. when the output
+    /// follows some text on the same line
+    ///
+    /// When the prefix is not printed on the first line, the first line is still measured as if it
+    /// started with the prefix.
+    pub fn prefix_first_line(mut self, enabled: bool) -> Self {
+        self.prefix_first_line = enabled;
+        self
+    }
+
+    /// Set the number of columns of a tab when measuring the width of a line
+    pub fn tab_width(mut self, width: usize) -> Self {
+        self.tab_width = width;
+        self
+    }
+
+    /// Set the line ending of the lines printed by the printer, e.g. by [`Printer::newline`]
+    /// or when breaking groups in pretty mode
+    pub fn line_ending(mut self, ending: LineEnding) -> Self {
+        self.line_ending = ending;
+        self
+    }
+
+    /// End the output returned by [`Printer::to_string`] and [`Printer::finish`] with exactly one
+    /// newline, unless the output is empty
+    ///
+    /// The printers writing into a writer cannot remove the newlines already written, so the
+    /// output can be ended with [`Printer::newline`] instead.
+    pub fn ensure_trailing_newline(mut self, enabled: bool) -> Self {
+        self.ensure_trailing_newline = enabled;
+        self
+    }
+
+    /// Wrap the tokens in the ANSI escape sequences of the color scheme, e.g. to display
+    /// the elements in a terminal, or print them without colors
+    ///
+    /// The escape sequences don't count in the width of the lines, so the groups are broken
+    /// the same way with or without colors. Checking that the output is a terminal is left to the caller.
+    ///
+    /// ```
+    /// use s_expr::{ColorScheme, PrintConfig, Printer};
+    ///
+    /// let mut p = Printer::new_with_config(PrintConfig::default().color(Some(ColorScheme::default())));
+    /// p.open(s_expr::GroupKind::Paren);
+    /// p.text("f");
+    /// p.uint(1);
+    /// p.close(s_expr::GroupKind::Paren);
+    /// assert_eq!(p.to_string(), "\x1b[2m(\x1b[0mf \x1b[36m1\x1b[0m\x1b[2m)\x1b[0m");
+    /// ```
+    #[cfg(feature = "color")]
+    pub fn color(mut self, scheme: Option<ColorScheme>) -> Self {
+        self.color = scheme;
+        self
+    }
+
+    // SGR parameters of the color of the tokens of the given kind, if colored
+    #[cfg(feature = "color")]
+    pub(crate) fn token_color(&self, kind: TokenKind) -> Option<&str> {
+        let scheme = self.color.as_ref()?;
+        let color = match kind {
+            TokenKind::Delimiter => &scheme.delimiters,
+            TokenKind::Ident => &scheme.idents,
+            TokenKind::String => &scheme.strings,
+            TokenKind::Number => &scheme.numbers,
+            TokenKind::Bytes => &scheme.bytes,
+            TokenKind::Comment => &scheme.comments,
+            TokenKind::Text => return None,
+        };
+        Some(color.as_str()).filter(|color| !color.is_empty())
+    }
+}
+
+impl ABytes<'_> {
+    /// Create an owned bytes literal, hex encoding the data with the case and the separators
+    /// of the printer configuration, see [`PrintConfig::bytes_uppercase`] and [`PrintConfig::bytes_group`]
+    ///
+    /// ```
+    /// use s_expr::{ABytes, PrintConfig};
+    ///
+    /// let cfg = PrintConfig::default().bytes_uppercase(true).bytes_group(2);
+    /// let bytes = ABytes::from_bytes_with_config(&[0xde, 0xad, 0xbe], &cfg);
+    /// assert_eq!(bytes.as_str(), "DEAD_BE");
+    /// ```
+    pub fn from_bytes_with_config(data: &[u8], cfg: &PrintConfig) -> ABytesBuf {
+        let mut hex = String::with_capacity(data.len() * 2);
+        // writing to a String doesn't fail
+        let _ = data::write_hex(&mut hex, data, cfg.bytes_uppercase, cfg.bytes_group);
+        ABytesBuf(hex)
+    }
+}
+
+/// ANSI colors of the tokens printed by the printer, see [`PrintConfig::color`]
+///
+/// Each color is given as the parameters of an SGR escape sequence, e.g. `"32"` for green
+/// or `"1;31"` for bold red, or as an empty string to print the tokens in the default color.
+/// The default scheme prints the delimiters dim, the idents in the default color, the strings
+/// in green, the numbers in cyan, the bytes in magenta and the comments in grey.
+#[cfg(feature = "color")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColorScheme {
+    pub(crate) delimiters: String,
+    pub(crate) idents: String,
+    pub(crate) strings: String,
+    pub(crate) numbers: String,
+    pub(crate) bytes: String,
+    pub(crate) comments: String,
+}
+
+#[cfg(feature = "color")]
+impl Default for ColorScheme {
+    fn default() -> Self {
+        ColorScheme {
+            delimiters: "2".to_string(),
+            idents: String::new(),
+            strings: "32".to_string(),
+            numbers: "36".to_string(),
+            bytes: "35".to_string(),
+            comments: "90".to_string(),
+        }
+    }
+}
+
+#[cfg(feature = "color")]
+impl ColorScheme {
+    /// Create a scheme printing every token in the default color
+    pub fn none() -> Self {
+        ColorScheme {
+            delimiters: String::new(),
+            idents: String::new(),
+            strings: String::new(),
+            numbers: String::new(),
+            bytes: String::new(),
+            comments: String::new(),
+        }
+    }
+
+    /// Set the color of the group delimiters
+    pub fn delimiters(mut self, color: &str) -> Self {
+        self.delimiters = color.to_string();
+        self
+    }
+
+    /// Set the color of the idents
+    pub fn idents(mut self, color: &str) -> Self {
+        self.idents = color.to_string();
+        self
+    }
+
+    /// Set the color of the string literals
+    pub fn strings(mut self, color: &str) -> Self {
+        self.strings = color.to_string();
+        self
+    }
+
+    /// Set the color of the integral and decimal number literals
+    pub fn numbers(mut self, color: &str) -> Self {
+        self.numbers = color.to_string();
+        self
+    }
+
+    /// Set the color of the bytes literals
+    pub fn bytes(mut self, color: &str) -> Self {
+        self.bytes = color.to_string();
+        self
+    }
+
+    /// Set the color of the comments
+    pub fn comments(mut self, color: &str) -> Self {
+        self.comments = color.to_string();
+        self
+    }
+}
+
+/// Kind of a printed token, selecting its color
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum TokenKind {
+    Delimiter,
+    Ident,
+    String,
+    Number,
+    Bytes,
+    Comment,
+    /// Raw text printed by [`Printer::text`], never colored
+    Text,
+}
+
+impl TokenKind {
+    fn of_atom(atom: &Atom) -> Self {
+        match atom {
+            Atom::Integral(_) | Atom::Decimal(_) => TokenKind::Number,
+            Atom::Bytes(_) => TokenKind::Bytes,
+            Atom::String(_) => TokenKind::String,
+            Atom::Ident(_) => TokenKind::Ident,
+        }
+    }
+}
+
+// write the token written by the closure, wrapped in the escape sequences of its color if colored
+pub(crate) fn write_colored<W, F>(
+    out: &mut W,
+    cfg: &PrintConfig,
+    kind: TokenKind,
+    f: F,
+) -> fmt::Result
+where
+    W: fmt::Write + ?Sized,
+    F: FnOnce(&mut W) -> fmt::Result,
+{
+    #[cfg(feature = "color")]
+    if let Some(color) = cfg.token_color(kind) {
+        write!(out, "\x1b[{}m", color)?;
+        f(out)?;
+        return out.write_str("\x1b[0m");
+    }
+    #[cfg(not(feature = "color"))]
+    let _ = (cfg, kind);
+    f(out)
+}
+
+// write a group delimiter, in the color of the delimiters if colored
+pub(crate) fn write_delimiter<W>(out: &mut W, cfg: &PrintConfig, c: char) -> fmt::Result
+where
+    W: fmt::Write + ?Sized,
+{
+    write_colored(out, cfg, TokenKind::Delimiter, |out| out.write_char(c))
+}
+
+/// Indentation of the elements of the broken groups in pretty mode, see [`PrintConfig::indent_style`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndentStyle {
+    /// Indent by the given number of spaces from the column of the group
+    Spaces(usize),
+    /// Indent by one tab per nesting level, counted as the tab width when measuring the lines
+    Tabs,
+}
+
+/// Line ending of the lines printed by the printer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineEnding {
+    /// `\n`
+    Lf,
+    /// `\r\n`
+    CrLf,
+}
+
+impl LineEnding {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+/// Escape of the control characters in the strings printed by [`Printer::string`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ControlEscape {
+    /// Escape as `\xNN` (e.g. `\x1b`), falling back to the unicode escape for non-ascii control characters
+    Hex,
+    /// Escape as `\u{N..}` (e.g. `\u{1b}`)
+    Unicode,
+}
+
+/// Check of the ident atoms, see [`PrintConfig::ident_check`]
+///
+/// There is no quoted syntax for idents, so an invalid ident cannot be escaped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IdentCheck {
+    /// Print the idents as is, even if they don't parse back as the same ident
+    Unchecked,
+    /// Reject the idents which don't parse back as the same ident with [`PrinterError::InvalidIdent`]
+    Reject,
+}
+
+/// Error when printing a number which doesn't have a literal form
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrintNumError {
+    /// The number is NaN or an infinity
+    NotFinite,
+    /// The number is negative, and there is no negative number literal
+    Negative,
+    /// The scale is 0, and a decimal literal has at least one fractional digit
+    ZeroScale,
+}
+
+impl fmt::Display for PrintNumError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrintNumError::NotFinite => write!(f, "number is not finite"),
+            PrintNumError::Negative => write!(f, "number is negative"),
+            PrintNumError::ZeroScale => write!(f, "decimal scale is 0"),
+        }
+    }
+}
+
+impl std::error::Error for PrintNumError {}
+
+/// Error when printing an element that wouldn't parse back, or closing a group which doesn't match
+/// the open groups of the printer
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PrinterError {
+    /// The closed group kind is different from the kind of the innermost open group
+    MismatchedClose {
+        /// Kind of the innermost open group
+        open: GroupKind,
+        /// Kind of the group being closed
+        close: GroupKind,
+    },
+    /// A group is closed while there is no open group
+    NoOpenGroup(GroupKind),
+    /// The ident doesn't parse back as the same ident, e.g. it contains a space
+    InvalidIdent(String),
+}
+
+impl fmt::Display for PrinterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrinterError::MismatchedClose { open, close } => write!(
+                f,
+                "closing `{}` doesn't match the open `{}`",
+                close.close_char(),
+                open.open_char()
+            ),
+            PrinterError::NoOpenGroup(grp) => {
+                write!(f, "closing `{}` without any open group", grp.close_char())
+            }
+            PrinterError::InvalidIdent(ident) => write!(f, "invalid ident {:?}", ident),
+        }
+    }
+}
+
+impl std::error::Error for PrinterError {}
+
+/// Error when finishing a printer with some groups still open
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PrinterUnbalanced {
+    open: Vec<GroupKind>,
+}
+
+impl PrinterUnbalanced {
+    /// Return the kinds of the groups still open, from the outermost to the innermost
+    pub fn open_groups(&self) -> &[GroupKind] {
+        &self.open
+    }
+}
+
+impl fmt::Display for PrinterUnbalanced {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} group(s) still open:", self.open.len())?;
+        for grp in &self.open {
+            write!(f, " `{}`", grp.open_char())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for PrinterUnbalanced {}
+
+/// Placement of a comment printed by [`Printer::comment_with_style`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CommentStyle {
+    /// Comment on the current line, after the previous element
+    EndOfLine,
+    /// Comment on its own line, at the current indentation
+    Standalone,
+}
+
+/// Output of a printer
+///
+/// A sink is a `fmt::Write` which also decides how the result of the printing
+/// operations is reported: printing into a `String` cannot fail, so the printing methods
+/// return nothing, whereas printing into a [`FmtWriter`] returns the `fmt::Result`.
+pub trait Sink: fmt::Write {
+    /// Type returned by the printing methods
+    type Output;
+
+    /// Turn the result of a printing operation into the output type
+    fn output(&mut self, result: fmt::Result) -> Self::Output;
+
+    /// Combine the outputs of two successive printing operations, keeping the first error
+    fn merge(first: Self::Output, second: Self::Output) -> Self::Output;
+}
+
+impl Sink for String {
+    type Output = ();
+
+    fn output(&mut self, result: fmt::Result) {
+        result.expect("writing to a String cannot fail")
+    }
+
+    fn merge(_: (), _: ()) {}
+}
+
+/// Sink over any `fmt::Write`, where the printing methods return a `fmt::Result`
+#[derive(Clone, Debug)]
+pub struct FmtWriter<W>(pub W);
+
+impl<W: fmt::Write> fmt::Write for FmtWriter<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.write_str(s)
+    }
+}
+
+impl<W: fmt::Write> Sink for FmtWriter<W> {
+    type Output = fmt::Result;
+
+    fn output(&mut self, result: fmt::Result) -> fmt::Result {
+        result
+    }
+
+    fn merge(first: fmt::Result, second: fmt::Result) -> fmt::Result {
+        first.and(second)
+    }
+}
+
+/// Sink over any `io::Write`, where the printing methods return an `io::Result`
+///
+/// The output is written to the underlying writer as soon as it is available: immediately
+/// for the default flat output, and when the outermost group is closed in pretty mode.
+#[derive(Debug)]
+pub struct IoWriter<W> {
+    writer: W,
+    // io error raised during the last printing operation
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> fmt::Write for IoWriter<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.writer.write_all(s.as_bytes()).map_err(|e| {
+            self.error = Some(e);
+            fmt::Error
+        })
+    }
+}
+
+impl<W: io::Write> Sink for IoWriter<W> {
+    type Output = io::Result<()>;
+
+    fn output(&mut self, result: fmt::Result) -> io::Result<()> {
+        result.map_err(|fmt::Error| {
+            self.error
+                .take()
+                .unwrap_or_else(|| io::Error::other("formatting error"))
+        })
+    }
+
+    fn merge(first: io::Result<()>, second: io::Result<()>) -> io::Result<()> {
+        first.and(second)
+    }
+}
 
 /// Simple printer
+///
+/// By default the printer output into a `String`, but it can print into any `fmt::Write`
+/// using [`Printer::with_writer`], or stream into any `io::Write` using [`Printer::with_io_writer`].
+///
+/// The keys of the source map are `usize` by default, e.g. the index of the element in the source,
+/// and can be of any type `K` with [`Printer::with_sink_keyed`].
 #[derive(Clone)]
-pub struct Printer {
-    buf: String,
+pub struct Printer<S = String, K = usize> {
+    sink: S,
     prev: PrinterState,
+    cfg: PrintConfig,
+    // line, column and byte offset of the next character printed in the sink
+    line: usize,
+    col: usize,
+    offset: usize,
+    // number of newlines ending the output, the start of the output counting as a blank line
+    newlines: usize,
+    // kinds of the open groups, from the outermost to the innermost
+    opened: Vec<GroupKind>,
+    // groups being built in pretty mode, laid out once the outermost group is closed
+    groups: Vec<(GroupKind, GroupLayout, Vec<Node<K>>)>,
+    // layout forced on the groups opened at the given depth
+    layout: (GroupLayout, usize),
+    // spans of the mapped elements, by key
+    source_map: Vec<(K, Span)>,
+    // key of the element being mapped, with the depth of the element in pretty mode
+    mapping: Option<(K, usize)>,
+    // end of the last comment printed in flat mode, before its newline
+    comment_end: Position,
+    // line prefix to print before the next character, unless it's a newline
+    pending_prefix: bool,
 }
 
 #[derive(Clone, PartialEq, Eq)]
 pub enum PrinterState {
+    Open,
     Group,
     Text,
 }
 
 impl Default for Printer {
     fn default() -> Self {
+        Self::new_with_config(PrintConfig::default())
+    }
+}
+
+// index of the group kind in the tables by group kind, following the order of `GroupKind::ALL`
+fn group_index(grp: GroupKind) -> usize {
+    GroupKind::ALL
+        .iter()
+        .position(|kind| *kind == grp)
+        .expect("all the group kinds are listed")
+}
+
+impl Printer {
+    /// Create a new printer with an associated config
+    pub fn new_with_config(cfg: PrintConfig) -> Self {
+        Self::with_sink(String::new(), cfg)
+    }
+
+    /// Create a new printer appending to the given buffer, e.g. to accumulate several printed
+    /// fragments in one allocation, and get the buffer back with [`Printer::into_string`]
+    ///
+    /// The content already in the buffer is kept as is, and the printer starts printing as if
+    /// at the start of a line.
+    ///
+    /// ```
+    /// use s_expr::{GroupKind, Printer};
+    ///
+    /// let mut buf = String::with_capacity(64);
+    /// buf.push_str("a: ");
+    /// let mut p = Printer::from_string(buf);
+    /// p.group(GroupKind::Paren, |p| p.text("x"));
+    /// let mut buf = p.into_string();
+    /// buf.push_str(", b: ");
+    /// let mut p = Printer::from_string(buf);
+    /// p.uint(2);
+    /// assert_eq!(p.into_string(), "a: (x), b: 2");
+    /// ```
+    pub fn from_string(buf: String) -> Self {
+        Self::from_string_with_config(buf, PrintConfig::default())
+    }
+
+    /// Create a new printer appending to the given buffer, with an associated config
+    pub fn from_string_with_config(buf: String, cfg: PrintConfig) -> Self {
+        Self::with_sink(buf, cfg)
+    }
+}
+
+impl<K: Clone> Printer<String, K> {
+    /// Return the output printed so far, without consuming the printer
+    ///
+    /// In pretty mode, the groups still open are not part of the output yet.
+    pub fn as_str(&self) -> &str {
+        &self.sink
+    }
+
+    /// Clear the output and the state of the printer, keeping the capacity of the output
+    ///
+    /// ```
+    /// use s_expr::{GroupKind, Printer};
+    ///
+    /// let mut p = Printer::default();
+    /// let mut messages = Vec::new();
+    /// for i in 0..3u128 {
+    ///     p.clear();
+    ///     p.group(GroupKind::Paren, |p| {
+    ///         p.text("msg");
+    ///         p.uint(i);
+    ///     });
+    ///     messages.push(p.as_str().to_string());
+    /// }
+    /// assert_eq!(messages, ["(msg 0)", "(msg 1)", "(msg 2)"]);
+    /// ```
+    pub fn clear(&mut self) {
+        self.sink.clear();
+        self.reset();
+    }
+
+    /// Return the buffer holding the output, without copying it, even if some groups are still open
+    ///
+    /// This is the same as [`Printer::to_string`].
+    pub fn into_string(self) -> String {
+        self.to_string()
+    }
+
+    /// Return the output, even if some groups are still open
+    #[allow(clippy::inherent_to_string)]
+    pub fn to_string(self) -> String {
+        let mut out = self.sink;
+        if self.cfg.ensure_trailing_newline && !out.is_empty() {
+            let len = out.trim_end_matches(['\r', '\n']).len();
+            out.truncate(len);
+            out.push_str(self.cfg.line_ending.as_str());
+        }
+        out
+    }
+
+    /// Return the output, or an error if some groups are still open
+    ///
+    /// ```
+    /// use s_expr::{GroupKind, Printer};
+    ///
+    /// let mut p = Printer::default();
+    /// p.open(GroupKind::Paren);
+    /// p.text("a");
+    /// let err = p.finish().expect_err("unbalanced");
+    /// assert_eq!(err.open_groups(), &[GroupKind::Paren]);
+    /// ```
+    pub fn finish(self) -> Result<String, PrinterUnbalanced> {
+        self.check_balanced()?;
+        Ok(self.to_string())
+    }
+}
+
+impl<W: fmt::Write> Printer<FmtWriter<W>> {
+    /// Create a new printer writing into a `fmt::Write`
+    pub fn with_writer(writer: W) -> Self {
+        Self::with_writer_and_config(writer, PrintConfig::default())
+    }
+
+    /// Create a new printer writing into a `fmt::Write`, with an associated config
+    pub fn with_writer_and_config(writer: W, cfg: PrintConfig) -> Self {
+        Self::with_sink(FmtWriter(writer), cfg)
+    }
+}
+
+impl<W: fmt::Write, K: Clone> Printer<FmtWriter<W>, K> {
+    /// Return the writer
+    pub fn into_writer(self) -> W {
+        self.sink.0
+    }
+
+    /// Return the writer, or an error if some groups are still open
+    pub fn finish(self) -> Result<W, PrinterUnbalanced> {
+        self.check_balanced()?;
+        Ok(self.sink.0)
+    }
+}
+
+impl<W: io::Write> Printer<IoWriter<W>> {
+    /// Create a new printer writing into an `io::Write`
+    pub fn with_io_writer(writer: W) -> Self {
+        Self::with_io_writer_and_config(writer, PrintConfig::default())
+    }
+
+    /// Create a new printer writing into an `io::Write`, with an associated config
+    pub fn with_io_writer_and_config(writer: W, cfg: PrintConfig) -> Self {
+        Self::with_sink(
+            IoWriter {
+                writer,
+                error: None,
+            },
+            cfg,
+        )
+    }
+}
+
+impl<W: io::Write, K: Clone> Printer<IoWriter<W>, K> {
+    /// Flush the writer
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.sink.writer.flush()
+    }
+
+    /// Return the writer
+    pub fn into_writer(self) -> W {
+        self.sink.writer
+    }
+
+    /// Return the writer, or an error if some groups are still open
+    pub fn finish(self) -> Result<W, PrinterUnbalanced> {
+        self.check_balanced()?;
+        Ok(self.sink.writer)
+    }
+}
+
+impl<S: Sink> Printer<S> {
+    /// Create a new printer on a sink, with an associated config
+    pub fn with_sink(sink: S, cfg: PrintConfig) -> Self {
+        Self::with_sink_keyed(sink, cfg)
+    }
+}
+
+impl<S: Sink, K: Clone> Printer<S, K> {
+    /// Create a new printer on a sink, with an associated config, recording the mapped elements
+    /// in the source map with keys of type `K`
+    ///
+    /// ```
+    /// use s_expr::{Atom, Element, PrintConfig, Printer, Span};
+    ///
+    /// let mut p: Printer<String, &str> = Printer::with_sink_keyed(String::new(), PrintConfig::default());
+    /// p.text("f");
+    /// p.element_mapped("arg", &Element::Atom(Atom::Ident("x")));
+    /// assert_eq!(p.source_map(), &[("arg", Span::on_line(1, 2, 3))]);
+    /// ```
+    pub fn with_sink_keyed(sink: S, cfg: PrintConfig) -> Self {
+        let pending_prefix = cfg.prefix_first_line;
+        let col = first_line_col(&cfg);
         Self {
-            buf: String::new(),
+            sink,
             prev: PrinterState::Group,
+            cfg,
+            line: 1,
+            col,
+            offset: 0,
+            newlines: 2,
+            opened: Vec::new(),
+            groups: Vec::new(),
+            layout: (GroupLayout::Auto, 0),
+            source_map: Vec::new(),
+            mapping: None,
+            comment_end: Position::default(),
+            pending_prefix,
+        }
+    }
+
+    // reset the state of the printer, as if nothing was printed, keeping the allocations
+    fn reset(&mut self) {
+        self.prev = PrinterState::Group;
+        self.line = 1;
+        self.col = first_line_col(&self.cfg);
+        self.offset = 0;
+        self.newlines = 2;
+        self.opened.clear();
+        self.groups.clear();
+        self.layout = (GroupLayout::Auto, 0);
+        self.source_map.clear();
+        self.mapping = None;
+        self.comment_end = Position::default();
+        self.pending_prefix = self.cfg.prefix_first_line;
+    }
+
+    /// Create a new group
+    pub fn open(&mut self, grp: GroupKind) -> S::Output {
+        let r = self.write_open(grp);
+        self.sink.output(r)
+    }
+
+    /// Close a group
+    ///
+    /// # Panics
+    ///
+    /// Panics if the group kind doesn't match the innermost open group, or if there is no open group.
+    /// Use [`Printer::try_close`] to get an error instead.
+    pub fn close(&mut self, grp: GroupKind) -> S::Output {
+        match self.try_close(grp) {
+            Ok(output) => output,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Close a group, checking that the group kind matches the innermost open group
+    ///
+    /// Nothing is printed if the kinds don't match, or if there is no open group.
+    ///
+    /// ```
+    /// use s_expr::{GroupKind, Printer, PrinterError};
+    ///
+    /// let mut p = Printer::default();
+    /// p.open(GroupKind::Paren);
+    /// assert_eq!(
+    ///     p.try_close(GroupKind::Bracket),
+    ///     Err(PrinterError::MismatchedClose { open: GroupKind::Paren, close: GroupKind::Bracket })
+    /// );
+    /// assert_eq!(p.try_close(GroupKind::Paren), Ok(()));
+    /// assert_eq!(p.to_string(), "()");
+    /// ```
+    pub fn try_close(&mut self, grp: GroupKind) -> Result<S::Output, PrinterError> {
+        match self.opened.last() {
+            None => return Err(PrinterError::NoOpenGroup(grp)),
+            Some(open) if *open != grp => {
+                return Err(PrinterError::MismatchedClose {
+                    open: *open,
+                    close: grp,
+                })
+            }
+            Some(_) => {}
+        }
+        let r = self.write_close(grp);
+        Ok(self.sink.output(r))
+    }
+
+    /// Return the number of open groups
+    pub fn depth(&self) -> usize {
+        self.opened.len()
+    }
+
+    /// Print a group, with its content printed by the closure
+    ///
+    /// The group is always closed after the closure has run, which guarantee the
+    /// output is balanced, even when the closure returns early.
+    ///
+    /// ```
+    /// use s_expr::{GroupKind, Printer};
+    ///
+    /// let mut p = Printer::default();
+    /// p.group(GroupKind::Paren, |p| {
+    ///     p.text("let");
+    ///     p.group(GroupKind::Bracket, |p| p.text("x"));
+    /// });
+    /// assert_eq!(p.to_string(), "(let [x])");
+    /// ```
+    pub fn group<F>(&mut self, grp: GroupKind, f: F) -> S::Output
+    where
+        F: FnOnce(&mut Self) -> S::Output,
+    {
+        let opened = self.open(grp);
+        let content = f(self);
+        let closed = self.close(grp);
+        S::merge(S::merge(opened, content), closed)
+    }
+
+    /// Print the groups opened by the closure on one line in pretty mode, whatever their width
+    ///
+    /// All the groups nested in these groups are printed on one line too, except the groups
+    /// containing a comment.
+    ///
+    /// ```
+    /// use s_expr::{GroupKind, PrintConfig, Printer};
+    ///
+    /// let mut p = Printer::new_with_config(PrintConfig::default().pretty(true).max_width(10));
+    /// p.group(GroupKind::Paren, |p| {
+    ///     p.text("point");
+    ///     p.flat(|p| p.group(GroupKind::Bracket, |p| {
+    ///         p.text("1.0");
+    ///         p.text("2.0");
+    ///         p.text("3.0");
+    ///     }));
+    /// });
+    /// assert_eq!(p.to_string(), "(point\n  [1.0 2.0 3.0])");
+    /// ```
+    pub fn flat<F>(&mut self, f: F) -> S::Output
+    where
+        F: FnOnce(&mut Self) -> S::Output,
+    {
+        self.with_layout(GroupLayout::Flat, f)
+    }
+
+    /// Print the groups opened by the closure over multiple lines in pretty mode, whatever their width
+    ///
+    /// The groups nested in these groups keep the width based layout.
+    pub fn broken<F>(&mut self, f: F) -> S::Output
+    where
+        F: FnOnce(&mut Self) -> S::Output,
+    {
+        self.with_layout(GroupLayout::Broken, f)
+    }
+
+    /// Print the groups opened by the closure with as many elements per line as fit in pretty mode,
+    /// when they don't fit on one line
+    ///
+    /// The lines are aligned on the first element of the group.
+    pub fn fill<F>(&mut self, f: F) -> S::Output
+    where
+        F: FnOnce(&mut Self) -> S::Output,
+    {
+        self.with_layout(GroupLayout::Fill, f)
+    }
+
+    /// Print the groups opened by the closure as alternating keys and values in pretty mode, with as
+    /// many pairs per line as fit when they don't fit on one line
+    ///
+    /// A key is never separated from its value, and the lines are aligned on the first key. With an odd
+    /// number of elements, the last element is placed like a pair.
+    pub fn pairs<F>(&mut self, f: F) -> S::Output
+    where
+        F: FnOnce(&mut Self) -> S::Output,
+    {
+        self.with_layout(GroupLayout::Pairs, f)
+    }
+
+    /// Print the groups opened by the closure as tables in pretty mode when they don't fit on one line,
+    /// with each of their groups on its own line and the elements of these groups aligned in columns
+    ///
+    /// The groups are only printed as tables if they contain only groups of the same length, without
+    /// comments, and if every row fits in the maximum width. Otherwise they are broken as usual.
+    pub fn table<F>(&mut self, f: F) -> S::Output
+    where
+        F: FnOnce(&mut Self) -> S::Output,
+    {
+        self.with_layout(GroupLayout::Table, f)
+    }
+
+    fn with_layout<F>(&mut self, layout: GroupLayout, f: F) -> S::Output
+    where
+        F: FnOnce(&mut Self) -> S::Output,
+    {
+        let saved = core::mem::replace(&mut self.layout, (layout, self.opened.len()));
+        let output = f(self);
+        self.layout = saved;
+        output
+    }
+
+    /// Add text
+    pub fn text(&mut self, s: &str) -> S::Output {
+        let r = self.write_text(s);
+        self.sink.output(r)
+    }
+
+    /// Add an atom, using the literal syntax of the atom type so that it parse back to the same atom
+    ///
+    /// Strings are printed with quotes around their raw (escaped) data, bytes are wrapped
+    /// in `#`, and integral numbers keep their base prefix.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the atom is an invalid ident and the idents are checked, see [`PrintConfig::ident_check`].
+    /// Use [`Printer::try_atom`] to get an error instead.
+    pub fn atom(&mut self, atom: &Atom) -> S::Output {
+        match self.try_atom(atom) {
+            Ok(output) => output,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Add an atom, or return an error without printing anything if the atom is an invalid ident
+    /// and the idents are checked
+    ///
+    /// ```
+    /// use s_expr::{Atom, IdentCheck, PrintConfig, Printer, PrinterError};
+    ///
+    /// let mut p = Printer::new_with_config(PrintConfig::default().ident_check(IdentCheck::Reject));
+    /// assert_eq!(
+    ///     p.try_atom(&Atom::Ident("a b")),
+    ///     Err(PrinterError::InvalidIdent("a b".to_string()))
+    /// );
+    /// assert_eq!(p.try_atom(&Atom::Ident("a-b")), Ok(()));
+    /// ```
+    pub fn try_atom(&mut self, atom: &Atom) -> Result<S::Output, PrinterError> {
+        self.check_atom(atom)?;
+        let r = self.write_atom(atom);
+        Ok(self.sink.output(r))
+    }
+
+    /// Add a string literal, quoting and escaping the string
+    ///
+    /// The quote and backslash characters are always escaped, and the whitespace and
+    /// control characters are escaped according to the config.
+    pub fn string(&mut self, s: &str) -> S::Output {
+        let r = self.write_token(TokenKind::String, |out, cfg| {
+            out.write_char('"')?;
+            escape::escape(out, s, cfg)?;
+            out.write_char('"')
+        });
+        self.sink.output(r)
+    }
+
+    /// Add a bytes literal, hex encoding the data
+    ///
+    /// ```
+    /// use s_expr::{PrintConfig, Printer};
+    ///
+    /// let mut p = Printer::new_with_config(PrintConfig::default().bytes_group(2));
+    /// p.bytes(&[0xde, 0xad, 0xbe, 0xef, 0x01]);
+    /// assert_eq!(p.to_string(), "#dead_beef_01#");
+    /// ```
+    pub fn bytes(&mut self, data: &[u8]) -> S::Output {
+        let r = self.write_token(TokenKind::Bytes, |out, cfg| {
+            out.write_char('#')?;
+            data::write_hex(out, data, cfg.bytes_uppercase, cfg.bytes_group)?;
+            out.write_char('#')
+        });
+        self.sink.output(r)
+    }
+
+    /// Add an unsigned integral number, in decimal
+    pub fn uint(&mut self, v: u128) -> S::Output {
+        self.uint_with_base(v, ANumBase::Decimal)
+    }
+
+    /// Add an unsigned integral number in the given base, with the `0b` or `0x` prefix if not decimal
+    pub fn uint_with_base(&mut self, v: u128, base: ANumBase) -> S::Output {
+        let r = self.write_token(TokenKind::Number, |out, cfg| {
+            let digits = match base {
+                ANumBase::Binary => format!("{:b}", v),
+                ANumBase::Decimal => format!("{}", v),
+                ANumBase::Hexadecimal => format!("{:x}", v),
+            };
+            number_literal(out, base, &digits, cfg.number_separators)
+        });
+        self.sink.output(r)
+    }
+
+    /// Add an integral number atom re-encoded in the given base, with the `0b` or `0x` prefix if not decimal
+    ///
+    /// The numbers of any size are re-encoded, without the leading zeros and the `_` separators
+    /// of the original digits, which are separated according to [`PrintConfig::number_separators`].
+    ///
+    /// ```
+    /// use s_expr::{ANum, ANumBase, Printer};
+    ///
+    /// let mut p = Printer::default();
+    /// p.num_as(&ANum { base: ANumBase::Decimal, dat: "65_536" }, ANumBase::Hexadecimal);
+    /// p.num_as(&ANum { base: ANumBase::Hexadecimal, dat: "ff" }, ANumBase::Binary);
+    /// assert_eq!(p.to_string(), "0x10000 0b11111111");
+    /// ```
+    pub fn num_as(&mut self, num: &ANum, base: ANumBase) -> S::Output {
+        let r = self.write_token(TokenKind::Number, |out, cfg| {
+            number_literal(out, base, &num.digits_in(base), cfg.number_separators)
+        });
+        self.sink.output(r)
+    }
+
+    /// Add an arbitrary precision unsigned integer in the given base, with the `0b` or `0x` prefix if not decimal
+    #[cfg(feature = "bigint")]
+    pub fn biguint(&mut self, v: &num_bigint::BigUint, base: ANumBase) -> S::Output {
+        let r = self.write_token(TokenKind::Number, |out, cfg| {
+            let digits = v.to_str_radix(base.to_radix());
+            number_literal(out, base, &digits, cfg.number_separators)
+        });
+        self.sink.output(r)
+    }
+
+    /// Add a decimal number, using the shortest representation that parse back to the same value
+    ///
+    /// The number is printed in the `<integral>.<fractional>` form, without exponent. NaN, infinities and
+    /// negative numbers don't have a literal form and are rejected with an error, without printing anything.
+    pub fn decimal(&mut self, v: f64) -> Result<S::Output, PrintNumError> {
+        check_decimal(v)?;
+        let r = self.write_token(TokenKind::Number, |out, _| {
+            decimal_literal(out, &format!("{}", v))
+        });
+        Ok(self.sink.output(r))
+    }
+
+    /// Add a decimal number, rounded to at most `max_digits` fractional digits
+    ///
+    /// Trailing zeros are removed, keeping at least one fractional digit. The same numbers as
+    /// [`Printer::decimal`] are rejected.
+    pub fn decimal_with(&mut self, v: f64, max_digits: usize) -> Result<S::Output, PrintNumError> {
+        check_decimal(v)?;
+        let r = self.write_token(TokenKind::Number, |out, _| {
+            decimal_literal(out, &format!("{:.*}", max_digits, v))
+        });
+        Ok(self.sink.output(r))
+    }
+
+    /// Add a decimal number, rounded to exactly `scale` fractional digits, keeping the trailing zeros
+    ///
+    /// The number is rounded half up from its shortest decimal representation, the one printed by
+    /// [`Printer::decimal`], so `7.25` is rounded to `7.3` even though the float is not exact. A scale of 0
+    /// is rejected with [`PrintNumError::ZeroScale`], as a decimal literal has at least one fractional
+    /// digit, as well as the numbers rejected by [`Printer::decimal`].
+    ///
+    /// ```
+    /// use s_expr::{PrintNumError, Printer};
+    ///
+    /// let mut p = Printer::default();
+    /// p.decimal_scaled(1.5, 2).unwrap();
+    /// p.decimal_scaled(7.25, 1).unwrap();
+    /// assert_eq!(p.decimal_scaled(7.25, 0), Err(PrintNumError::ZeroScale));
+    /// assert_eq!(p.to_string(), "1.50 7.3");
+    /// ```
+    pub fn decimal_scaled(&mut self, v: f64, scale: usize) -> Result<S::Output, PrintNumError> {
+        check_decimal(v)?;
+        if scale == 0 {
+            return Err(PrintNumError::ZeroScale);
+        }
+        let r = self.write_token(TokenKind::Number, |out, _| {
+            out.write_str(&scaled_digits(&format!("{}", v), scale))
+        });
+        Ok(self.sink.output(r))
+    }
+
+    /// Add a comment on its own line
+    ///
+    /// The `;` leader is added if the text doesn't start with it, and the comment is always
+    /// followed by a newline, so that it doesn't swallow the following elements. Text with
+    /// multiple lines is printed as multiple comments.
+    pub fn comment(&mut self, text: &str) -> S::Output {
+        self.comment_with_style(text, CommentStyle::Standalone)
+    }
+
+    /// Add a comment, placed on its own line or at the end of the current line
+    pub fn comment_with_style(&mut self, text: &str, style: CommentStyle) -> S::Output {
+        let r = self.write_comment(text, style);
+        self.sink.output(r)
+    }
+
+    /// Add a sequence of elements, each separated from the previous one
+    ///
+    /// The elements don't need to be inside a group: on the top level they are separated by a space,
+    /// or each printed on its own line in pretty mode, which allows printing the content of a group
+    /// without its delimiters.
+    ///
+    /// ```
+    /// use s_expr::{Atom, Element, GroupKind, Printer};
+    ///
+    /// let fields = [
+    ///     Element::Group(GroupKind::Paren, Vec::new()),
+    ///     Element::Atom(Atom::Ident("a")),
+    ///     Element::Group(GroupKind::Bracket, Vec::new()),
+    /// ];
+    /// let mut p = Printer::default();
+    /// p.elements_joined(&fields);
+    /// assert_eq!(p.to_string(), "() a []");
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if an element contains an invalid ident and the idents are checked, see [`PrintConfig::ident_check`].
+    /// Use [`Printer::try_elements_joined`] to get an error instead.
+    pub fn elements_joined<'e, 'b: 'e, I>(&mut self, elements: I) -> S::Output
+    where
+        I: IntoIterator<Item = &'e Element<'b>>,
+    {
+        match self.try_elements_joined(elements) {
+            Ok(output) => output,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Add a sequence of elements, or return an error if an element contains an invalid ident and
+    /// the idents are checked
+    ///
+    /// The elements before the invalid element are printed, and nothing is printed of the invalid element.
+    pub fn try_elements_joined<'e, 'b: 'e, I>(
+        &mut self,
+        elements: I,
+    ) -> Result<S::Output, PrinterError>
+    where
+        I: IntoIterator<Item = &'e Element<'b>>,
+    {
+        let mut r = Ok(());
+        for el in elements {
+            self.check_element(el)?;
+            r = self.write_separator().and_then(|()| self.write_element(el));
+            if r.is_err() {
+                break;
+            }
+        }
+        Ok(self.sink.output(r))
+    }
+
+    /// End the current line, unless the output already ends with a newline
+    ///
+    /// The next element starts on a new line, at the indentation of the current group in pretty mode
+    /// (which forces the group to be broken over multiple lines). Nothing is printed at the start of the output.
+    pub fn newline(&mut self) -> S::Output {
+        let r = self.write_newlines(1);
+        self.sink.output(r)
+    }
+
+    /// End the current line and add an empty line, unless the output already ends with a blank line
+    ///
+    /// Nothing is printed at the start of the output.
+    pub fn blank_line(&mut self) -> S::Output {
+        let r = self.write_newlines(2);
+        self.sink.output(r)
+    }
+
+    /// Add an element, recursively printing the groups and their elements
+    ///
+    /// Comments are followed by a newline, so that the comment doesn't swallow the following elements
+    ///
+    /// # Panics
+    ///
+    /// Panics if the element contains an invalid ident and the idents are checked, see [`PrintConfig::ident_check`].
+    /// Use [`Printer::try_element`] to get an error instead.
+    pub fn element(&mut self, el: &Element) -> S::Output {
+        match self.try_element(el) {
+            Ok(output) => output,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Add an element, or return an error without printing anything if the element contains an
+    /// invalid ident and the idents are checked
+    pub fn try_element(&mut self, el: &Element) -> Result<S::Output, PrinterError> {
+        self.check_element(el)?;
+        let r = self.write_element(el);
+        Ok(self.sink.output(r))
+    }
+
+    /// Add an element truncated to its top shape, e.g. to log a large document
+    ///
+    /// The groups nested deeper than `max_depth` are replaced by `…`, and the elements of a group after the
+    /// first `max_children` are replaced by `… +N more`. The output is marked as truncated and doesn't parse back.
+    ///
+    /// ```
+    /// use s_expr::{Parser, Printer};
+    ///
+    /// let el = Parser::new("(list (a (b c)) 1 2 3 4)").next().unwrap().unwrap();
+    /// let mut p = Printer::default();
+    /// p.element_truncated(&el.inner, 2, 3);
+    /// assert_eq!(p.to_string(), "(list (a …) 1 … +3 more)");
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the element contains an invalid ident and the idents are checked, see [`PrintConfig::ident_check`].
+    /// Use [`Printer::try_element_truncated`] to get an error instead.
+    pub fn element_truncated(
+        &mut self,
+        el: &Element,
+        max_depth: usize,
+        max_children: usize,
+    ) -> S::Output {
+        match self.try_element_truncated(el, max_depth, max_children) {
+            Ok(output) => output,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Add an element truncated to its top shape, or return an error without printing anything if
+    /// the element contains an invalid ident and the idents are checked
+    pub fn try_element_truncated(
+        &mut self,
+        el: &Element,
+        max_depth: usize,
+        max_children: usize,
+    ) -> Result<S::Output, PrinterError> {
+        self.check_element(el)?;
+        let r = self.write_truncated(el, max_depth, max_children);
+        Ok(self.sink.output(r))
+    }
+
+    fn write_truncated(
+        &mut self,
+        el: &Element,
+        max_depth: usize,
+        max_children: usize,
+    ) -> fmt::Result {
+        match el {
+            Element::Group(_, _) if max_depth == 0 => self.write_text("…"),
+            Element::Group(grp, elements) => {
+                self.write_open(*grp)?;
+                // the elements are always separated, to make the markers clear
+                for e in elements.iter().take(max_children) {
+                    self.write_separator()?;
+                    self.write_truncated(&e.inner, max_depth - 1, max_children)?;
+                }
+                if elements.len() > max_children {
+                    self.write_separator()?;
+                    self.write_text(&format!("… +{} more", elements.len() - max_children))?;
+                }
+                self.write_close(*grp)
+            }
+            _ => self.write_element(el),
+        }
+    }
+
+    /// Add an element like [`Printer::element`], recording the span of the element in the output
+    /// with the given key in the source map
+    ///
+    /// ```
+    /// use s_expr::{Atom, Element, Printer, Span};
+    ///
+    /// let mut p = Printer::default();
+    /// p.text("f");
+    /// p.element_mapped(7, &Element::Atom(Atom::Ident("x")));
+    /// assert_eq!(p.source_map(), &[(7, Span::on_line(1, 2, 3))]);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the element contains an invalid ident and the idents are checked, see [`PrintConfig::ident_check`].
+    /// Use [`Printer::try_element_mapped`] to get an error instead.
+    pub fn element_mapped(&mut self, key: K, el: &Element) -> S::Output {
+        match self.try_element_mapped(key, el) {
+            Ok(output) => output,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Add an element recording its span in the source map, or return an error without printing
+    /// or recording anything if the element contains an invalid ident and the idents are checked
+    pub fn try_element_mapped(&mut self, key: K, el: &Element) -> Result<S::Output, PrinterError> {
+        self.check_element(el)?;
+        let r = self.write_mapped(key, |p| p.write_element(el));
+        Ok(self.sink.output(r))
+    }
+
+    /// Add an atom like [`Printer::atom`], recording the span of the atom in the output
+    /// with the given key in the source map
+    ///
+    /// # Panics
+    ///
+    /// Panics if the atom is an invalid ident and the idents are checked, see [`PrintConfig::ident_check`].
+    /// Use [`Printer::try_atom_mapped`] to get an error instead.
+    pub fn atom_mapped(&mut self, key: K, atom: &Atom) -> S::Output {
+        match self.try_atom_mapped(key, atom) {
+            Ok(output) => output,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Add an atom recording its span in the source map, or return an error without printing
+    /// or recording anything if the atom is an invalid ident and the idents are checked
+    pub fn try_atom_mapped(&mut self, key: K, atom: &Atom) -> Result<S::Output, PrinterError> {
+        self.check_atom(atom)?;
+        let r = self.write_mapped(key, |p| p.write_atom(atom));
+        Ok(self.sink.output(r))
+    }
+
+    /// Return the spans in the output of the elements printed with [`Printer::element_mapped`]
+    /// and [`Printer::atom_mapped`], with their keys, in the order of the output
+    ///
+    /// In pretty mode, the span of an element is only known once its outermost group is closed.
+    pub fn source_map(&self) -> &[(K, Span)] {
+        &self.source_map
+    }
+
+    fn write_mapped<F>(&mut self, key: K, f: F) -> fmt::Result
+    where
+        F: FnOnce(&mut Self) -> fmt::Result,
+    {
+        if self.cfg.pretty {
+            // the node of the element is wrapped when pushed at this depth
+            self.mapping = Some((key, self.groups.len()));
+            let r = f(self);
+            self.mapping = None;
+            return r;
+        }
+        // the element is separated from the previous text first, so that its span starts on the element
+        if self.prev == PrinterState::Text {
+            self.write_char(' ')?;
+            self.prev = PrinterState::Group;
+        }
+        let start = self.position();
+        let comments = self.comment_end;
+        f(self)?;
+        let end = if self.comment_end != comments {
+            self.comment_end
+        } else {
+            self.position()
+        };
+        self.source_map.push((key, Span { start, end }));
+        Ok(())
+    }
+
+    fn position(&self) -> Position {
+        Position {
+            line: self.line,
+            col: self.col,
+            offset: self.offset,
+        }
+    }
+
+    fn check_atom(&self, atom: &Atom) -> Result<(), PrinterError> {
+        match atom {
+            Atom::Ident(ident)
+                if self.cfg.ident_check == IdentCheck::Reject
+                    && !self.cfg.tokenizer.is_ident(ident) =>
+            {
+                Err(PrinterError::InvalidIdent(ident.to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn check_element(&self, el: &Element) -> Result<(), PrinterError> {
+        match el {
+            Element::Atom(atom) => self.check_atom(atom),
+            Element::Group(_, elements) => elements
+                .iter()
+                .try_for_each(|e| self.check_element(&e.inner)),
+            Element::Comment(_) => Ok(()),
         }
     }
+
+    fn check_balanced(&self) -> Result<(), PrinterUnbalanced> {
+        if self.opened.is_empty() {
+            Ok(())
+        } else {
+            Err(PrinterUnbalanced {
+                open: self.opened.clone(),
+            })
+        }
+    }
+
+    fn write_open(&mut self, grp: GroupKind) -> fmt::Result {
+        self.opened.push(grp);
+        if self.cfg.pretty {
+            let layout = match self.layout {
+                (layout, depth) if depth + 1 == self.opened.len() => layout,
+                _ => GroupLayout::Auto,
+            };
+            self.groups.push((grp, layout, Vec::new()));
+            return Ok(());
+        }
+        if self.prev == PrinterState::Text {
+            self.write(" ")?;
+        }
+        self.prev = PrinterState::Open;
+        self.write_delimiter(grp.open_char())
+    }
+
+    // close the innermost open group, which the callers check is of the given kind, so that
+    // the flat and pretty modes print the same delimiter
+    fn write_close(&mut self, grp: GroupKind) -> fmt::Result {
+        let open = self.opened.pop();
+        debug_assert_eq!(open, Some(grp));
+        if self.cfg.pretty {
+            if let Some((grp, layout, children)) = self.groups.pop() {
+                // an empty group is laid out like an atom, never broken
+                if children.is_empty() {
+                    let mut text = String::new();
+                    write_delimiter(&mut text, &self.cfg, grp.open_char())?;
+                    text.push_str(&self.cfg.empty_groups[group_index(grp)]);
+                    write_delimiter(&mut text, &self.cfg, grp.close_char())?;
+                    return self.push_node(Node::Text(text));
+                }
+                return self.push_node(Node::Group(grp, layout, children));
+            }
+        }
+        if self.prev == PrinterState::Open {
+            let text = self.cfg.empty_groups[group_index(grp)].clone();
+            self.write(&text)?;
+        }
+        self.prev = PrinterState::Group;
+        self.write_delimiter(grp.close_char())
+    }
+
+    fn write_text(&mut self, s: &str) -> fmt::Result {
+        self.write_token(TokenKind::Text, |out, _| out.write_str(s))
+    }
+
+    fn write_atom(&mut self, atom: &Atom) -> fmt::Result {
+        self.write_token(TokenKind::of_atom(atom), |out, _| atom_literal(out, atom))
+    }
+
+    // write a token separated from the previous text, with the content written by the closure
+    fn write_token<F>(&mut self, kind: TokenKind, f: F) -> fmt::Result
+    where
+        F: FnOnce(&mut dyn fmt::Write, &PrintConfig) -> fmt::Result,
+    {
+        if self.cfg.pretty {
+            let mut token = String::new();
+            write_colored(&mut token, &self.cfg, kind, |out| f(out, &self.cfg))?;
+            return self.push_node(Node::Text(token));
+        }
+        self.start_text()?;
+        let (mut out, cfg) = self.out_with_cfg();
+        write_colored(&mut out, cfg, kind, |out| f(out, cfg))
+    }
+
+    fn write_element(&mut self, el: &Element) -> fmt::Result {
+        match el {
+            Element::Atom(atom) => self.write_atom(atom),
+            Element::Group(grp, elements) => {
+                self.write_open(*grp)?;
+                for e in elements {
+                    self.write_element(&e.inner)?;
+                }
+                self.write_close(*grp)
+            }
+            Element::Comment(comment) => {
+                let style = if self.cfg.pretty {
+                    CommentStyle::Standalone
+                } else {
+                    CommentStyle::EndOfLine
+                };
+                self.write_comment(comment, style)
+            }
+        }
+    }
+
+    fn write_newlines(&mut self, count: usize) -> fmt::Result {
+        if self.cfg.pretty {
+            if let Some((_, _, children)) = self.groups.last_mut() {
+                match children.last_mut() {
+                    Some(Node::Newline(n)) => *n = (*n).max(count),
+                    _ => children.push(Node::Newline(count)),
+                }
+                return Ok(());
+            }
+        }
+        let ending = if self.line_started() {
+            0
+        } else {
+            self.newlines
+        };
+        for _ in ending..count {
+            self.write_line_break()?;
+        }
+        self.prev = PrinterState::Group;
+        Ok(())
+    }
+
+    // in flat mode, separate the next element from anything on the current line except an opening delimiter
+    fn write_separator(&mut self) -> fmt::Result {
+        if !self.cfg.pretty && self.line_started() && self.prev != PrinterState::Open {
+            self.write_char(' ')?;
+            self.prev = PrinterState::Group;
+        }
+        Ok(())
+    }
+
+    fn write_comment(&mut self, text: &str, style: CommentStyle) -> fmt::Result {
+        for (i, line) in text.split('\n').enumerate() {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            let style = if i == 0 {
+                style
+            } else {
+                CommentStyle::Standalone
+            };
+            let comment = if line.starts_with(';') {
+                line.to_string()
+            } else if line.is_empty() {
+                ";".to_string()
+            } else {
+                format!("; {}", line)
+            };
+            let mut colored = String::new();
+            write_colored(&mut colored, &self.cfg, TokenKind::Comment, |out| {
+                out.write_str(&comment)
+            })?;
+            if self.cfg.pretty {
+                self.push_node(Node::Comment(colored, style))?;
+                continue;
+            }
+            match style {
+                CommentStyle::EndOfLine => self.start_text()?,
+                CommentStyle::Standalone if self.line_started() => self.write_line_break()?,
+                CommentStyle::Standalone => {}
+            }
+            self.write(&colored)?;
+            self.comment_end = self.position();
+            self.write_line_break()?;
+            self.prev = PrinterState::Group;
+        }
+        Ok(())
+    }
+
+    // in pretty mode, add a node to the current group, or lay it out on its own line when at the top level
+    fn push_node(&mut self, node: Node<K>) -> fmt::Result {
+        let node = match self.mapping.take() {
+            Some((key, depth)) if depth == self.groups.len() => Node::Mapped(key, Box::new(node)),
+            mapping => {
+                self.mapping = mapping;
+                node
+            }
+        };
+        match self.groups.last_mut() {
+            Some((_, _, children)) => {
+                children.push(node);
+                Ok(())
+            }
+            None => {
+                if let Node::Comment(_, CommentStyle::EndOfLine) = node.inner() {
+                    if self.line_started() {
+                        self.write(" ")?;
+                    }
+                } else if self.line_started() {
+                    self.write_line_break()?;
+                }
+                // the prefix is printed first, so that the layout starts after it
+                self.write_prefix()?;
+                let start = self.position();
+                let mut out = ColumnWriter {
+                    out: &mut self.sink,
+                    line: &mut self.line,
+                    col: &mut self.col,
+                    offset: &mut self.offset,
+                    newlines: &mut self.newlines,
+                    prefix: &self.cfg.line_prefix,
+                    pending_prefix: &mut self.pending_prefix,
+                    tab_width: self.cfg.tab_width,
+                };
+                pretty::layout(&self.cfg, &node, &mut out, start, &mut self.source_map)?;
+                if let Node::Comment(_, _) = node.inner() {
+                    self.write_line_break()?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    // separate the text from any previous text
+    fn start_text(&mut self) -> fmt::Result {
+        if self.prev == PrinterState::Text {
+            self.write(" ")?;
+        }
+        self.prev = PrinterState::Text;
+        Ok(())
+    }
+
+    // check if something is printed on the current line
+    fn line_started(&self) -> bool {
+        self.newlines == 0
+    }
+
+    // end the line, the next line starting with the line prefix
+    fn write_line_break(&mut self) -> fmt::Result {
+        let ending = self.cfg.line_ending.as_str();
+        self.write(ending)?;
+        self.pending_prefix = true;
+        Ok(())
+    }
+
+    fn write_prefix(&mut self) -> fmt::Result {
+        if self.pending_prefix {
+            self.pending_prefix = false;
+            let mut out = ColumnWriter {
+                out: &mut self.sink,
+                line: &mut self.line,
+                col: &mut self.col,
+                offset: &mut self.offset,
+                newlines: &mut self.newlines,
+                prefix: "",
+                pending_prefix: &mut self.pending_prefix,
+                tab_width: self.cfg.tab_width,
+            };
+            out.write_str(&self.cfg.line_prefix)?;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, s: &str) -> fmt::Result {
+        self.out().write_str(s)
+    }
+
+    fn write_char(&mut self, c: char) -> fmt::Result {
+        self.out().write_char(c)
+    }
+
+    fn write_delimiter(&mut self, c: char) -> fmt::Result {
+        let (mut out, cfg) = self.out_with_cfg();
+        write_delimiter(&mut out, cfg, c)
+    }
+
+    fn out(&mut self) -> ColumnWriter<'_, S> {
+        self.out_with_cfg().0
+    }
+
+    // the writer to the sink, with the config borrowed alongside it
+    fn out_with_cfg(&mut self) -> (ColumnWriter<'_, S>, &PrintConfig) {
+        let out = ColumnWriter {
+            out: &mut self.sink,
+            line: &mut self.line,
+            col: &mut self.col,
+            offset: &mut self.offset,
+            newlines: &mut self.newlines,
+            prefix: &self.cfg.line_prefix,
+            pending_prefix: &mut self.pending_prefix,
+            tab_width: self.cfg.tab_width,
+        };
+        (out, &self.cfg)
+    }
+}
+
+// write to the sink, keeping track of the position and of the newlines ending the output,
+// and printing the pending line prefix before the first character of a line
+struct ColumnWriter<'a, W> {
+    out: &'a mut W,
+    line: &'a mut usize,
+    col: &'a mut usize,
+    offset: &'a mut usize,
+    newlines: &'a mut usize,
+    prefix: &'a str,
+    pending_prefix: &'a mut bool,
+    tab_width: usize,
+}
+
+impl<'a, W: fmt::Write> fmt::Write for ColumnWriter<'a, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if *self.pending_prefix && !s.is_empty() && !s.starts_with(['\r', '\n']) {
+            *self.pending_prefix = false;
+            let prefix = self.prefix;
+            self.write_str(prefix)?;
+        }
+        match s.rfind('\n') {
+            None => *self.col = advance_col(*self.col, s, self.tab_width),
+            Some(i) => {
+                *self.line += s.matches('\n').count();
+                *self.col = advance_col(0, &s[i + 1..], self.tab_width);
+            }
+        }
+        let rest = s.trim_end_matches(['\r', '\n']);
+        let trailing = s[rest.len()..].matches('\n').count();
+        if rest.is_empty() {
+            *self.newlines = self.newlines.saturating_add(trailing);
+        } else {
+            *self.newlines = trailing;
+        }
+        *self.offset += s.len();
+        self.out.write_str(s)
+    }
+}
+
+// column of the start of the output, which is measured as if the first line started with the prefix
+// when the prefix is not printed on the first line
+fn first_line_col(cfg: &PrintConfig) -> usize {
+    if cfg.prefix_first_line {
+        0
+    } else {
+        advance_col(0, &cfg.line_prefix, cfg.tab_width)
+    }
+}
+
+fn check_decimal(v: f64) -> Result<(), PrintNumError> {
+    if !v.is_finite() {
+        Err(PrintNumError::NotFinite)
+    } else if v.is_sign_negative() && v != 0.0 {
+        Err(PrintNumError::Negative)
+    } else {
+        Ok(())
+    }
 }
 
-impl Printer {
-    /// Create a new group
-    pub fn open(&mut self, grp: GroupKind) {
-        if self.prev == PrinterState::Text {
-            self.buf.push(' ');
+// write a formatted float in the decimal literal form, trimming the trailing zeros of the fractional part
+fn decimal_literal<W: fmt::Write + ?Sized>(out: &mut W, formatted: &str) -> fmt::Result {
+    let formatted = formatted.trim_start_matches('-');
+    match formatted.split_once('.') {
+        None => write!(out, "{}.0", formatted),
+        Some((integral, fractional)) => {
+            let fractional = fractional.trim_end_matches('0');
+            let fractional = if fractional.is_empty() {
+                "0"
+            } else {
+                fractional
+            };
+            write!(out, "{}.{}", integral, fractional)
         }
-        let c = match grp {
-            GroupKind::Paren => '(',
-            GroupKind::Bracket => '[',
-            GroupKind::Brace => '{',
-        };
-        self.prev = PrinterState::Group;
-        self.buf.push(c);
     }
+}
 
-    /// Close a group
-    pub fn close(&mut self, grp: GroupKind) {
-        let c = match grp {
-            GroupKind::Paren => ')',
-            GroupKind::Bracket => ']',
-            GroupKind::Brace => '}',
-        };
-        self.prev = PrinterState::Group;
-        self.buf.push(c);
+// round the shortest representation of a non negative float half up to exactly `scale` fractional digits
+fn scaled_digits(formatted: &str, scale: usize) -> String {
+    let formatted = formatted.trim_start_matches('-');
+    let (integral, fractional) = formatted.split_once('.').unwrap_or((formatted, ""));
+    let mut digits: Vec<u8> = integral.bytes().collect();
+    digits.extend(
+        fractional
+            .bytes()
+            .chain(core::iter::repeat(b'0'))
+            .take(scale),
+    );
+    if fractional.as_bytes().get(scale).is_some_and(|d| *d >= b'5') {
+        // propagate the carry, adding a leading digit if all the digits are nines
+        match digits.iter().rposition(|d| *d != b'9') {
+            Some(i) => {
+                digits[i] += 1;
+                digits[i + 1..].fill(b'0');
+            }
+            None => {
+                digits.fill(b'0');
+                digits.insert(0, b'1');
+            }
+        }
     }
+    let point = digits.len() - scale;
+    let digits = String::from_utf8(digits).expect("ascii digits");
+    format!("{}.{}", &digits[..point], &digits[point..])
+}
 
-    /// Add text
-    pub fn text(&mut self, s: &str) {
-        if self.prev == PrinterState::Text {
-            self.buf.push(' ');
+// write a number with its base prefix, optionally grouping the digits with separators
+fn number_literal<W: fmt::Write + ?Sized>(
+    out: &mut W,
+    base: ANumBase,
+    digits: &str,
+    separators: bool,
+) -> fmt::Result {
+    out.write_str(base.prefix())?;
+    let group = match base {
+        ANumBase::Decimal => 3,
+        ANumBase::Binary | ANumBase::Hexadecimal => 4,
+    };
+    for (i, c) in digits.chars().enumerate() {
+        if separators && i > 0 && (digits.len() - i).is_multiple_of(group) {
+            out.write_char('_')?;
         }
-        self.prev = PrinterState::Text;
-        self.buf.push_str(s)
+        out.write_char(c)?;
     }
+    Ok(())
+}
 
-    pub fn to_string(self) -> String {
-        self.buf
-    }
+// write the literal syntax of an atom
+fn atom_literal<W: fmt::Write + ?Sized>(out: &mut W, atom: &Atom) -> fmt::Result {
+    write!(out, "{}", atom)
+}
+
+/// Print an element into a new String
+pub fn to_string(el: &Element) -> String {
+    let mut p = Printer::default();
+    p.element(el);
+    p.to_string()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::loc::{Span, Spanned};
+    use crate::parser::Parser;
+    use crate::pretty::IndentRule;
+    use crate::tests::Rng;
+    use crate::tokenizer::{Token, Tokenizer};
 
     #[test]
     fn t1() {
@@ -104,4 +2138,1312 @@ mod tests {
         let out = p.to_string();
         assert_eq!(out, "(let x = (+ 1 0xabc))");
     }
+
+    #[test]
+    fn atoms() {
+        let data = r#"hello "a \"b\" c" #0aFF# 0x1_ab 0b101 12 3.14 1."#;
+        let mut tokenizer = Tokenizer::new(data);
+        let mut p = Printer::default();
+        p.open(GroupKind::Paren);
+        while let Some(tok) = tokenizer.next().expect("valid token") {
+            match tok.inner {
+                Token::Atom(atom) => p.atom(&atom),
+                _ => panic!("unexpected token"),
+            }
+        }
+        p.close(GroupKind::Paren);
+        let out = p.to_string();
+        assert_eq!(out, format!("({})", data));
+    }
+
+    fn pretty_print(data: &str, cfg: PrintConfig) -> String {
+        let mut parser = Parser::new(data);
+        let mut p = Printer::new_with_config(cfg.pretty(true));
+        while let Some(el) = parser.next().expect("parse") {
+            p.element(&el.inner)
+        }
+        p.to_string()
+    }
+
+    #[test]
+    fn pretty_nested() {
+        let data = "(define (f x) (if (zero? x) 1 (* x 2))) (define y [1 2 3])";
+        let out = pretty_print(data, PrintConfig::default());
+        assert_eq!(
+            out,
+            "(define (f x) (if (zero? x) 1 (* x 2)))\n(define y [1 2 3])"
+        );
+        let out = pretty_print(data, PrintConfig::default().max_width(30));
+        assert_eq!(
+            out,
+            "(define\n  (f x)\n  (if (zero? x) 1 (* x 2)))\n(define y [1 2 3])"
+        );
+        let out = pretty_print(
+            "(a (b c) (d (e)))",
+            PrintConfig::default().indent(4).max_width(0),
+        );
+        assert_eq!(out, "(a\n    (b\n        c)\n    (d\n        (e)))");
+    }
+
+    #[test]
+    fn pretty_width_inner_most() {
+        let data = r#"(config (name "server") (ports [80 443 8080 8443 9000 9443]))"#;
+        let out = pretty_print(data, PrintConfig::default().max_width(38));
+        assert_eq!(
+            out,
+            "(config\n  (name \"server\")\n  (ports\n    [80 443 8080 8443 9000 9443]))"
+        );
+        let out = pretty_print(data, PrintConfig::default().max_width(33));
+        assert_eq!(
+            out,
+            "(config\n  (name \"server\")\n  (ports\n    [80 443 8080 8443 9000\n     9443]))"
+        );
+        let out = pretty_print(data, PrintConfig::default().max_width(33).fill_atoms(false));
+        assert_eq!(
+            out,
+            "(config\n  (name \"server\")\n  (ports\n    [80\n      443\n      8080\n      8443\n      9000\n      9443]))"
+        );
+    }
+
+    #[test]
+    fn pretty_width_everything() {
+        let data = r#"(a (b "a long string that never split" #00112233445566778899#))"#;
+        let out = pretty_print(data, PrintConfig::default().max_width(10));
+        assert_eq!(
+            out,
+            "(a\n  (b\n    \"a long string that never split\"\n    #00112233445566778899#))"
+        );
+    }
+
+    #[test]
+    fn pretty_indent_rules() {
+        let data = "(define (f x) (let ((y (* x 2))) (if (zero? x) (lambda (z) (+ y z)) y)))";
+        let cfg = PrintConfig::default()
+            .max_width(0)
+            .indent_rules(IndentRules::lisp());
+        let out = pretty_print(data, cfg);
+        assert_eq!(
+            out,
+            "(define (f\n          x)\n  (let ((y\n          (*\n            x\n            2)))\n    (if (zero?\n          x)\n        (lambda (z)\n          (+\n            y\n            z))\n      y)))"
+        );
+        // the then branch of an `if` is indented more than the else branches
+        let cfg = PrintConfig::default()
+            .max_width(30)
+            .indent_rules(IndentRules::lisp());
+        let data = "(if (< n 2) n (+ (fib (- n 1)) (fib (- n 2)))) (if c ; then\n a b d)";
+        assert_eq!(
+            pretty_print(data, cfg),
+            "(if (< n 2)\n    n\n  (+\n    (fib (- n 1))\n    (fib (- n 2))))\n(if c\n    ; then\n    a\n  b\n  d)"
+        );
+        let cfg = PrintConfig::default().max_width(30).indent_rules(
+            IndentRules::lisp()
+                .rule("f", IndentRule::new(2, 4))
+                .rule("g", IndentRule::new(3, 4)),
+        );
+        let out = pretty_print("(f a b c d e f g h i j k l m n o p) (g a ; b\n c d)", cfg);
+        assert_eq!(
+            out,
+            "(f a b\n    c\n    d\n    e\n    f\n    g\n    h\n    i\n    j\n    k\n    l\n    m\n    n\n    o\n    p)\n(g a\n    ; b\n    c\n    d)"
+        );
+    }
+
+    #[test]
+    fn pretty_comments() {
+        let data = "; header\n(a (b) ; trailing\n)";
+        let out = pretty_print(data, PrintConfig::default());
+        assert_eq!(out, "; header\n(a\n  (b)\n  ; trailing\n)");
+    }
+
+    #[test]
+    fn fmt_writer() {
+        struct Wrapper<'a>(&'a Element<'a>);
+        impl<'a> fmt::Display for Wrapper<'a> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let mut p = Printer::with_writer(f);
+                p.element(self.0)
+            }
+        }
+
+        let mut parser = Parser::new("(a [b 0x10] \"c\")");
+        let el = parser.next().expect("parse").expect("element");
+        assert_eq!(format!("{}", Wrapper(&el.inner)), "(a [b 0x10]\"c\")");
+
+        let mut out = String::from("prefix: ");
+        let mut p = Printer::with_writer_and_config(&mut out, PrintConfig::default().pretty(true));
+        p.element(&el.inner).expect("printing");
+        p.text("d").expect("printing");
+        assert_eq!(out, "prefix: (a [b 0x10] \"c\")\nd");
+    }
+
+    #[test]
+    fn fmt_writer_error() {
+        struct Failing;
+        impl fmt::Write for Failing {
+            fn write_str(&mut self, _: &str) -> fmt::Result {
+                Err(fmt::Error)
+            }
+        }
+        let mut p = Printer::with_writer(Failing);
+        assert_eq!(p.text("a"), Err(fmt::Error));
+    }
+
+    fn synthetic_tree(p: &mut Printer<IoWriter<Vec<u8>>>, depth: usize) -> io::Result<()> {
+        p.open(GroupKind::Paren)?;
+        p.text("node")?;
+        for i in 0..4 {
+            if depth > 0 {
+                synthetic_tree(p, depth - 1)?;
+            } else {
+                p.text(&format!("{}", i))?;
+            }
+        }
+        p.close(GroupKind::Paren)
+    }
+
+    #[test]
+    fn io_writer() {
+        for cfg in [PrintConfig::default(), PrintConfig::default().pretty(true)] {
+            let mut p = Printer::with_io_writer_and_config(Vec::new(), cfg);
+            for _ in 0..10 {
+                synthetic_tree(&mut p, 5).expect("printing");
+            }
+            p.flush().expect("flush");
+            let out = String::from_utf8(p.into_writer()).expect("utf8");
+
+            let mut parser = Parser::new(&out);
+            let mut nb = 0;
+            while let Some(el) = parser.next().expect("parse") {
+                let elements = el.paren().expect("paren");
+                assert_eq!(elements.len(), 5);
+                nb += 1;
+            }
+            assert_eq!(nb, 10);
+        }
+    }
+
+    #[test]
+    fn io_writer_error() {
+        let mut buf = [0u8; 4];
+        let mut p = Printer::with_io_writer(&mut buf[..]);
+        p.text("abc").expect("fit in the buffer");
+        let err = p.text("def").expect_err("buffer full");
+        assert_eq!(err.kind(), io::ErrorKind::WriteZero);
+    }
+
+    #[test]
+    fn group_scope() {
+        let mut manual = Printer::default();
+        manual.text("a");
+        manual.open(GroupKind::Paren);
+        manual.text("b");
+        manual.open(GroupKind::Brace);
+        manual.close(GroupKind::Brace);
+        manual.close(GroupKind::Paren);
+        manual.text("c");
+
+        let mut scoped = Printer::default();
+        scoped.text("a");
+        scoped.group(GroupKind::Paren, |p| {
+            p.text("b");
+            p.group(GroupKind::Brace, |_| {})
+        });
+        scoped.text("c");
+        assert_eq!(scoped.to_string(), manual.to_string());
+
+        let mut out = String::new();
+        let mut p = Printer::with_writer(&mut out);
+        let r = p.group(GroupKind::Bracket, |p| {
+            p.text("x")?;
+            Err(fmt::Error)
+        });
+        assert_eq!(r, Err(fmt::Error));
+        assert_eq!(out, "[x]");
+    }
+
+    #[test]
+    fn comments() {
+        let mut p = Printer::default();
+        p.group(GroupKind::Paren, |p| {
+            p.text("a");
+            p.comment_with_style("end of line", CommentStyle::EndOfLine);
+            p.text("b");
+            p.comment(";; standalone\nsecond line");
+            p.text("c");
+        });
+        let out = p.to_string();
+        assert_eq!(out, "(a ; end of line\nb\n;; standalone\n; second line\nc)");
+        let reparsed = Parser::new(&out).next().expect("parse").expect("element");
+        assert_eq!(reparsed.paren().expect("paren").len(), 6);
+
+        let mut p = Printer::new_with_config(PrintConfig::default().pretty(true));
+        p.comment("header");
+        p.group(GroupKind::Paren, |p| {
+            p.comment("first");
+            p.text("a");
+            p.comment_with_style("about a", CommentStyle::EndOfLine);
+            p.text("b");
+        });
+        p.comment_with_style("after", CommentStyle::EndOfLine);
+        assert_eq!(
+            p.to_string(),
+            "; header\n(\n  ; first\n  a ; about a\n  b) ; after\n"
+        );
+    }
+
+    fn parse_string_atom(s: &str) -> String {
+        let mut tokenizer = Tokenizer::new(s);
+        let tok = tokenizer.next().expect("valid token").expect("token");
+        assert!(tokenizer.next().expect("valid token").is_none());
+        match tok.inner {
+            Token::Atom(Atom::String(astr)) => {
+                astr.try_decoded().expect("valid escapes").into_owned()
+            }
+            _ => panic!("not a string: {}", s),
+        }
+    }
+
+    #[test]
+    fn string_escapes() {
+        let mut p = Printer::default();
+        p.string("a \"quoted\" \\ line\n\ttab\u{1b}\u{85}é");
+        assert_eq!(p.to_string(), r#""a \"quoted\" \\ line\n\ttab\x1b\u{85}é""#);
+
+        let cfg = PrintConfig::default()
+            .escape_whitespace(false)
+            .control_escape(ControlEscape::Unicode);
+        let mut p = Printer::new_with_config(cfg);
+        p.string("line\n\t\u{0}");
+        assert_eq!(p.to_string(), "\"line\n\t\\u{0}\"");
+    }
+
+    #[test]
+    fn string_roundtrip() {
+        let alphabet = [
+            'a', 'z', ' ', '"', '\\', '\n', '\t', '\r', '\0', '\u{7f}', 'é', '😀', ';', ')',
+        ];
+        let cfgs = [
+            PrintConfig::default(),
+            PrintConfig::default().escape_whitespace(false),
+            PrintConfig::default().control_escape(ControlEscape::Unicode),
+            PrintConfig::default().ascii_strings(true),
+        ];
+        let mut rng = Rng(0x5eed);
+        for _ in 0..1000 {
+            let s = rng.string(&alphabet, 20);
+            for cfg in cfgs.iter() {
+                let mut p = Printer::new_with_config(cfg.clone());
+                p.string(&s);
+                assert_eq!(parse_string_atom(&p.to_string()), s);
+            }
+        }
+    }
+
+    #[test]
+    fn ascii_strings() {
+        let cfg = PrintConfig::default().ascii_strings(true);
+        let cases = [
+            ("plain ~", r#""plain ~""#),
+            ("é", r#""\u{e9}""#),
+            // combining acute accent
+            ("e\u{301}", r#""e\u{301}""#),
+            ("😀 \u{10ffff}", r#""\u{1f600} \u{10ffff}""#),
+            ("\u{7f}\u{85}\u{feff}", r#""\x7f\u{85}\u{feff}""#),
+        ];
+        for (s, expected) in cases {
+            let mut p = Printer::new_with_config(cfg.clone());
+            p.string(s);
+            let out = p.to_string();
+            assert_eq!(out, expected);
+            assert!(out.is_ascii());
+            assert_eq!(parse_string_atom(&out), s);
+        }
+        let alphabet: Vec<char> = "aé😀\u{301}\u{10000}\u{10ffff}\u{85}\"\\\n"
+            .chars()
+            .collect();
+        let mut rng = Rng(154);
+        for _ in 0..500 {
+            let s = rng.string(&alphabet, 16);
+            let mut p =
+                Printer::new_with_config(cfg.clone().control_escape(ControlEscape::Unicode));
+            p.string(&s);
+            let out = p.to_string();
+            assert!(out.is_ascii(), "{:?}", out);
+            assert_eq!(parse_string_atom(&out), s);
+        }
+    }
+
+    fn parse_bytes_atom(s: &str) -> String {
+        let mut tokenizer = Tokenizer::new(s);
+        let tok = tokenizer.next().expect("valid token").expect("token");
+        match tok.inner {
+            Token::Atom(Atom::Bytes(bytes)) => bytes.0.to_string(),
+            _ => panic!("not bytes: {}", s),
+        }
+    }
+
+    #[test]
+    fn bytes() {
+        let data = [0x00, 0x1f, 0xab, 0xcd, 0xef, 0x80, 0x7f];
+        let cases = [
+            (PrintConfig::default(), "#001fabcdef807f#"),
+            (
+                PrintConfig::default().bytes_uppercase(true),
+                "#001FABCDEF807F#",
+            ),
+            (PrintConfig::default().bytes_group(3), "#001fab_cdef80_7f#"),
+            (
+                PrintConfig::default().bytes_group(1),
+                "#00_1f_ab_cd_ef_80_7f#",
+            ),
+        ];
+        for (cfg, expected) in cases {
+            let mut p = Printer::new_with_config(cfg);
+            p.bytes(&data);
+            let out = p.to_string();
+            assert_eq!(out, expected);
+            assert_eq!(parse_bytes_atom(&out), &expected[1..expected.len() - 1]);
+        }
+
+        let mut p = Printer::new_with_config(PrintConfig::default().bytes_group(2));
+        p.bytes(&[]);
+        let out = p.to_string();
+        assert_eq!(out, "##");
+        assert_eq!(parse_bytes_atom(&out), "");
+    }
+
+    fn parse_number_atom(s: &str) -> (ANumBase, u128) {
+        let mut tokenizer = Tokenizer::new(s);
+        let tok = tokenizer.next().expect("valid token").expect("token");
+        assert!(tokenizer.next().expect("valid token").is_none());
+        match tok.inner {
+            Token::Atom(Atom::Integral(num)) => (num.base, num.to_u128().expect("u128")),
+            _ => panic!("not a number: {}", s),
+        }
+    }
+
+    #[test]
+    fn uint() {
+        let cases = [
+            (ANumBase::Decimal, false, 1234567, "1234567"),
+            (ANumBase::Decimal, true, 1234567, "1_234_567"),
+            (ANumBase::Decimal, true, 123, "123"),
+            (ANumBase::Hexadecimal, false, 255, "0xff"),
+            (ANumBase::Hexadecimal, true, 0xdeadbeef1, "0xd_eadb_eef1"),
+            (ANumBase::Binary, true, 0b1_0110_1001, "0b1_0110_1001"),
+            (ANumBase::Binary, false, 0, "0b0"),
+        ];
+        for (base, separators, v, expected) in cases {
+            let mut p =
+                Printer::new_with_config(PrintConfig::default().number_separators(separators));
+            p.uint_with_base(v, base);
+            assert_eq!(p.to_string(), expected);
+        }
+        let mut p = Printer::default();
+        p.text("x");
+        p.uint(1);
+        assert_eq!(p.to_string(), "x 1");
+    }
+
+    #[test]
+    fn uint_roundtrip() {
+        let mut rng = Rng(0xcafe);
+        let values = (0..200u32)
+            .map(|i| 1u128 << (i % 128))
+            .chain((0..500).map(|i| (rng.next() as u128) >> (i % 64)))
+            .chain([0, 1, u64::MAX as u128, u128::MAX]);
+        for v in values {
+            for base in [ANumBase::Binary, ANumBase::Decimal, ANumBase::Hexadecimal] {
+                for separators in [false, true] {
+                    let cfg = PrintConfig::default().number_separators(separators);
+                    let mut p = Printer::new_with_config(cfg);
+                    p.uint_with_base(v, base);
+                    assert_eq!(parse_number_atom(&p.to_string()), (base, v));
+                }
+            }
+        }
+    }
+
+    fn parse_decimal_atom(s: &str) -> f64 {
+        let mut tokenizer = Tokenizer::new(s);
+        let tok = tokenizer.next().expect("valid token").expect("token");
+        assert!(tokenizer.next().expect("valid token").is_none());
+        match tok.inner {
+            Token::Atom(Atom::Decimal(dec)) => format!("{}.{}", dec.integral(), dec.fractional())
+                .parse()
+                .expect("f64"),
+            _ => panic!("not a decimal: {}", s),
+        }
+    }
+
+    #[test]
+    fn decimal() {
+        let cases = [
+            (0.0, "0.0"),
+            (-0.0, "0.0"),
+            (1.0, "1.0"),
+            (3.25, "3.25"),
+            (0.1, "0.1"),
+            (1e21, "1000000000000000000000.0"),
+            (1.5e-7, "0.00000015"),
+        ];
+        for (v, expected) in cases {
+            let mut p = Printer::default();
+            p.decimal(v).expect("finite");
+            assert_eq!(p.to_string(), expected);
+        }
+
+        let mut p = Printer::default();
+        p.decimal_with(1.23456, 2).expect("finite");
+        p.decimal_with(2.5, 3).expect("finite");
+        p.decimal_with(7.0, 0).expect("finite");
+        assert_eq!(p.to_string(), "1.23 2.5 7.0");
+
+        let mut p = Printer::default();
+        p.decimal_scaled(1.5, 2).expect("finite");
+        p.decimal_scaled(2.0, 3).expect("finite");
+        p.decimal_scaled(7.25, 1).expect("finite");
+        p.decimal_scaled(-0.0, 1).expect("finite");
+        p.decimal_scaled(9.996, 2).expect("finite");
+        p.decimal_scaled(0.125, 2).expect("finite");
+        p.decimal_scaled(2.675, 2).expect("finite");
+        p.decimal_scaled(99.95, 1).expect("finite");
+        p.decimal_scaled(1e21, 1).expect("finite");
+        assert_eq!(p.decimal_scaled(7.25, 0), Err(PrintNumError::ZeroScale));
+        assert_eq!(
+            p.to_string(),
+            "1.50 2.000 7.3 0.0 10.00 0.13 2.68 100.0 1000000000000000000000.0"
+        );
+
+        let mut p = Printer::default();
+        assert_eq!(p.decimal(f64::NAN), Err(PrintNumError::NotFinite));
+        assert_eq!(p.decimal(f64::INFINITY), Err(PrintNumError::NotFinite));
+        assert_eq!(p.decimal_with(-1.5, 2), Err(PrintNumError::Negative));
+        assert_eq!(p.to_string(), "");
+    }
+
+    #[test]
+    fn decimal_roundtrip() {
+        let mut rng = Rng(0xdec1);
+        let mut values: Vec<f64> = (0..2000).map(|_| f64::from_bits(rng.next() >> 2)).collect();
+        for i in 0..2000 {
+            values.push((rng.next() % 1_000_000) as f64 / 10f64.powi(i % 12));
+        }
+        values.extend([f64::MIN_POSITIVE, f64::MAX, f64::EPSILON, 5e-324]);
+        for v in values.into_iter().filter(|v| v.is_finite()) {
+            let mut p = Printer::default();
+            p.decimal(v).expect("finite");
+            assert_eq!(parse_decimal_atom(&p.to_string()), v);
+        }
+    }
+
+    #[test]
+    fn element_with_comment() {
+        let mut parser = Parser::new("(a ; comment\n b [c {}])");
+        let el = parser.next().expect("parse").expect("element");
+        assert_eq!(to_string(&el.inner), "(a ; comment\nb [c {}])");
+    }
+
+    #[test]
+    fn newlines() {
+        let mut p = Printer::default();
+        p.blank_line();
+        p.text("a");
+        p.newline();
+        p.newline();
+        p.text("b");
+        p.blank_line();
+        p.blank_line();
+        p.group(GroupKind::Paren, |p| {
+            p.text("c");
+            p.newline();
+            p.text("d");
+        });
+        p.comment("e");
+        p.newline();
+        p.blank_line();
+        p.text("f");
+        assert_eq!(p.to_string(), "a\nb\n\n(c\nd)\n; e\n\nf");
+
+        let mut p = Printer::new_with_config(PrintConfig::default().pretty(true));
+        p.text("a");
+        p.blank_line();
+        p.group(GroupKind::Paren, |p| {
+            p.text("if");
+            p.newline();
+            p.newline();
+            p.text("c");
+            p.blank_line();
+            p.text("d");
+            p.newline();
+        });
+        p.newline();
+        p.text("e");
+        assert_eq!(p.to_string(), "a\n\n(if\n  c\n\n  d\n)\ne");
+    }
+
+    #[test]
+    fn close_checks() {
+        for cfg in [PrintConfig::default(), PrintConfig::default().pretty(true)] {
+            let mut p = Printer::new_with_config(cfg);
+            assert_eq!(
+                p.try_close(GroupKind::Paren),
+                Err(PrinterError::NoOpenGroup(GroupKind::Paren))
+            );
+            p.open(GroupKind::Paren);
+            p.text("a");
+            p.open(GroupKind::Brace);
+            assert_eq!(p.depth(), 2);
+            assert_eq!(
+                p.try_close(GroupKind::Paren),
+                Err(PrinterError::MismatchedClose {
+                    open: GroupKind::Brace,
+                    close: GroupKind::Paren
+                })
+            );
+            p.element(&Element::Group(GroupKind::Bracket, Vec::new()));
+            assert_eq!(p.depth(), 2);
+            p.close(GroupKind::Brace);
+            p.try_close(GroupKind::Paren).expect("balanced");
+            assert_eq!(p.depth(), 0);
+            assert_eq!(p.to_string(), "(a {[]})");
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "closing `]` doesn't match the open `(`")]
+    fn close_mismatch_panics() {
+        let mut p = Printer::default();
+        p.open(GroupKind::Paren);
+        p.close(GroupKind::Bracket);
+    }
+
+    #[test]
+    fn finish() {
+        let mut p = Printer::default();
+        p.group(GroupKind::Paren, |p| p.text("a"));
+        assert_eq!(p.finish(), Ok("(a)".to_string()));
+
+        let mut p = Printer::new_with_config(PrintConfig::default().pretty(true));
+        p.open(GroupKind::Paren);
+        p.open(GroupKind::Bracket);
+        let err = p.finish().expect_err("unbalanced");
+        assert_eq!(err.open_groups(), &[GroupKind::Paren, GroupKind::Bracket]);
+        assert_eq!(err.to_string(), "2 group(s) still open: `(` `[`");
+
+        let mut p = Printer::with_io_writer(Vec::new());
+        p.group(GroupKind::Brace, |p| p.text("b"))
+            .expect("printing");
+        assert_eq!(p.finish().expect("balanced"), b"{b}");
+
+        let mut p = Printer::with_writer(String::new());
+        p.open(GroupKind::Brace).expect("printing");
+        assert!(p.finish().is_err());
+
+        // the rejected closes print nothing and close nothing, in both modes
+        for cfg in [PrintConfig::default(), PrintConfig::default().pretty(true)] {
+            let mut p = Printer::new_with_config(cfg.clone());
+            p.open(GroupKind::Paren);
+            p.text("a");
+            assert!(p.try_close(GroupKind::Bracket).is_err());
+            let err = p.finish().expect_err("unbalanced");
+            assert_eq!(err.open_groups(), &[GroupKind::Paren]);
+
+            let mut p = Printer::new_with_config(cfg);
+            assert!(p.try_close(GroupKind::Bracket).is_err());
+            p.group(GroupKind::Paren, |p| p.text("a"));
+            assert_eq!(p.finish(), Ok("(a)".to_string()));
+        }
+    }
+
+    #[test]
+    fn elements_joined() {
+        let mut parser = Parser::new("(record (a 1)(b [2]) c ; comment\n d)");
+        let el = parser.next().expect("parse").expect("element");
+        let fields = el.paren().expect("paren");
+        let fields = || fields.iter().map(|e| &e.inner);
+
+        let mut p = Printer::default();
+        p.elements_joined(fields().skip(1));
+        assert_eq!(p.to_string(), "(a 1) (b [2]) c ; comment\nd");
+
+        let mut p = Printer::default();
+        p.text("x");
+        p.group(GroupKind::Paren, |p| p.elements_joined(fields()));
+        p.elements_joined(fields().skip(3).take(1));
+        assert_eq!(p.to_string(), "x (record (a 1) (b [2]) c ; comment\nd) c");
+
+        let mut p = Printer::new_with_config(PrintConfig::default().pretty(true));
+        p.elements_joined(fields().skip(1));
+        assert_eq!(p.to_string(), "(a 1)\n(b [2])\nc\n; comment\nd");
+    }
+
+    #[test]
+    fn ident_check() {
+        let reject = PrintConfig::default().ident_check(IdentCheck::Reject);
+        let alphabet: Vec<char> = "ab1_-+ #;\"()[]{}\\|.\n".chars().collect();
+        let mut rng = Rng(0x1d3a_7c55_0f21_9b44);
+        for _ in 0..1000 {
+            let ident = rng.string(&alphabet, 6);
+            let atom = Atom::Ident(&ident);
+            let mut p = Printer::new_with_config(reject.clone());
+            match p.try_atom(&atom) {
+                Ok(()) => {
+                    let out = p.to_string();
+                    let mut parser = Parser::new(&out);
+                    let el = parser.next().expect("parse").expect("element");
+                    assert_eq!(el.atom(), Some(&atom), "ident {:?}", ident);
+                    assert!(parser.next().expect("parse").is_none());
+                }
+                Err(e) => {
+                    assert_eq!(e, PrinterError::InvalidIdent(ident.clone()));
+                    assert_eq!(p.to_string(), "");
+                    let unchecked = to_string(&Element::Atom(atom.clone()));
+                    let mut parser = Parser::new(&unchecked);
+                    let same = matches!(parser.next(), Ok(Some(el)) if el.atom() == Some(&atom))
+                        && matches!(parser.next(), Ok(None));
+                    assert!(!same, "valid ident {:?} rejected", ident);
+                }
+            }
+        }
+
+        let el = Element::Group(
+            GroupKind::Paren,
+            vec![Spanned {
+                span: Span::on_line(1, 1, 2),
+                inner: Element::Atom(Atom::Ident("(")),
+            }],
+        );
+        let mut p = Printer::new_with_config(reject.clone());
+        assert!(p.try_element(&el).is_err());
+        assert_eq!(p.depth(), 0);
+
+        let invalid = Err(PrinterError::InvalidIdent("(".to_string()));
+        let mut p = Printer::new_with_config(reject.clone());
+        assert_eq!(p.try_element_truncated(&el, 1, 1), invalid);
+        assert_eq!(p.try_element_mapped(0, &el), invalid);
+        assert_eq!(p.try_atom_mapped(1, &Atom::Ident("(")), invalid);
+        assert!(p.source_map().is_empty());
+        let a = Element::Atom(Atom::Ident("a"));
+        assert_eq!(p.try_elements_joined([&a, &el, &a]), invalid);
+        assert_eq!(p.to_string(), "a");
+
+        // the idents are checked against the tokenizer reading the output back
+        let no_bytes = reject.tokenizer_config(TokenizerConfig::default().support_bytes(false));
+        let mut p = Printer::new_with_config(no_bytes);
+        assert_eq!(p.try_atom(&Atom::Ident("#ff#")), Ok(()));
+        assert_eq!(p.to_string(), "#ff#");
+        let mut p =
+            Printer::new_with_config(PrintConfig::default().ident_check(IdentCheck::Reject));
+        assert!(p.try_atom(&Atom::Ident("#ff#")).is_err());
+    }
+
+    #[test]
+    fn layout_overrides() {
+        let cfg = PrintConfig::default().pretty(true).max_width(20);
+        let mut p = Printer::new_with_config(cfg);
+        p.broken(|p| {
+            p.group(GroupKind::Paren, |p| {
+                p.text("define");
+                p.text("x");
+                p.group(GroupKind::Paren, |p| p.text("f"));
+            })
+        });
+        p.group(GroupKind::Paren, |p| {
+            p.text("triples");
+            p.flat(|p| {
+                p.group(GroupKind::Bracket, |p| {
+                    p.text("100000");
+                    p.group(GroupKind::Bracket, |p| {
+                        p.text("200000");
+                        p.text("300000");
+                    });
+                });
+                p.group(GroupKind::Bracket, |p| {
+                    p.text("a");
+                    p.comment_with_style("c", CommentStyle::EndOfLine);
+                    p.group(GroupKind::Paren, |p| {
+                        p.text("b");
+                        p.broken(|p| p.group(GroupKind::Paren, |p| p.text("c d")));
+                    });
+                });
+            });
+            p.group(GroupKind::Bracket, |p| p.text("zzzzzzzzzzzzzzzzzzzzz"));
+        });
+        assert_eq!(
+            p.to_string(),
+            "(define\n  x\n  (f))\n(triples\n  [100000 [200000 300000]]\n  [a ; c\n    (b (c d))]\n  [zzzzzzzzzzzzzzzzzzzzz])"
+        );
+    }
+
+    #[test]
+    fn pretty_fill() {
+        let numbers: Vec<String> = (0..24).map(|i| (i * i * 7).to_string()).collect();
+        let data = format!("(data [{}])", numbers.join(" "));
+        let out = pretty_print(&data, PrintConfig::default().max_width(20));
+        assert_eq!(
+            out,
+            "(data
+  [0 7 28 63 112 175
+   252 343 448 567
+   700 847 1008 1183
+   1372 1575 1792
+   2023 2268 2527
+   2800 3087 3388
+   3703])"
+        );
+        let out = pretty_print(&data, PrintConfig::default().max_width(40));
+        assert_eq!(
+            out,
+            "(data
+  [0 7 28 63 112 175 252 343 448 567 700
+   847 1008 1183 1372 1575 1792 2023
+   2268 2527 2800 3087 3388 3703])"
+        );
+        let out = pretty_print(&data, PrintConfig::default().max_width(80));
+        assert_eq!(
+            out,
+            "(data
+  [0 7 28 63 112 175 252 343 448 567 700 847 1008 1183 1372 1575 1792 2023 2268
+   2527 2800 3087 3388 3703])"
+        );
+
+        let mut p = Printer::new_with_config(PrintConfig::default().pretty(true).max_width(16));
+        p.fill(|p| {
+            p.group(GroupKind::Paren, |p| {
+                for t in ["f", "a", "b", "c", "d"] {
+                    p.text(t)
+                }
+                p.comment_with_style("end", CommentStyle::EndOfLine);
+                p.group(GroupKind::Bracket, |p| p.text("e"));
+                p.group(GroupKind::Paren, |p| p.text("this-is-long"));
+            })
+        });
+        assert_eq!(p.to_string(), "(f a b c d ; end\n [e]\n (this-is-long))");
+    }
+
+    // slice of the text between 2 positions
+    fn span_slice(text: &str, span: Span) -> &str {
+        let offset = |pos: Position| {
+            let line_start: usize = text
+                .split_inclusive('\n')
+                .take(pos.line - 1)
+                .map(str::len)
+                .sum();
+            let line = &text[line_start..];
+            line_start
+                + line
+                    .char_indices()
+                    .nth(pos.col)
+                    .map_or(line.len(), |(i, _)| i)
+        };
+        &text[offset(span.start)..offset(span.end)]
+    }
+
+    #[test]
+    fn source_map() {
+        let data = "(define x 1) ; post\n(if (zero? x) [a \"b\\nc\" #00#] (f x y z w v u)) (g)";
+        let mut parser = Parser::new(data);
+        let mut elements = Vec::new();
+        while let Some(el) = parser.next().expect("parse") {
+            elements.push(el)
+        }
+        let cfgs = [
+            PrintConfig::default(),
+            PrintConfig::default().pretty(true),
+            PrintConfig::default().pretty(true).max_width(10),
+            PrintConfig::default()
+                .pretty(true)
+                .max_width(10)
+                .escape_whitespace(false)
+                .align_trailing_comments(true),
+        ];
+        for cfg in cfgs {
+            let mut p = Printer::new_with_config(cfg);
+            let mut expected = Vec::new();
+            for el in &elements {
+                // map the top level atoms and comments, and the elements of the top level groups
+                match el.group(GroupKind::Paren) {
+                    Some(children) => p.group(GroupKind::Paren, |p| {
+                        for child in children {
+                            p.element_mapped(expected.len(), &child.inner);
+                            expected.push(&child.inner);
+                        }
+                    }),
+                    None => {
+                        p.element_mapped(expected.len(), &el.inner);
+                        expected.push(&el.inner);
+                    }
+                }
+            }
+            let source_map = p.source_map().to_vec();
+            let out = p.finish().expect("balanced");
+            assert_eq!(source_map.len(), expected.len());
+            for (key, span) in source_map {
+                let text = span_slice(&out, span);
+                assert_eq!(&out[span.start.offset..span.end.offset], text);
+                match expected[key] {
+                    Element::Comment(comment) => assert_eq!(text, *comment),
+                    el => {
+                        let mut parser = Parser::new(text);
+                        let printed = parser.next().expect("parse").expect("element");
+                        assert!(printed.structural_eq(el), "{:?} in {:?}", text, out);
+                    }
+                }
+            }
+        }
+
+        // the keys can be of any type
+        let cfg = PrintConfig::default().pretty(true).max_width(6);
+        let mut p: Printer<String, String> = Printer::with_sink_keyed(String::new(), cfg);
+        p.group(GroupKind::Paren, |p| {
+            p.text("f");
+            p.element_mapped("x".to_string(), &Element::Atom(Atom::Ident("xxxx")));
+            p.atom_mapped("y".to_string(), &Atom::Ident("y"));
+        });
+        let source_map = p.source_map().to_vec();
+        let out = p.to_string();
+        assert_eq!(out, "(f\n  xxxx\n  y)");
+        let keys: Vec<_> = source_map
+            .iter()
+            .map(|(key, span)| (key.as_str(), span_slice(&out, *span)))
+            .collect();
+        assert_eq!(keys, [("x", "xxxx"), ("y", "y")]);
+    }
+
+    #[test]
+    fn clear() {
+        for cfg in [PrintConfig::default(), PrintConfig::default().pretty(true)] {
+            let mut p = Printer::new_with_config(cfg.clone());
+            p.text("a");
+            p.open(GroupKind::Paren);
+            p.flat(|p| p.open(GroupKind::Bracket));
+            p.element_mapped(0, &Element::Atom(Atom::Ident("b")));
+            let capacity = p.as_str().len();
+            p.clear();
+            assert_eq!(p.as_str(), "");
+            assert_eq!(p.depth(), 0);
+            assert!(p.source_map().is_empty());
+            assert!(p.sink.capacity() >= capacity);
+
+            p.text("c");
+            p.group(GroupKind::Paren, |p| p.text("d"));
+            let mut fresh = Printer::new_with_config(cfg);
+            fresh.text("c");
+            fresh.group(GroupKind::Paren, |p| p.text("d"));
+            assert_eq!(p.as_str(), fresh.as_str());
+        }
+    }
+
+    #[test]
+    fn line_prefix() {
+        let data = "(config (name \"server\") (ports [80 443]))";
+        let cfg = PrintConfig::default().max_width(24).initial_indent(4);
+        assert_eq!(
+            pretty_print(data, cfg.clone()),
+            "    (config\n      (name \"server\")\n      (ports [80 443]))"
+        );
+        assert_eq!(
+            pretty_print(data, cfg.prefix_first_line(false)),
+            "(config\n      (name \"server\")\n      (ports [80 443]))"
+        );
+        let cfg = PrintConfig::default()
+            .max_width(20)
+            .line_prefix("\t;")
+            .tab_width(4);
+        assert_eq!(
+            pretty_print(data, cfg),
+            "\t;(config\n\t;  (name\n\t;    \"server\")\n\t;  (ports\n\t;    [80 443]))"
+        );
+
+        let mut p = Printer::new_with_config(
+            PrintConfig::default()
+                .line_prefix("> ")
+                .escape_whitespace(false),
+        );
+        p.text("a");
+        p.string("b\nc");
+        p.blank_line();
+        p.comment("d");
+        p.text("e");
+        assert_eq!(p.to_string(), "> a \"b\nc\"\n\n> ; d\n> e");
+    }
+
+    #[test]
+    fn line_endings() {
+        let cfg = PrintConfig::default()
+            .line_ending(LineEnding::CrLf)
+            .ensure_trailing_newline(true);
+        let mut p = Printer::new_with_config(cfg.clone());
+        p.text("a");
+        p.comment("b");
+        p.blank_line();
+        p.blank_line();
+        p.text("c");
+        assert_eq!(p.to_string(), "a\r\n; b\r\n\r\nc\r\n");
+
+        let mut p = Printer::new_with_config(cfg.clone().pretty(true).max_width(4));
+        p.group(GroupKind::Paren, |p| {
+            p.text("d");
+            p.text("e");
+        });
+        p.blank_line();
+        p.blank_line();
+        assert_eq!(p.finish(), Ok("(d\r\n  e)\r\n".to_string()));
+
+        let p = Printer::new_with_config(cfg);
+        assert_eq!(p.to_string(), "");
+    }
+
+    #[test]
+    fn pretty_pairs() {
+        let data = r#"{name "server" port 8080 tls true (timeout 30) extra}"#;
+        let cfg = PrintConfig::default().pair_braces(true);
+        // the pairs are never split, whatever the width
+        for width in 20..60 {
+            let out = pretty_print(data, cfg.clone().max_width(width));
+            for line in out.lines() {
+                let words = line
+                    .trim_matches(|c| c == '{' || c == ' ' || c == '}')
+                    .split(' ')
+                    .count();
+                assert!(
+                    words % 2 == 0 || line.ends_with("extra}"),
+                    "{:?} at {}",
+                    out,
+                    width
+                );
+            }
+        }
+        assert_eq!(
+            pretty_print(data, cfg.clone().max_width(30)),
+            "{name \"server\" port 8080
+ tls true (timeout 30) extra}"
+        );
+        assert_eq!(
+            pretty_print(data, cfg.clone().max_width(20)),
+            "{name \"server\"
+ port 8080 tls true
+ (timeout 30) extra}"
+        );
+        assert_eq!(pretty_print(data, cfg.clone()), data);
+
+        // the comments don't count in the pairs
+        assert_eq!(
+            pretty_print("{a 1 ; one\n b 2 c 3}", cfg.clone()),
+            "{a 1\n ; one\n b 2 c 3}"
+        );
+
+        // opt-in for the other groups
+        let mut p = Printer::new_with_config(PrintConfig::default().pretty(true).max_width(12));
+        p.pairs(|p| {
+            p.group(GroupKind::Paren, |p| {
+                for (k, v) in [("a", "1"), ("bb", "22"), ("ccc", "333")] {
+                    p.text(k);
+                    p.text(v);
+                }
+            })
+        });
+        assert_eq!(p.to_string(), "(a 1 bb 22\n ccc 333)");
+    }
+
+    #[test]
+    #[cfg(feature = "bigint")]
+    fn biguint() {
+        let v = num_bigint::BigUint::from(1u8) << 255;
+        let mut p = Printer::default();
+        p.biguint(&v, ANumBase::Hexadecimal);
+        p.biguint(&v, ANumBase::Decimal);
+        p.biguint(&num_bigint::BigUint::default(), ANumBase::Binary);
+        let out = p.to_string();
+        let toks: Vec<_> = out.split(' ').collect();
+        assert_eq!(toks[0], format!("0x8{}", "0".repeat(63)));
+        assert_eq!(toks[1], v.to_string());
+        assert_eq!(toks[2], "0b0");
+
+        let mut tokenizer = Tokenizer::new(&out);
+        while let Some(tok) = tokenizer.next().expect("token") {
+            match tok.inner {
+                Token::Atom(Atom::Integral(num)) if num.base == ANumBase::Binary => {
+                    assert_eq!(num.to_biguint(), Some(num_bigint::BigUint::default()))
+                }
+                Token::Atom(Atom::Integral(num)) => {
+                    assert_eq!(num.to_biguint(), Some(v.clone()));
+                    assert_eq!(num.to_bigint(), Some(v.clone().into()));
+                }
+                t => panic!("unexpected {:?}", t),
+            }
+        }
+    }
+
+    #[test]
+    fn num_as() {
+        let bases = [ANumBase::Binary, ANumBase::Decimal, ANumBase::Hexadecimal];
+        let mut rng = Rng(150);
+        for _ in 0..200 {
+            let v = (rng.next() as u128) << 64 | rng.next() as u128;
+            let v = v >> (rng.next() % 128);
+            for from in bases {
+                let dat = match from {
+                    ANumBase::Binary => format!("{:b}", v),
+                    ANumBase::Decimal => format!("{}", v),
+                    ANumBase::Hexadecimal => format!("{:x}", v),
+                };
+                let num = ANum {
+                    base: from,
+                    dat: &dat,
+                };
+                for to in bases {
+                    let mut p = Printer::default();
+                    p.num_as(&num, to);
+                    let mut expected = Printer::default();
+                    expected.uint_with_base(v, to);
+                    assert_eq!(p.to_string(), expected.to_string());
+                }
+            }
+        }
+
+        // beyond u128, with leading zeros and separators
+        let big = format!("00_1{}", "0".repeat(40));
+        let num = ANum {
+            base: ANumBase::Hexadecimal,
+            dat: &big,
+        };
+        let mut p = Printer::new_with_config(PrintConfig::default().number_separators(true));
+        p.num_as(&num, ANumBase::Hexadecimal);
+        p.num_as(&num, ANumBase::Decimal);
+        assert_eq!(
+            p.to_string(),
+            "0x1_0000_0000_0000_0000_0000_0000_0000_0000_0000_0000 \
+             1_461_501_637_330_902_918_203_684_832_716_283_019_655_932_542_976"
+        );
+    }
+
+    #[test]
+    fn empty_groups() {
+        let cfg = PrintConfig::default()
+            .empty_group(GroupKind::Paren, " ")
+            .empty_group(GroupKind::Brace, "  ");
+        let data = "(f () [] {} (g ()) [()])";
+        assert_eq!(
+            pretty_print(data, cfg.clone().pretty(false)),
+            "(f ( ) [] {  } (g ( )) [( )])"
+        );
+        assert_eq!(
+            pretty_print(data, cfg.clone()),
+            "(f ( ) [] {  } (g ( )) [( )])"
+        );
+        assert_eq!(
+            pretty_print(data, cfg.clone().max_width(12)),
+            "(f
+  ( )
+  []
+  {  }
+  (g ( ))
+  [( )])"
+        );
+        // never broken, even when forced
+        let mut p = Printer::new_with_config(cfg.pretty(true).max_width(4));
+        p.broken(|p| p.group(GroupKind::Paren, |_| {}));
+        assert_eq!(p.to_string(), "( )");
+    }
+
+    #[test]
+    fn element_truncated() {
+        let data = r#"(root (a (b (c (d)))) [1 2 3 4 5 6] ; note
+ "s")"#;
+        let el = Parser::new(data).next().unwrap().unwrap();
+        let truncated = |cfg: PrintConfig, depth, children| {
+            let mut p = Printer::new_with_config(cfg);
+            p.element_truncated(&el.inner, depth, children);
+            p.to_string()
+        };
+        assert_eq!(truncated(PrintConfig::default(), 0, 10), "…");
+        assert_eq!(
+            truncated(PrintConfig::default(), 1, 10),
+            "(root … … ; note\n\"s\")"
+        );
+        assert_eq!(
+            truncated(PrintConfig::default(), 3, 2),
+            "(root (a (b …)) … +3 more)"
+        );
+        assert_eq!(
+            truncated(PrintConfig::default().pretty(true), 2, 4),
+            "(root
+  (a …)
+  [1 2 3 4 … +2 more]
+  ; note
+  … +1 more)"
+        );
+        // without limits, the whole element
+        assert_eq!(
+            truncated(PrintConfig::default(), usize::MAX, usize::MAX),
+            "(root (a (b (c (d)))) [1 2 3 4 5 6] ; note\n\"s\")"
+        );
+    }
+
+    #[test]
+    fn indent_tabs() {
+        let cfg = PrintConfig::default()
+            .indent_style(IndentStyle::Tabs)
+            .indent_rules(IndentRules::lisp());
+        let data = "(let (x 1) (list [10 20 30 40 50 60 70 80 90] (f x)))";
+        assert_eq!(
+            pretty_print(data, cfg.clone().max_width(30)),
+            "(let (x 1)
+\t(list
+\t\t[10 20 30 40
+\t\t 50 60 70 80
+\t\t 90]
+\t\t(f x)))"
+        );
+        // the tabs count as the tab width
+        assert_eq!(
+            pretty_print(data, cfg.clone().max_width(30).tab_width(2)),
+            "(let (x 1)
+\t(list
+\t\t[10 20 30 40 50 60 70 80
+\t\t 90]
+\t\t(f x)))"
+        );
+        for width in 10..60 {
+            let out = pretty_print(data, cfg.clone().max_width(width).tab_width(4));
+            for line in out.lines() {
+                let tabs = line.chars().take_while(|c| *c == '\t').count();
+                assert!(!line[tabs..].starts_with('\t'));
+                assert!(
+                    4 * tabs + line[tabs..].chars().count() <= width.max(4 * tabs + 7),
+                    "{:?} at {}",
+                    out,
+                    width
+                );
+            }
+        }
+
+        // the trailing comments are aligned with spaces, after the prefix
+        let mut p = Printer::new_with_config(
+            cfg.pretty(true)
+                .align_trailing_comments(true)
+                .line_prefix("> "),
+        );
+        p.broken(|p| {
+            p.group(GroupKind::Paren, |p| {
+                p.text("a");
+                p.text("bb");
+                p.comment_with_style("b", CommentStyle::EndOfLine);
+                p.text("c");
+                p.comment_with_style("c", CommentStyle::EndOfLine);
+            })
+        });
+        assert_eq!(p.to_string(), "> (a\n> \tbb ; b\n> \tc  ; c\n> )");
+    }
+
+    #[test]
+    fn pretty_table() {
+        let cfg = PrintConfig::default().tabular_groups(true).max_width(30);
+        let uniform = r#"(rows ((a 1 "x") (bb 22 "yy") (ccc (f 3) "")))"#;
+        assert_eq!(
+            pretty_print(uniform, cfg.clone()),
+            r#"(rows
+  ((a   1     "x")
+   (bb  22    "yy")
+   (ccc (f 3) "")))"#
+        );
+        // ragged rows are broken as usual
+        let ragged = r#"(rows ((a 1 "x") (bb 22) (ccc (f 3) "" 4)))"#;
+        assert_eq!(
+            pretty_print(ragged, cfg.clone()),
+            pretty_print(ragged, cfg.clone().tabular_groups(false))
+        );
+        assert_eq!(
+            pretty_print(ragged, cfg.clone()),
+            r#"(rows
+  ((a 1 "x")
+    (bb 22)
+    (ccc (f 3) "" 4)))"#
+        );
+        // so are the tables too wide
+        assert_eq!(
+            pretty_print(uniform, cfg.clone().max_width(20)),
+            r#"(rows
+  ((a 1 "x")
+    (bb 22 "yy")
+    (ccc (f 3) "")))"#
+        );
+        // and the groups which fit on one line
+        assert_eq!(pretty_print("((a 1) (b 2))", cfg.clone()), "((a 1) (b 2))");
+
+        // opt-in for a group
+        let mut p = Printer::new_with_config(PrintConfig::default().pretty(true).max_width(12));
+        p.table(|p| {
+            p.group(GroupKind::Bracket, |p| {
+                for (k, v) in [("a", 100), ("bbb", 2)] {
+                    p.group(GroupKind::Paren, |p| {
+                        p.text(k);
+                        p.uint(v);
+                    });
+                }
+            })
+        });
+        assert_eq!(p.to_string(), "[(a   100)\n (bbb 2)]");
+    }
+
+    #[test]
+    fn from_string() {
+        let buf = String::with_capacity(256);
+        let ptr = buf.as_ptr();
+        let mut p = Printer::from_string(buf);
+        p.text("a");
+        let mut buf = p.into_string();
+        buf.push('\n');
+        let mut p = Printer::from_string_with_config(buf, PrintConfig::default().pretty(true));
+        p.group(GroupKind::Paren, |p| p.text("b"));
+        let buf = p.into_string();
+        assert_eq!(buf, "a\n(b)");
+        assert_eq!(buf.as_ptr(), ptr);
+    }
+
+    #[cfg(feature = "color")]
+    #[test]
+    fn color() {
+        fn strip(s: &str) -> String {
+            let mut out = String::new();
+            let mut rest = s;
+            while let Some(i) = rest.find("\x1b[") {
+                out.push_str(&rest[..i]);
+                let end = rest[i..].find('m').expect("end of escape");
+                rest = &rest[i + end + 1..];
+            }
+            out.push_str(rest);
+            out
+        }
+
+        let colored = PrintConfig::default().color(Some(ColorScheme::default()));
+        let out = pretty_print("(f \"a\" #00# 1.5 ; c\n)", colored);
+        assert_eq!(
+            out,
+            "\x1b[2m(\x1b[0mf\n  \x1b[32m\"a\"\x1b[0m\n  \x1b[35m#00#\x1b[0m\n  \x1b[36m1.5\x1b[0m\n  \x1b[90m; c\x1b[0m\n\x1b[2m)\x1b[0m"
+        );
+
+        // the escapes don't change the layout
+        for width in [10, 20, 40, 80] {
+            for prog in [crate::tests::PROG1, crate::tests::PROG2] {
+                let cfg = PrintConfig::default()
+                    .max_width(width)
+                    .indent_rules(IndentRules::lisp());
+                let plain = pretty_print(prog, cfg.clone());
+                let scheme = ColorScheme::default().idents("1");
+                let out = pretty_print(prog, cfg.color(Some(scheme)));
+                assert_ne!(out, plain);
+                assert_eq!(strip(&out), plain);
+            }
+        }
+
+        let mut p =
+            Printer::new_with_config(PrintConfig::default().color(Some(ColorScheme::none())));
+        p.open(GroupKind::Bracket);
+        p.string("s");
+        p.comment_with_style("c", CommentStyle::EndOfLine);
+        p.close(GroupKind::Bracket);
+        assert_eq!(p.to_string(), "[\"s\" ; c\n]");
+    }
 }
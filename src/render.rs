@@ -0,0 +1,208 @@
+//! Rendering of a span of a source as a code frame, e.g. to report an error
+//!
+//! The lines of the span are printed with their line number in a gutter, and `^` markers
+//! under the characters of the span:
+//!
+//! ```text
+//! closing brackets `[]` while parentheses `()` are open
+//!  --> 2:2
+//!   |
+//! 2 |   (b c]
+//!   |   ^^^^^
+//! ```
+
+use super::loc::{LineIndex, Position, Span};
+use super::pretty::advance_col;
+use core::fmt::Write;
+
+// width of the tabs of the rendered lines
+const TAB_WIDTH: usize = 4;
+
+/// Render the lines of the source covered by the span with markers under the span, after the message
+///
+/// A span on multiple lines marks the end of its first line and the start of its last line,
+/// the lines in between being elided. An empty span, e.g. at the end of the source, is marked
+/// with one `^`. The tabs are expanded to the next multiple of 4 columns, for the markers to stay
+/// under the characters of the span.
+///
+/// ```
+/// use s_expr::{render_span, Position, Span};
+///
+/// let source = "(a\n  (b c]";
+/// let start = Position { line: 2, col: 2, offset: 5 };
+/// let end = Position { line: 2, col: 7, offset: 10 };
+/// let frame = render_span(source, Span { start, end }, "mismatch");
+/// assert_eq!(frame, "mismatch\n --> 2:2\n  |\n2 |   (b c]\n  |   ^^^^^\n");
+/// ```
+pub fn render_span(source: &str, span: Span, message: &str) -> String {
+    let index = LineIndex::new(source);
+    let start = normalize(&index, span.start);
+    let mut end = normalize(&index, span.end);
+    if end < start {
+        end = start;
+    }
+    // a span ending just after a newline ends on the previous line
+    if end.line > start.line && end.col == 0 {
+        let line = end.line - 1;
+        let col = index.line_text(line).map_or(0, |text| text.chars().count());
+        end = Position {
+            line,
+            col,
+            offset: end.offset - 1,
+        };
+    }
+
+    let width = end.line.to_string().len();
+    let mut out = String::new();
+    // writing to a String doesn't fail
+    let _ = writeln!(out, "{}", message);
+    let _ = writeln!(out, "{:w$}--> {}", "", start, w = width);
+    let _ = writeln!(out, "{:w$} |", "", w = width);
+    if start.line == end.line {
+        marked_line(
+            &mut out,
+            &index,
+            width,
+            start.line,
+            start.col,
+            Some(end.col),
+        );
+    } else {
+        marked_line(&mut out, &index, width, start.line, start.col, None);
+        if end.line > start.line + 1 {
+            let _ = writeln!(out, "{:w$} ...", "", w = width);
+        }
+        marked_line(&mut out, &index, width, end.line, 0, Some(end.col));
+    }
+    out
+}
+
+// the position in the source with the line and the column, the end of the source if not in the source
+fn normalize(index: &LineIndex, pos: Position) -> Position {
+    index.position_at(index.offset_at(pos).unwrap_or(usize::MAX))
+}
+
+// write a line of the source, with markers under its characters from the start column
+// to the end column, or to the end of the line if None
+fn marked_line(
+    out: &mut String,
+    index: &LineIndex,
+    width: usize,
+    line: usize,
+    start_col: usize,
+    end_col: Option<usize>,
+) {
+    let text = index.line_text(line).unwrap_or("");
+    let text = text.strip_suffix('\r').unwrap_or(text);
+    let byte_at = |col: usize| text.char_indices().nth(col).map_or(text.len(), |(i, _)| i);
+    let start = byte_at(start_col);
+    let end = end_col.map_or(text.len(), byte_at).max(start);
+    let marker_col = advance_col(0, &text[..start], TAB_WIDTH);
+    let markers = advance_col(marker_col, &text[start..end], TAB_WIDTH) - marker_col;
+
+    let _ = write!(out, "{:>w$} |", line, w = width);
+    if !text.is_empty() {
+        out.push(' ');
+        expand_tabs(out, text);
+    }
+    out.push('\n');
+    let _ = writeln!(
+        out,
+        "{:w$} | {}{}",
+        "",
+        " ".repeat(marker_col),
+        "^".repeat(markers.max(1)),
+        w = width
+    );
+}
+
+// write the text with the tabs replaced by spaces up to the next tab stop
+fn expand_tabs(out: &mut String, text: &str) {
+    let mut col = 0;
+    for c in text.chars() {
+        if c == '\t' {
+            let next = advance_col(col, "\t", TAB_WIDTH);
+            out.extend(std::iter::repeat_n(' ', next - col));
+            col = next;
+        } else {
+            out.push(c);
+            col += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(start: (usize, usize), end: (usize, usize)) -> Span {
+        let pos = |(line, col)| Position {
+            line,
+            col,
+            offset: 0,
+        };
+        Span {
+            start: pos(start),
+            end: pos(end),
+        }
+    }
+
+    #[test]
+    fn single_line() {
+        let source = "(define x 1)";
+        assert_eq!(
+            render_span(source, Span::on_line(1, 8, 9), "unknown x"),
+            "unknown x\n --> 1:8\n  |\n1 | (define x 1)\n  |         ^\n"
+        );
+        // empty span at the end of the source
+        assert_eq!(
+            render_span(source, Span::on_line(1, 12, 12), "unfinished"),
+            "unfinished\n --> 1:12\n  |\n1 | (define x 1)\n  |             ^\n"
+        );
+        // outside of the source
+        assert_eq!(
+            render_span(source, span((3, 0), (3, 4)), "eof"),
+            "eof\n --> 1:12\n  |\n1 | (define x 1)\n  |             ^\n"
+        );
+    }
+
+    #[test]
+    fn multi_line() {
+        let source = "(a\n  (b\n   c\n   d)\n e)";
+        let frame = render_span(source, span((2, 2), (4, 5)), "group");
+        assert_eq!(
+            frame,
+            "group\n --> 2:2\n  |\n2 |   (b\n  |   ^^\n  ...\n4 |    d)\n  | ^^^^^\n"
+        );
+        let frame = render_span(source, span((1, 0), (2, 4)), "group");
+        assert_eq!(
+            frame,
+            "group\n --> 1:0\n  |\n1 | (a\n  | ^^\n2 |   (b\n  | ^^^^\n"
+        );
+        // ending after the newline of the first line
+        let frame = render_span(source, span((1, 1), (2, 0)), "a");
+        assert_eq!(frame, "a\n --> 1:1\n  |\n1 | (a\n  |  ^\n");
+    }
+
+    #[test]
+    fn tabs_and_unicode() {
+        let source = "\t(é\tb)\r\n";
+        let frame = render_span(source, span((1, 4), (1, 5)), "b");
+        assert_eq!(frame, "b\n --> 1:4\n  |\n1 |     (é  b)\n  |         ^\n");
+        let frame = render_span(source, span((1, 1), (1, 6)), "group");
+        assert_eq!(
+            frame,
+            "group\n --> 1:1\n  |\n1 |     (é  b)\n  |     ^^^^^^\n"
+        );
+    }
+
+    #[test]
+    fn gutter_width() {
+        let source = "a\n".repeat(9) + "(b\n c)";
+        let frame = render_span(&source, span((10, 0), (11, 3)), "wide");
+        assert_eq!(
+            frame,
+            "wide\n  --> 10:0\n   |\n10 | (b\n   | ^^\n11 |  c)\n   | ^^^\n"
+        );
+    }
+}
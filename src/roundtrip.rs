@@ -0,0 +1,225 @@
+//! Round trip check of the parser and the canonical printer
+//!
+//! The input is parsed, each top level element is printed in its canonical form, and the
+//! printed output is parsed again to be compared with the elements of the input, ignoring
+//! the spans and the comments, which are not part of the canonical form. The atoms are compared
+//! by value, since the canonical form changes the base of the numbers, the separators and the
+//! escapes of the strings.
+
+use super::canonical::to_canonical_string;
+use super::loc::{offset, Span};
+use super::parser::{Element, Parser, ParserError, SpannedElement};
+use core::fmt;
+
+/// Mismatch found by [`check_roundtrip`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RoundtripMismatch {
+    /// The input doesn't parse
+    Input(ParserError),
+    /// The printed output doesn't parse
+    Output { printed: String, error: ParserError },
+    /// An element parsed from the printed output differs from the element of the input
+    ///
+    /// The path is the index of the element in each group from the top level, not counting
+    /// the comments. The spans and texts are missing on the side without an element at this path.
+    Element {
+        path: Vec<usize>,
+        input_span: Option<Span>,
+        input: String,
+        output_span: Option<Span>,
+        output: String,
+    },
+}
+
+impl fmt::Display for RoundtripMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RoundtripMismatch::Input(e) => write!(f, "the input doesn't parse: {}", e),
+            RoundtripMismatch::Output { printed, error } => {
+                write!(
+                    f,
+                    "the printed output doesn't parse: {}\n  output: {}",
+                    error, printed
+                )
+            }
+            RoundtripMismatch::Element {
+                path,
+                input_span,
+                input,
+                output_span,
+                output,
+            } => {
+                write!(f, "the elements differ at path {:?}", path)?;
+                match input_span {
+                    Some(span) => write!(f, "\n  input  ({}): {}", span, input)?,
+                    None => write!(f, "\n  input: no element")?,
+                }
+                match output_span {
+                    Some(span) => write!(f, "\n  output ({}): {}", span, output),
+                    None => write!(f, "\n  output: no element"),
+                }
+            }
+        }
+    }
+}
+
+impl std::error::Error for RoundtripMismatch {}
+
+/// Check that the input parses, prints in canonical form and parses back to the same elements
+///
+/// ```
+/// use s_expr::check_roundtrip;
+///
+/// assert_eq!(check_roundtrip("(let x 0x10) ; comment\n[\"a\\nb\" #00#]"), Ok(()));
+/// assert!(check_roundtrip("(let x").is_err());
+/// ```
+#[allow(clippy::result_large_err)]
+pub fn check_roundtrip(input: &str) -> Result<(), RoundtripMismatch> {
+    let elements = parse(input).map_err(RoundtripMismatch::Input)?;
+    let printed = elements
+        .iter()
+        .filter(|el| !is_comment(el))
+        .map(|el| to_canonical_string(&el.inner))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let reparsed = parse(&printed).map_err(|error| RoundtripMismatch::Output {
+        printed: printed.clone(),
+        error,
+    })?;
+    let mut path = Vec::new();
+    match first_mismatch(&elements, &reparsed, &mut path) {
+        None => Ok(()),
+        Some((input_el, output_el)) => Err(RoundtripMismatch::Element {
+            path,
+            input_span: input_el.map(|el| el.span),
+            input: input_el.map_or(String::new(), |el| slice(input, el.span).to_string()),
+            output_span: output_el.map(|el| el.span),
+            output: output_el.map_or(String::new(), |el| slice(&printed, el.span).to_string()),
+        }),
+    }
+}
+
+/// Check the round trip of the input like [`check_roundtrip`], for use in tests
+///
+/// # Panics
+///
+/// Panics with a description of the first mismatch if the round trip fails
+pub fn assert_roundtrip(input: &str) {
+    if let Err(e) = check_roundtrip(input) {
+        panic!("round trip failed: {}", e)
+    }
+}
+
+fn parse(data: &str) -> Result<Vec<SpannedElement<'_>>, ParserError> {
+    let mut parser = Parser::new(data);
+    let mut elements = Vec::new();
+    while let Some(el) = parser.next()? {
+        elements.push(el);
+    }
+    Ok(elements)
+}
+
+fn is_comment(el: &SpannedElement) -> bool {
+    matches!(el.inner, Element::Comment(_))
+}
+
+type Mismatch<'e, 'a> = (
+    Option<&'e SpannedElement<'a>>,
+    Option<&'e SpannedElement<'a>>,
+);
+
+// find the first pair of elements which differ, ignoring the comments, with its path
+fn first_mismatch<'e, 'a>(
+    input: &'e [SpannedElement<'a>],
+    output: &'e [SpannedElement<'a>],
+    path: &mut Vec<usize>,
+) -> Option<Mismatch<'e, 'a>> {
+    let mut input = input.iter().filter(|el| !is_comment(el));
+    let mut output = output.iter().filter(|el| !is_comment(el));
+    for i in 0.. {
+        let (e1, e2) = match (input.next(), output.next()) {
+            (None, None) => return None,
+            (Some(e1), Some(e2)) => (e1, e2),
+            (e1, e2) => {
+                path.push(i);
+                return Some((e1, e2));
+            }
+        };
+        path.push(i);
+        match (&e1.inner, &e2.inner) {
+            (Element::Atom(a1), Element::Atom(a2)) if a1 == a2 => {}
+            (Element::Group(grp1, c1), Element::Group(grp2, c2)) if grp1 == grp2 => {
+                if let Some(mismatch) = first_mismatch(c1, c2, path) {
+                    return Some(mismatch);
+                }
+            }
+            _ => return Some((Some(e1), Some(e2))),
+        }
+        path.pop();
+    }
+    None
+}
+
+// the text of the span
+fn slice(text: &str, span: Span) -> &str {
+    match (offset(text, span.start), offset(text, span.end)) {
+        (Some(start), Some(end)) => &text[start..end],
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "unicode")]
+    use crate::tests::{PROG1, PROG2};
+
+    #[test]
+    #[cfg(feature = "unicode")]
+    fn roundtrip() {
+        assert_roundtrip(PROG1);
+        assert_roundtrip(PROG2);
+        assert_roundtrip("");
+        assert_roundtrip("; only a comment");
+        assert_roundtrip("(a 0b1_01 0x0f 00.50 #AB_cd# \"\\u{1b}\\t\" (b [c {d}]))");
+        assert!(matches!(
+            check_roundtrip("(a]"),
+            Err(RoundtripMismatch::Input(
+                ParserError::UnbalancedMismatch { .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn mismatch() {
+        let input = "(a ; c\n (b 1 2) c)";
+        let output = "(a (b 1 3) c)";
+        let e1 = parse(input).unwrap();
+        let e2 = parse(output).unwrap();
+        let mut path = Vec::new();
+        let (m1, m2) = first_mismatch(&e1, &e2, &mut path).expect("mismatch");
+        assert_eq!(path, [0, 1, 2]);
+        assert_eq!(slice(input, m1.unwrap().span), "2");
+        assert_eq!(slice(output, m2.unwrap().span), "3");
+
+        let e2 = parse("(a (b 1 2))").unwrap();
+        let mut path = Vec::new();
+        let (m1, m2) = first_mismatch(&e1, &e2, &mut path).expect("mismatch");
+        assert_eq!(path, [0, 2]);
+        assert_eq!(slice(input, m1.unwrap().span), "c");
+        assert!(m2.is_none());
+
+        let e2 = parse("(a (b 1 2) c) d").unwrap();
+        let mut path = Vec::new();
+        let (m1, m2) = first_mismatch(&e1, &e2, &mut path).expect("mismatch");
+        assert_eq!(path, [1]);
+        assert!(m1.is_none());
+        assert_eq!(m2.unwrap().span, Span::on_line(1, 14, 15));
+    }
+
+    #[test]
+    #[should_panic(expected = "round trip failed: the input doesn't parse")]
+    fn assert_panics() {
+        assert_roundtrip("(a");
+    }
+}
@@ -0,0 +1,160 @@
+//! Proptest strategies generating random atoms, element trees and source text
+//!
+//! The generated atoms respect the rules of the tokenizer, so that they print and parse back
+//! to the same atoms, which allows to property test the code consuming the parsed elements.
+//!
+//! ```
+//! use proptest::prelude::*;
+//! use s_expr::{strategies, Parser};
+//!
+//! proptest!(|(source in strategies::source())| {
+//!     let mut parser = Parser::new(&source);
+//!     while let Some(_) = parser.next().unwrap() {}
+//! });
+//! ```
+
+use super::data::{ANumBase, AtomBuf, GroupKind};
+use super::ident::is_ident;
+use super::loc::{Span, Spanned};
+use super::parser::ElementOwned;
+use super::printer::{PrintConfig, Printer};
+use proptest::prelude::*;
+
+/// Strategy generating the valid idents
+pub fn ident() -> impl Strategy<Value = String> {
+    "[a-zA-Z_+*/<>=!?-][a-zA-Z0-9_+*/<>=!?.:-]{0,8}".prop_filter("valid ident", |s| is_ident(s))
+}
+
+/// Strategy generating the number bases
+pub fn base() -> impl Strategy<Value = ANumBase> {
+    prop_oneof![
+        Just(ANumBase::Binary),
+        Just(ANumBase::Decimal),
+        Just(ANumBase::Hexadecimal),
+    ]
+}
+
+/// Strategy generating the group kinds
+pub fn group_kind() -> impl Strategy<Value = GroupKind> {
+    prop_oneof![
+        Just(GroupKind::Paren),
+        Just(GroupKind::Brace),
+        Just(GroupKind::Bracket),
+    ]
+}
+
+/// Strategy generating the atoms of all the variants, with any content for the strings and the bytes
+pub fn atom_buf() -> impl Strategy<Value = AtomBuf> {
+    prop_oneof![
+        ident().prop_map(AtomBuf::Ident),
+        (any::<u128>(), base()).prop_map(|(v, base)| AtomBuf::integral_u128(v, base)),
+        ("[0-9]{1,8}", "[0-9]{1,8}").prop_map(|(integral, fractional)| AtomBuf::Decimal {
+            integral,
+            fractional
+        }),
+        any::<String>().prop_map(|s| AtomBuf::string(&s)),
+        prop::collection::vec(any::<u8>(), 0..16).prop_map(|bytes| AtomBuf::bytes(&bytes)),
+    ]
+}
+
+/// Strategy generating the element trees, with groups nested up to `depth` levels and
+/// about `size` elements in total
+///
+/// The spans of the generated elements are [`Span::DUMMY`], as the elements don't come from a source.
+pub fn element_owned(depth: u32, size: u32) -> impl Strategy<Value = ElementOwned> {
+    let leaf = prop_oneof![
+        4 => atom_buf().prop_map(ElementOwned::Atom),
+        1 => "[ -~]{0,16}".prop_map(|text| ElementOwned::Comment(format!(";{}", text))),
+    ];
+    leaf.prop_recursive(depth, size, 8, |inner| {
+        (group_kind(), prop::collection::vec(inner, 0..8)).prop_map(|(grp, elements)| {
+            let elements = elements
+                .into_iter()
+                .map(|inner| Spanned::new(Span::DUMMY, inner))
+                .collect();
+            ElementOwned::Group(grp, elements)
+        })
+    })
+}
+
+/// Strategy generating syntactically valid source text, made of a few element trees
+/// printed either flat or pretty
+pub fn source() -> impl Strategy<Value = String> {
+    (
+        prop::collection::vec(element_owned(4, 32), 0..4),
+        any::<bool>(),
+    )
+        .prop_map(|(elements, pretty)| {
+            let mut p = Printer::new_with_config(PrintConfig::default().pretty(pretty));
+            for el in elements.iter() {
+                p.element(&el.to_element());
+                p.newline();
+            }
+            p.to_string()
+        })
+}
+
+impl Arbitrary for AtomBuf {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        atom_buf().boxed()
+    }
+}
+
+impl Arbitrary for ElementOwned {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        element_owned(4, 64).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Element, Parser};
+    use crate::printer::to_string;
+    use crate::tokenizer::Tokenizer;
+
+    fn parse(source: &str) -> Vec<Element<'_>> {
+        let mut parser = Parser::new(source);
+        let mut elements = Vec::new();
+        while let Some(el) = parser.next().expect("element") {
+            elements.push(el.inner);
+        }
+        elements
+    }
+
+    proptest! {
+        #[test]
+        fn atom_parses_back(atom in any::<AtomBuf>()) {
+            let printed = atom.to_string();
+            let elements = parse(&printed);
+            prop_assert_eq!(elements.len(), 1);
+            prop_assert!(elements[0].atom().is_some_and(|a| a.raw_eq(&atom.as_atom())));
+        }
+
+        #[test]
+        fn tree_parses_back(el in any::<ElementOwned>()) {
+            let el = el.to_element();
+            let printed = to_string(&el);
+            let elements = parse(&printed);
+            prop_assert_eq!(elements.len(), 1);
+            prop_assert!(elements[0].structural_eq(&el), "{}", printed);
+        }
+
+        #[test]
+        fn source_parses(source in source()) {
+            parse(&source);
+        }
+
+        #[test]
+        fn tokenizer_never_panics(text in any::<String>()) {
+            let mut tokenizer = Tokenizer::new(&text);
+            while let Ok(Some(_)) = tokenizer.next() {}
+        }
+    }
+}
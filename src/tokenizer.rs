@@ -1,21 +1,27 @@
 use super::data::*;
+use super::escape::EscapeError;
+use super::ident::{is_id_continue, is_id_start, is_ident_with};
 use super::loc::{Position, Span, Spanned};
 use super::utf8::{next_char, MovementInBytes, NextCharError};
-
-#[cfg(feature = "unicode")]
-use unicode_xid::UnicodeXID;
+use core::fmt;
+use std::borrow::Cow;
 
 /// Config for the tokenizer, for flags
 #[derive(Debug, Clone)]
 pub struct TokenizerConfig {
     /// Tokenize the comment
     filter_comment: bool,
-    /// Add support for the bytes token, which is of the format `#<hexadecimal>#`. Default is set to true
+    /// Add support for the bytes token, which is of the format `#<hexadecimal>#`, with optional `_` separators.
+    /// Default is set to true
     support_bytes: bool,
     /// Add support for the { } group, Default is set to true
     support_brace: bool,
     /// Add support for the [ ] group, Default is set to true
     support_bracket: bool,
+    /// Reject the strings with invalid escapes. Default is set to false
+    strict_escapes: bool,
+    /// Accept the CRLF line endings. Default is set to false
+    crlf: bool,
 }
 
 impl Default for TokenizerConfig {
@@ -25,6 +31,8 @@ impl Default for TokenizerConfig {
             support_bytes: true,
             support_bracket: true,
             support_brace: true,
+            strict_escapes: false,
+            crlf: false,
         }
     }
 }
@@ -53,6 +61,39 @@ impl TokenizerConfig {
         self.support_bytes = supported;
         self
     }
+
+    /// Reject the strings containing an invalid escape with [`TokenError::InvalidEscape`], or accept
+    /// any character after a backslash
+    ///
+    /// The valid escapes are the escapes decoded by [`AStr::try_decoded`](crate::AStr::try_decoded).
+    pub fn strict_escapes(mut self, enabled: bool) -> Self {
+        self.strict_escapes = enabled;
+        self
+    }
+
+    /// Accept the `\r` of the CRLF line endings as whitespace, and strip it from the end of
+    /// the comments, or reject it as any unexpected character
+    ///
+    /// ```
+    /// use s_expr::{Token, Tokenizer, TokenizerConfig};
+    ///
+    /// let cfg = TokenizerConfig::default().crlf(true);
+    /// let mut tokenizer = Tokenizer::new_with_config("; a\r\nb", cfg);
+    /// let comment = tokenizer.next().unwrap().unwrap();
+    /// assert!(matches!(comment.inner, Token::Comment("; a")));
+    /// let mut lf_only = Tokenizer::new("a\r\nb");
+    /// lf_only.next().unwrap();
+    /// assert!(lf_only.next().is_err());
+    /// ```
+    pub fn crlf(mut self, enabled: bool) -> Self {
+        self.crlf = enabled;
+        self
+    }
+
+    // check if the text is tokenized back as this exact ident with this config
+    pub(crate) fn is_ident(&self, s: &str) -> bool {
+        is_ident_with(s, self.support_bytes)
+    }
 }
 
 /// Tokenizer state on the data
@@ -81,23 +122,69 @@ pub enum Token<'a> {
 
 impl<'a> Token<'a> {
     pub fn is_comment(&self) -> bool {
-        match self {
-            Token::Comment(_) => true,
-            _ => false,
-        }
+        matches!(self, Token::Comment(_))
     }
 }
 
 /// A Token with the span (start and end positions) associated
 pub type SpannedToken<'a> = Spanned<Token<'a>>;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum TokenError {
     DataError(NextCharError, usize),
     UnterminatedString(Position),
     UnterminatedBytes(Position),
-    UnprocessedChar(char),
+    /// Character which doesn't start any token, at its position
+    UnprocessedChar(Position, char),
     UnterminatedBytesChar(Position, char),
+    /// Invalid escape in a string, at the position of its backslash, with strict escapes
+    InvalidEscape(Position, EscapeError),
+}
+
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenError::DataError(e, offset) => {
+                write!(f, "invalid data at the byte offset {}: {}", offset, e)
+            }
+            TokenError::UnterminatedString(pos) => write!(
+                f,
+                "{}: unterminated string, missing the closing `\"`",
+                pos.display_human()
+            ),
+            TokenError::UnterminatedBytes(pos) => write!(
+                f,
+                "{}: unterminated bytes, missing the closing `#`",
+                pos.display_human()
+            ),
+            TokenError::UnprocessedChar(pos, ch) => {
+                write!(f, "{}: unexpected character {:?}", pos.display_human(), ch)
+            }
+            TokenError::UnterminatedBytesChar(pos, ch) => write!(
+                f,
+                "{}: unexpected character {:?} in bytes, expected hexadecimal digits or `#`",
+                pos.display_human(),
+                ch
+            ),
+            TokenError::InvalidEscape(pos, e) => write!(
+                f,
+                "{}: invalid escape `{}`: {}",
+                pos.display_human(),
+                e.sequence(),
+                e.kind()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TokenError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TokenError::DataError(e, _) => Some(e),
+            TokenError::InvalidEscape(_, e) => Some(e),
+            _ => None,
+        }
+    }
 }
 
 impl<'a> Tokenizer<'a> {
@@ -121,7 +208,27 @@ impl<'a> Tokenizer<'a> {
         }
     }
 
+    /// Create a new tokenizer from the data stream embedded in a document at the base position,
+    /// the positions of the tokens and the errors being the positions in the document
+    ///
+    /// The positions are the ones translated with [`Position::offset_by`].
+    pub fn new_at(data: &'a str, base: Position) -> Self {
+        Self::new_with_config_at(data, TokenizerConfig::default(), base)
+    }
+
+    /// Create a new tokenizer from the data stream embedded in a document at the base position,
+    /// with an associated config
+    pub fn new_with_config_at(data: &'a str, cfg: TokenizerConfig, base: Position) -> Self {
+        Tokenizer {
+            data: data.as_bytes(),
+            index: TokDataPos(0),
+            position: base,
+            cfg,
+        }
+    }
+
     /// Return the next token, or none if reach the end of stream
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Result<Option<SpannedToken<'a>>, TokenError> {
         // note that the tokenizer only take `str` type, so that the content is always invalid,
         // short of an internal error, so all the .expect should not never trigger except on a
@@ -137,7 +244,7 @@ impl<'a> Tokenizer<'a> {
                     self.move_index(advance);
                     let tok = self.next_cont(token_start, position_start, leading_char)?;
                     // if it's a comment, and we filter comment, we don't return
-                    if !tok.inner.is_comment() {
+                    if !tok.is_comment() {
                         return Ok(Some(tok));
                     } else {
                         if !self.cfg.filter_comment {
@@ -170,7 +277,7 @@ impl<'a> Tokenizer<'a> {
             match self.peek_char()? {
                 None => return Ok(()),
                 Some((ch, advance)) => {
-                    if !"\n\t ".contains(ch) {
+                    if !("\n\t ".contains(ch) || (self.cfg.crlf && ch == '\r')) {
                         return Ok(());
                     }
                     self.position.advance(ch);
@@ -220,7 +327,7 @@ impl<'a> Tokenizer<'a> {
 
     fn bytes(&mut self) -> Result<ABytes<'a>, TokenError> {
         let position_start = self.index;
-        self.skip_while(|c| c.is_ascii_hexdigit())?;
+        self.skip_while(|c| c.is_ascii_hexdigit() || c == '_')?;
         match self.peek_char()? {
             None => Err(TokenError::UnterminatedBytes(self.position)),
             Some((ch, advance)) => {
@@ -231,9 +338,9 @@ impl<'a> Tokenizer<'a> {
                     self.position.advance(ch);
                     self.move_index(advance);
 
-                    return Ok(ABytes(dat));
+                    Ok(ABytes(dat))
                 } else {
-                    return Err(TokenError::UnterminatedBytesChar(self.position, ch));
+                    Err(TokenError::UnterminatedBytesChar(self.position, ch))
                 }
             }
         }
@@ -250,7 +357,7 @@ impl<'a> Tokenizer<'a> {
                 let dat = self.slice_from(position_start);
                 Ok(ANum {
                     base: ANumBase::Decimal,
-                    dat: dat,
+                    dat,
                 })
             }
             Some((ch, advance)) => {
@@ -281,7 +388,8 @@ impl<'a> Tokenizer<'a> {
                             base: ANumBase::Hexadecimal,
                             dat: self.slice_from(position_start),
                         })
-                    } else if ch.is_ascii_digit() {
+                    } else if ch.is_ascii_digit() || ch == '_' {
+                        // a `_` separator can follow the first digit, e.g. `0_1`
                         self.position.advance(ch);
                         self.move_index(advance);
 
@@ -294,11 +402,12 @@ impl<'a> Tokenizer<'a> {
                         let dat = self.slice_from(position_start);
                         Ok(ANum {
                             base: ANumBase::Decimal,
-                            dat: dat,
+                            dat,
                         })
                     }
                 } else {
-                    if ch.is_ascii_digit() {
+                    // a `_` separator can follow the first digit, e.g. `1_000`
+                    if ch.is_ascii_digit() || ch == '_' {
                         self.position.advance(ch);
                         self.move_index(advance);
 
@@ -311,7 +420,7 @@ impl<'a> Tokenizer<'a> {
                         let dat = self.slice_from(position_start);
                         Ok(ANum {
                             base: ANumBase::Decimal,
-                            dat: dat,
+                            dat,
                         })
                     }
                 }
@@ -344,7 +453,7 @@ impl<'a> Tokenizer<'a> {
 
                             return Ok(AStr {
                                 has_escape,
-                                raw_data: dat,
+                                raw_data: Cow::Borrowed(dat),
                             });
                         }
                     }
@@ -378,26 +487,36 @@ impl<'a> Tokenizer<'a> {
         // * number : '0'..'9'
         // * identifier : anything else
 
-        if leading_char == '(' {
-            stok(self.position, Token::Left(GroupKind::Paren))
-        } else if leading_char == ')' {
-            stok(self.position, Token::Right(GroupKind::Paren))
-        } else if self.cfg.support_bracket && leading_char == '[' {
-            stok(self.position, Token::Left(GroupKind::Bracket))
-        } else if self.cfg.support_bracket && leading_char == ']' {
-            stok(self.position, Token::Right(GroupKind::Bracket))
-        } else if self.cfg.support_brace && leading_char == '{' {
-            stok(self.position, Token::Left(GroupKind::Brace))
-        } else if self.cfg.support_brace && leading_char == '}' {
-            stok(self.position, Token::Right(GroupKind::Brace))
+        let supported = |grp: &GroupKind| match grp {
+            GroupKind::Paren => true,
+            GroupKind::Bracket => self.cfg.support_bracket,
+            GroupKind::Brace => self.cfg.support_brace,
+        };
+        if let Some(grp) = GroupKind::from_open_char(leading_char).filter(supported) {
+            stok(self.position, Token::Left(grp))
+        } else if let Some(grp) = GroupKind::from_close_char(leading_char).filter(supported) {
+            stok(self.position, Token::Right(grp))
         } else if leading_char == ';' {
             // comment
             self.skip_until(|c| c == '\n')?;
-            let comment = self.slice_from(position_start);
+            let mut comment = self.slice_from(position_start);
+            if self.cfg.crlf {
+                // the carriage return of a CRLF line ending is not part of the comment
+                comment = comment.strip_suffix('\r').unwrap_or(comment);
+            }
             stok(self.position, Token::Comment(comment))
         } else if leading_char == '"' {
             // string
             let astr = self.string()?;
+            if self.cfg.strict_escapes && astr.has_escape {
+                if let Err(e) = astr.try_decoded() {
+                    let span = Span {
+                        start: token_start,
+                        end: self.position,
+                    };
+                    return Err(TokenError::InvalidEscape(e.position(astr.raw(), span), e));
+                }
+            }
             stok(self.position, Token::Atom(Atom::String(astr)))
         } else if self.cfg.support_bytes && leading_char == '#' {
             // byte stream
@@ -431,45 +550,11 @@ impl<'a> Tokenizer<'a> {
                 stok(self.position, Token::Atom(Atom::Integral(anum)))
             }
         } else if is_id_start(leading_char) {
-            self.skip_while(|c| is_id_continue(c))?;
+            self.skip_while(is_id_continue)?;
             let ident = self.slice_from(position_start);
             stok(self.position, Token::Atom(Atom::Ident(ident)))
         } else {
-            Err(TokenError::UnprocessedChar(leading_char))
+            Err(TokenError::UnprocessedChar(token_start, leading_char))
         }
     }
 }
-
-fn is_id_start(ch: char) -> bool {
-    #[cfg(feature = "unicode")]
-    {
-        ch.is_xid_start()
-            || ch == '_'
-            || is_ascii_operator(ch)
-            || crate::utf8::extended_math_operator(ch)
-    }
-    #[cfg(not(feature = "unicode"))]
-    {
-        ch.is_ascii_alphabetic() || ch == '_' || is_ascii_operator(ch)
-    }
-}
-
-fn is_id_continue(ch: char) -> bool {
-    #[cfg(feature = "unicode")]
-    {
-        ch.is_xid_continue()
-            || ch == '_'
-            || ch.is_ascii_digit()
-            || is_ascii_operator(ch)
-            || crate::utf8::extended_math_operator(ch)
-    }
-    #[cfg(not(feature = "unicode"))]
-    {
-        ch.is_ascii_alphabetic() || ch == '_' || ch.is_ascii_digit() || is_ascii_operator(ch)
-    }
-}
-
-fn is_ascii_operator(ch: char) -> bool {
-    // any ascii operator except: [] {} () " ; \\
-    "?!#@$+-*/=<>,.:|%^&~'`".contains(ch)
-}
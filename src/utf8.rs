@@ -41,7 +41,7 @@ pub(crate) const fn extended_math_alphanumeric(ch: char) -> bool {
     (c >= 0x1D400 && c <= 0x1D7FF)
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum NextCharError {
     EmptyDataStream,
     IncompleteUtf8Sequence(u8),
@@ -49,6 +49,23 @@ pub enum NextCharError {
     InvalidUtf8ContByte,
 }
 
+impl core::fmt::Display for NextCharError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            NextCharError::EmptyDataStream => f.write_str("empty data stream"),
+            NextCharError::IncompleteUtf8Sequence(leading) => write!(
+                f,
+                "incomplete UTF-8 sequence starting with the byte 0x{:02x}",
+                leading
+            ),
+            NextCharError::InvalidUtf8Sequence => f.write_str("invalid UTF-8 sequence"),
+            NextCharError::InvalidUtf8ContByte => f.write_str("invalid UTF-8 continuation byte"),
+        }
+    }
+}
+
+impl std::error::Error for NextCharError {}
+
 #[derive(Clone, Copy)]
 pub(crate) struct MovementInBytes(pub(crate) usize);
 